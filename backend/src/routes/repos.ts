@@ -438,7 +438,10 @@ router.put("/:owner/:repo", async (req: Request, res: Response) => {
     try {
       const registerResponse = await fetch(`${config.shadeAgentUrl}/api/repo/register`, {
         method: "POST",
-        headers: { "Content-Type": "application/json" },
+        headers: {
+          "Content-Type": "application/json",
+          "x-agent-secret": config.maintainerSecret || "",
+        },
         body: JSON.stringify({
           repo: fullName,
           maintainerNearId: nearWallet,
@@ -699,11 +702,11 @@ router.post("/:owner/:repo/fund", async (req: Request, res: Response) => {
       instructions: {
         method: "fund_bounty",
         contractId: contractId,
-        args: { repo_id: fullName },
-        deposit: "Amount in NEAR you want to deposit",
+        args: { repo_id: fullName, amount: "Amount in yoctoNEAR you want to fund" },
+        deposit: "The amount in NEAR plus 0.01 NEAR for storage, the unused part is refunded",
         maintainerAccount: maintainerAccount,
       },
-      cliExample: `near call ${contractId} fund_bounty '{"repo_id": "${fullName}"}' --accountId ${maintainerAccount} --deposit 10`,
+      cliExample: `near call ${contractId} fund_bounty '{"repo_id": "${fullName}", "amount": "10000000000000000000000000"}' --accountId ${maintainerAccount} --deposit 10.01`,
     });
   } catch (err) {
     console.error("Fund endpoint error:", err);
@@ -220,7 +220,7 @@ export default function DashboardPage() {
                 onClick={() => {
                   const cmd = repos
                     .filter(r => (Number(r.bountyBalance) || 0) < 5)
-                    .map(r => `near call holy_contract.testnet fund_bounty '{"repo_id": "${r.fullName}"}' --accountId ${r.nearWallet || 'YOUR_ACCOUNT'} --deposit 10`)
+                    .map(r => `near call holy_contract.testnet fund_bounty '{"repo_id": "${r.fullName}", "amount": "10000000000000000000000000"}' --accountId ${r.nearWallet || 'YOUR_ACCOUNT'} --deposit 10.01`)
                     .join(' && \\\n');
                   navigator.clipboard.writeText(cmd);
                   setCopied(true);
@@ -236,7 +236,7 @@ export default function DashboardPage() {
                 <pre style={{ fontSize: 11, color: '#8b5cf6', margin: 0, lineHeight: 1.6 }}>
                   {repos
                     .filter(r => (Number(r.bountyBalance) || 0) < 5)
-                    .map(r => `near call holy_contract.testnet fund_bounty '{"repo_id": "${r.fullName}"}' --accountId ${r.nearWallet || 'YOUR_ACCOUNT'} --deposit 10`)
+                    .map(r => `near call holy_contract.testnet fund_bounty '{"repo_id": "${r.fullName}", "amount": "10000000000000000000000000"}' --accountId ${r.nearWallet || 'YOUR_ACCOUNT'} --deposit 10.01`)
                     .join(' && \\\n')}
                 </pre>
               ) : (
@@ -27,10 +27,11 @@ impl Contract {
                 report_data_bytes[..32].copy_from_slice(&account_id_bytes);
                 let expected_report_data = ReportData::from(report_data_bytes);
 
-                // Convert measurements to Vec and convert to FullMeasurements
+                // Only active measurements are accepted, deprecated and revoked ones block new attestations
                 let expected_measurements: Vec<FullMeasurements> = self
                     .approved_measurements
                     .iter()
+                    .filter(|m| self.measurement_status(m) == Some(MeasurementStatus::Active))
                     .cloned()
                     .map(Into::into)
                     .collect();
@@ -62,7 +63,8 @@ impl Contract {
                 );
                 let default_measurements = create_mock_full_measurements_hex();
                 require!(
-                    self.approved_measurements.contains(&default_measurements),
+                    self.measurement_status(&default_measurements)
+                        == Some(MeasurementStatus::Active),
                     "Default measurements must be approved for local mode"
                 );
                 require!(
@@ -0,0 +1,486 @@
+use crate::internal::sponsors::is_sponsor_refundable;
+use crate::*;
+
+// Gas for the callbacks that resolve bounty payouts
+pub(crate) const PAYOUT_CALLBACK_GAS: Gas = Gas::from_tgas(10);
+
+// Each share of a split payout is a separate transfer, so their number is capped to fit in gas
+const MAX_PAYOUT_SHARES: usize = 5;
+
+pub(crate) const TOTAL_BASIS_POINTS: u16 = 10_000;
+
+// Turn basis point shares into amounts, rounding dust goes to the first share
+pub(crate) fn split_amount(shares: &[PayoutShare], amount: u128) -> Vec<PayoutRecipient> {
+    require!(!shares.is_empty(), "At least one share is required");
+    require!(shares.len() <= MAX_PAYOUT_SHARES, "Too many shares");
+    require!(
+        shares
+            .iter()
+            .map(|share| share.basis_points as u32)
+            .sum::<u32>()
+            == TOTAL_BASIS_POINTS as u32,
+        "Shares must sum to 10000 basis points"
+    );
+
+    let mut recipients: Vec<PayoutRecipient> = Vec::with_capacity(shares.len());
+    for share in shares {
+        require!(
+            recipients
+                .iter()
+                .all(|recipient| recipient.account_id != share.account_id),
+            "Duplicate share recipient"
+        );
+        recipients.push(PayoutRecipient {
+            account_id: share.account_id.clone(),
+            basis_points: share.basis_points,
+            amount: amount * share.basis_points as u128 / TOTAL_BASIS_POINTS as u128,
+        });
+    }
+    let dust = amount
+        - recipients
+            .iter()
+            .map(|recipient| recipient.amount)
+            .sum::<u128>();
+    recipients[0].amount += dust;
+    require!(
+        recipients.iter().all(|recipient| recipient.amount > 0),
+        "Every share must receive a positive amount"
+    );
+    recipients
+}
+
+impl Contract {
+    // Get the role of an account in a repo, the primary maintainer is always an admin
+    pub(crate) fn internal_repo_role(
+        &self,
+        repo_id: &String,
+        account_id: &AccountId,
+    ) -> Option<MaintainerRole> {
+        let maintainer = self
+            .repo_maintainers
+            .get(repo_id)
+            .expect("Repo not registered");
+        if account_id == maintainer {
+            return Some(MaintainerRole::Admin);
+        }
+        self.repo_members.get(repo_id).and_then(|members| {
+            members
+                .iter()
+                .find(|member| &member.account_id == account_id)
+                .map(|member| member.role)
+        })
+    }
+
+    // Require the account to have one of the given roles in the repo
+    pub(crate) fn require_repo_role(
+        &self,
+        repo_id: &String,
+        account_id: &AccountId,
+        roles: &[MaintainerRole],
+        message: &str,
+    ) {
+        let role = self.internal_repo_role(repo_id, account_id);
+        require!(role.is_some_and(|role| roles.contains(&role)), message);
+    }
+
+    // Require the caller to be the primary maintainer of the repo
+    pub(crate) fn require_primary_maintainer(&self, repo_id: &String) {
+        let maintainer = self
+            .repo_maintainers
+            .get(repo_id)
+            .expect("Repo not registered");
+        require!(
+            env::predecessor_account_id() == *maintainer,
+            "Caller is not the primary maintainer"
+        );
+    }
+
+    // Get the pool balance of a repo, in NEAR if no token is given
+    pub(crate) fn internal_pool_balance(
+        &self,
+        repo_id: &String,
+        token_id: &Option<AccountId>,
+    ) -> u128 {
+        match token_id {
+            None => *self.bounties.get(repo_id).unwrap_or(&0),
+            Some(token_id) => *self
+                .ft_bounties
+                .get(&(repo_id.clone(), token_id.clone()))
+                .unwrap_or(&0),
+        }
+    }
+
+    pub(crate) fn internal_set_pool_balance(
+        &mut self,
+        repo_id: String,
+        token_id: Option<AccountId>,
+        balance: u128,
+    ) {
+        match token_id {
+            None => {
+                self.bounties.insert(repo_id, balance);
+            }
+            Some(token_id) => {
+                self.ft_bounties.insert((repo_id, token_id), balance);
+            }
+        }
+    }
+
+    pub(crate) fn internal_credit_pool(
+        &mut self,
+        repo_id: String,
+        token_id: Option<AccountId>,
+        amount: u128,
+    ) {
+        let balance = self.internal_pool_balance(&repo_id, &token_id);
+        self.internal_set_pool_balance(repo_id, token_id, balance + amount);
+    }
+
+    // Get the part of a repo pool given by sponsors, in NEAR if no token is given
+    pub(crate) fn internal_sponsored_pool_balance(
+        &self,
+        repo_id: &str,
+        token_id: &Option<AccountId>,
+    ) -> u128 {
+        self.sponsored_pool_balances
+            .get(&(repo_id.to_string(), token_id.clone()))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub(crate) fn internal_credit_sponsored_pool(
+        &mut self,
+        repo_id: String,
+        token_id: Option<AccountId>,
+        amount: u128,
+    ) {
+        let balance = self.internal_sponsored_pool_balance(&repo_id, &token_id);
+        self.sponsored_pool_balances
+            .insert((repo_id, token_id), balance + amount);
+    }
+
+    // Take funds out of a repo pool, the funds that were not given by sponsors are used first
+    // Returns the part taken from the sponsored funds
+    pub(crate) fn internal_debit_pool(
+        &mut self,
+        repo_id: String,
+        token_id: Option<AccountId>,
+        amount: u128,
+    ) -> u128 {
+        let balance = self.internal_pool_balance(&repo_id, &token_id);
+        require!(balance >= amount, "Insufficient bounty funds");
+        let sponsored = self.internal_sponsored_pool_balance(&repo_id, &token_id);
+        let from_sponsors = amount.saturating_sub(balance.saturating_sub(sponsored));
+        if from_sponsors > 0 {
+            let key = (repo_id.clone(), token_id.clone());
+            if sponsored > from_sponsors {
+                self.sponsored_pool_balances
+                    .insert(key, sponsored - from_sponsors);
+            } else {
+                self.sponsored_pool_balances.remove(&key);
+            }
+        }
+        self.internal_set_pool_balance(repo_id, token_id, balance - amount);
+        from_sponsors
+    }
+
+    // Claim an open bounty for a merged PR and record the payout
+    // The bounty keeps its whole escrow until the payout is made, see internal_settle_bounty
+    // Recipients is empty when the amount is held for a GitHub user
+    // An amount above the repo threshold leaves the bounty PendingApproval instead of Claimed,
+    // the caller must not pay it out then
+    // The payout counts towards the rate limits of the calling agent and of the repo
+    // Returns the claimed bounty and the amount returned to the pool
+    pub(crate) fn internal_claim_bounty(
+        &mut self,
+        bounty_id: BountyId,
+        amount: u128,
+        recipients: &[PayoutRecipient],
+        github_user_id: Option<GithubUserId>,
+        pr_number: u64,
+        merge_commit_sha: &String,
+    ) -> (Bounty, u128) {
+        let mut bounty = self.internal_get_bounty(bounty_id);
+        let payout_key = (bounty.repo_id.clone(), pr_number);
+        if let Some(payout) = self.payouts.get(&payout_key) {
+            require!(
+                &payout.merge_commit_sha != merge_commit_sha,
+                "Payout already processed for this merge commit"
+            );
+            env::panic_str("Payout already processed for this PR");
+        }
+        require!(bounty.status == BountyStatus::Open, "Bounty is not open");
+        require!(amount > 0, "Amount must be positive");
+        require!(bounty.amount >= amount, "Insufficient bounty funds");
+        self.internal_record_payout_usage(&bounty.repo_id, &bounty.token_id, amount);
+        let remainder = bounty.amount - amount;
+
+        self.payouts.insert(
+            payout_key.clone(),
+            Payout {
+                bounty_id,
+                merge_commit_sha: merge_commit_sha.clone(),
+                recipients: recipients.to_vec(),
+                github_user_id,
+                token_id: bounty.token_id.clone(),
+                amount,
+                paid_at_ms: block_timestamp_ms(),
+                released_by: env::predecessor_account_id(),
+            },
+        );
+
+        // The bounty stays claimed until the transfers resolve
+        bounty.status = BountyStatus::Claimed;
+        bounty.updated_at_ms = block_timestamp_ms();
+
+        let threshold = self
+            .payout_thresholds
+            .get(&(payout_key.0, bounty.token_id.clone()));
+        if threshold.is_some_and(|threshold| amount > *threshold) {
+            let expires_at_ms = block_timestamp_ms() + self.payout_approval_timeout_ms;
+            Event::PayoutPendingApproval {
+                bounty_id,
+                repo_id: &bounty.repo_id,
+                number: bounty.number,
+                token_id: &bounty.token_id,
+                amount: U128(amount),
+                returned_to_pool: U128(remainder),
+                expires_at_ms: U64::from(expires_at_ms),
+                pr_number,
+                merge_commit_sha,
+            }
+            .emit();
+            bounty.status = BountyStatus::PendingApproval;
+            self.pending_payouts.insert(
+                bounty_id,
+                PendingPayout {
+                    pr_number,
+                    requested_at_ms: block_timestamp_ms(),
+                    expires_at_ms,
+                },
+            );
+        }
+        self.bounty_records.insert(bounty_id, bounty.clone());
+        (bounty, remainder)
+    }
+
+    // Drop a pending payout and reopen its bounty, its escrow was not touched by the claim
+    // The payout no longer counts towards the rate limits
+    // Returns the reopened bounty and the PR of the dropped payout
+    pub(crate) fn internal_cancel_pending_payout(&mut self, bounty_id: BountyId) -> (Bounty, u64) {
+        let mut bounty = self.internal_get_bounty(bounty_id);
+        require!(
+            bounty.status == BountyStatus::PendingApproval,
+            "Payout is not pending approval"
+        );
+        let pending = self.pending_payouts.remove(&bounty_id).unwrap();
+        if let Some(payout) = self
+            .payouts
+            .remove(&(bounty.repo_id.clone(), pending.pr_number))
+        {
+            self.internal_release_payout_usage(&bounty.repo_id, &payout);
+        }
+
+        bounty.status = BountyStatus::Open;
+        bounty.updated_at_ms = block_timestamp_ms();
+        self.bounty_records.insert(bounty_id, bounty.clone());
+        (bounty, pending.pr_number)
+    }
+
+    // Cancel a pending payout nobody approved in time
+    pub(crate) fn internal_expire_pending_payout(&mut self, bounty_id: BountyId) {
+        let (bounty, pr_number) = self.internal_cancel_pending_payout(bounty_id);
+        Event::PendingPayoutExpired {
+            bounty_id,
+            repo_id: &bounty.repo_id,
+            pr_number,
+        }
+        .emit();
+    }
+
+    // Mark a claimed bounty as paid once the paid amount has left its escrow
+    // Sponsors get their pro-rata share of the rest of the escrow as a refund, what is left
+    // goes back to the repo pool
+    // Returns the amount returned to the pool
+    pub(crate) fn internal_settle_bounty(
+        &mut self,
+        bounty_id: BountyId,
+        bounty: &mut Bounty,
+        paid: u128,
+    ) -> u128 {
+        let remainder = bounty.amount - paid;
+        let refunded =
+            self.internal_refund_contributions(bounty_id, bounty, remainder, is_sponsor_refundable);
+        bounty.amount = paid;
+        bounty.status = BountyStatus::Paid;
+        bounty.updated_at_ms = block_timestamp_ms();
+        remainder - refunded
+    }
+
+    // Transfer each recipient its amount, on_bounty_released gets one result per recipient
+    pub(crate) fn internal_pay_out(
+        &self,
+        bounty_id: BountyId,
+        token_id: &Option<AccountId>,
+        recipients: Vec<PayoutRecipient>,
+        pr_number: u64,
+    ) -> Promise {
+        let transfers = recipients
+            .iter()
+            .map(|recipient| {
+                self.internal_transfer(token_id, recipient.account_id.clone(), recipient.amount)
+            })
+            .reduce(Promise::and)
+            .unwrap();
+        let recipients = recipients
+            .into_iter()
+            .map(|recipient| (recipient.account_id, U128(recipient.amount)))
+            .collect();
+
+        transfers.then(
+            Self::ext(env::current_account_id())
+                .with_static_gas(PAYOUT_CALLBACK_GAS)
+                .on_bounty_released(bounty_id, recipients, pr_number),
+        )
+    }
+
+    pub(crate) fn internal_get_bounty(&self, bounty_id: BountyId) -> Bounty {
+        self.bounty_records
+            .get(&bounty_id)
+            .expect("Bounty not found")
+            .clone()
+    }
+
+    // Add funds to an open bounty and record the contribution
+    // from_pool is set when the funds were taken out of the repo pool
+    pub(crate) fn internal_fund_bounty(
+        &mut self,
+        bounty_id: BountyId,
+        funder: &AccountId,
+        amount: u128,
+        from_pool: bool,
+        sponsor: bool,
+    ) {
+        let mut bounty = self.internal_get_bounty(bounty_id);
+        bounty.amount += amount;
+        bounty.updated_at_ms = block_timestamp_ms();
+
+        Event::BountyFunded {
+            repo_id: &bounty.repo_id,
+            bounty_id: Some(bounty_id),
+            number: Some(bounty.number),
+            token_id: &bounty.token_id,
+            funder,
+            amount: U128(amount),
+            from_pool,
+            sponsor,
+        }
+        .emit();
+        self.bounty_records.insert(bounty_id, bounty);
+        self.internal_record_contribution(
+            bounty_id,
+            Contribution {
+                account_id: funder.clone(),
+                amount,
+                from_pool,
+                sponsor,
+            },
+        );
+    }
+
+    // Add funds to the pool of a repo, what sponsors give can only be spent on bounties
+    pub(crate) fn internal_fund_pool(
+        &mut self,
+        repo_id: String,
+        token_id: Option<AccountId>,
+        funder: &AccountId,
+        amount: u128,
+        sponsor: bool,
+    ) {
+        Event::BountyFunded {
+            repo_id: &repo_id,
+            bounty_id: None,
+            number: None,
+            token_id: &token_id,
+            funder,
+            amount: U128(amount),
+            from_pool: false,
+            sponsor,
+        }
+        .emit();
+        if sponsor {
+            let key = (repo_id.clone(), token_id.clone(), funder.clone());
+            let total = self.pool_sponsorships.get(&key).copied().unwrap_or(0);
+            self.pool_sponsorships.insert(key, total + amount);
+            self.internal_credit_sponsored_pool(repo_id.clone(), token_id.clone(), amount);
+        }
+        self.internal_credit_pool(repo_id, token_id, amount);
+    }
+
+    // Get the open bounty of an issue or PR, or open a new empty one
+    // A deadline can only be given for a new bounty, the funder extends it afterwards
+    pub(crate) fn internal_open_issue_bounty(
+        &mut self,
+        repo_id: &String,
+        number: u64,
+        token_id: &Option<AccountId>,
+        creator: &AccountId,
+        deadline_ms: Option<u64>,
+    ) -> BountyId {
+        let now = block_timestamp_ms();
+        if let Some(&bounty_id) = self.issue_bounties.get(&(repo_id.clone(), number)) {
+            let bounty = self.internal_get_bounty(bounty_id);
+            if bounty.status == BountyStatus::Open {
+                require!(
+                    &bounty.token_id == token_id,
+                    "Bounty is funded with a different token"
+                );
+                require!(
+                    deadline_ms.is_none(),
+                    "Bounty already exists, its deadline can only be extended by the funder"
+                );
+                return bounty_id;
+            }
+            require!(
+                !matches!(
+                    bounty.status,
+                    BountyStatus::Claimed | BountyStatus::PendingApproval
+                ),
+                "Bounty payout is in progress"
+            );
+        }
+        if let Some(deadline_ms) = deadline_ms {
+            require!(deadline_ms > now, "Deadline must be in the future");
+        }
+
+        // A paid, cancelled or expired bounty is kept as history
+        let bounty_id = self.next_bounty_id;
+        self.next_bounty_id += 1;
+        self.bounty_records.insert(
+            bounty_id,
+            Bounty {
+                repo_id: repo_id.clone(),
+                number,
+                token_id: token_id.clone(),
+                amount: 0,
+                status: BountyStatus::Open,
+                creator: creator.clone(),
+                created_at_ms: now,
+                updated_at_ms: now,
+                deadline_ms,
+            },
+        );
+        self.issue_bounties
+            .insert((repo_id.clone(), number), bounty_id);
+        Event::BountyCreated {
+            bounty_id,
+            repo_id,
+            number,
+            token_id,
+            creator,
+            deadline_ms: deadline_ms.map(U64::from),
+        }
+        .emit();
+        bounty_id
+    }
+}
@@ -0,0 +1,155 @@
+use crate::internal::bounty::PAYOUT_CALLBACK_GAS;
+use crate::*;
+use std::collections::BTreeMap;
+
+// Total amount per token, a claim makes one transfer per token in this order
+pub(crate) fn claim_totals(balances: &[ClaimableBalance]) -> BTreeMap<Option<AccountId>, u128> {
+    let mut totals = BTreeMap::new();
+    for balance in balances {
+        *totals.entry(balance.token_id.clone()).or_insert(0) += balance.amount;
+    }
+    totals
+}
+
+impl Contract {
+    // Hold the recorded payout of a claimed bounty for its GitHub user
+    // Nothing is transferred, so the bounty is paid right away
+    pub(crate) fn internal_hold_payout(&mut self, mut bounty: Bounty, pr_number: u64) {
+        let payout = self
+            .payouts
+            .get(&(bounty.repo_id.clone(), pr_number))
+            .cloned()
+            .unwrap();
+        let github_user_id = payout.github_user_id.unwrap();
+        let expires_at_ms = block_timestamp_ms() + self.claim_expiry_ms;
+        let returned_to_pool =
+            self.internal_settle_bounty(payout.bounty_id, &mut bounty, payout.amount);
+
+        Event::BountyHeldForGithubUser {
+            bounty_id: payout.bounty_id,
+            repo_id: &bounty.repo_id,
+            number: bounty.number,
+            github_user_id,
+            token_id: &bounty.token_id,
+            amount: U128(payout.amount),
+            returned_to_pool: U128(returned_to_pool),
+            expires_at_ms: U64::from(expires_at_ms),
+            pr_number,
+            merge_commit_sha: &payout.merge_commit_sha,
+        }
+        .emit();
+        self.internal_add_claimable_balance(
+            github_user_id,
+            ClaimableBalance {
+                bounty_id: payout.bounty_id,
+                repo_id: bounty.repo_id.clone(),
+                token_id: bounty.token_id.clone(),
+                amount: payout.amount,
+                expires_at_ms,
+            },
+        );
+        self.bounty_records.insert(payout.bounty_id, bounty);
+    }
+
+    // Hold an amount for a GitHub user until they claim it or it expires
+    pub(crate) fn internal_add_claimable_balance(
+        &mut self,
+        github_user_id: GithubUserId,
+        balance: ClaimableBalance,
+    ) {
+        let mut balances = self
+            .claimable_balances
+            .get(&github_user_id)
+            .cloned()
+            .unwrap_or_default();
+        balances.push(balance);
+        self.claimable_balances.insert(github_user_id, balances);
+    }
+
+    // Remove the balances held for a GitHub user, expired ones go back to their repo pool
+    // Returns the balances that can still be claimed
+    pub(crate) fn internal_take_claimable_balances(
+        &mut self,
+        github_user_id: GithubUserId,
+    ) -> Vec<ClaimableBalance> {
+        let balances = self
+            .claimable_balances
+            .remove(&github_user_id)
+            .unwrap_or_default();
+        let now = block_timestamp_ms();
+
+        let mut claimable = Vec::new();
+        for balance in balances {
+            if balance.expires_at_ms > now {
+                claimable.push(balance);
+                continue;
+            }
+            Event::ClaimableBalanceExpired {
+                github_user_id,
+                bounty_id: balance.bounty_id,
+                repo_id: &balance.repo_id,
+                token_id: &balance.token_id,
+                amount: U128(balance.amount),
+            }
+            .emit();
+            self.internal_credit_pool(balance.repo_id, balance.token_id, balance.amount);
+        }
+        claimable
+    }
+
+    // Transfer everything held for a GitHub user to an account, one transfer per token
+    // Returns None if nothing can be claimed
+    pub(crate) fn internal_pay_claimable_balances(
+        &mut self,
+        github_user_id: GithubUserId,
+        account_id: &AccountId,
+    ) -> Option<Promise> {
+        let balances = self.internal_take_claimable_balances(github_user_id);
+        if balances.is_empty() {
+            return None;
+        }
+
+        let transfers = claim_totals(&balances)
+            .into_iter()
+            .map(|(token_id, amount)| {
+                Event::ClaimableBalanceClaimed {
+                    github_user_id,
+                    account_id,
+                    token_id: &token_id,
+                    amount: U128(amount),
+                }
+                .emit();
+                self.internal_transfer(&token_id, account_id.clone(), amount)
+            })
+            .reduce(Promise::and)
+            .unwrap();
+
+        Some(
+            transfers.then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(PAYOUT_CALLBACK_GAS)
+                    .on_claimed(
+                        github_user_id,
+                        account_id.clone(),
+                        balances.iter().map(ClaimableBalanceView::from).collect(),
+                    ),
+            ),
+        )
+    }
+
+    // Pay what is held for a GitHub user to the account it was just linked to
+    // Unless payouts are paused, then the contributor claims it later
+    pub(crate) fn internal_release_on_link(
+        &mut self,
+        github_user_id: GithubUserId,
+        account_id: &AccountId,
+    ) -> PromiseOrValue<bool> {
+        if self.paused.payouts {
+            return PromiseOrValue::Value(true);
+        }
+        match self.internal_pay_claimable_balances(github_user_id, account_id) {
+            Some(promise) => PromiseOrValue::Promise(promise),
+            None => PromiseOrValue::Value(true),
+        }
+    }
+}
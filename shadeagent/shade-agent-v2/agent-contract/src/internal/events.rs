@@ -17,10 +17,382 @@ pub enum Event<'a> {
         valid_until_ms: U64,
         // Cannot log attestation, it's too large
     },
+    AgentRenewed {
+        account_id: &'a AccountId,
+        measurements: &'a FullMeasurementsHex,
+        ppid: &'a Ppid,
+        old_valid_until_ms: U64,
+        valid_until_ms: U64,
+    },
+    // The agent may renew its attestation with these measurements and PPID
+    AgentChangeApproved {
+        account_id: &'a AccountId,
+        change: &'a AgentChange,
+    },
     AgentRemoved {
         account_id: &'a AccountId,
         reasons: Vec<AgentRemovalReason>,
     },
+    AgentStorageRefunded {
+        account_id: &'a AccountId,
+        refund_to: &'a AccountId,
+        amount: U128,
+    },
+    StorageRefundAccountUpdated {
+        account_id: &'a AccountId,
+        refund_to: Option<&'a AccountId>,
+    },
+    StorageDeposited {
+        account_id: &'a AccountId,
+        amount: U128,
+    },
+    StorageWithdrawn {
+        account_id: &'a AccountId,
+        amount: U128,
+    },
+    // The transfer failed and the amount was credited back to the storage balance
+    StorageWithdrawFailed {
+        account_id: &'a AccountId,
+        amount: U128,
+    },
+    OwnerProposed {
+        owner_id: &'a AccountId,
+        pending_owner_id: &'a AccountId,
+    },
+    OwnershipTransferred {
+        old_owner_id: &'a AccountId,
+        new_owner_id: &'a AccountId,
+    },
+    OwnerProposalCancelled {
+        pending_owner_id: &'a AccountId,
+    },
+    RoleGranted {
+        role: Role,
+        account_id: &'a AccountId,
+    },
+    RoleRevoked {
+        role: Role,
+        account_id: &'a AccountId,
+    },
+    PauseUpdated {
+        paused: &'a PauseFlags,
+    },
+    UpgradeProposed {
+        code_hash: &'a CodeHash,
+        executable_at_ms: U64,
+    },
+    UpgradeCancelled {
+        code_hash: &'a CodeHash,
+    },
+    AttestationExpirationTimeUpdated {
+        old_attestation_expiration_time_ms: U64,
+        new_attestation_expiration_time_ms: U64,
+    },
+    MpcContractIdUpdated {
+        old_mpc_contract_id: &'a AccountId,
+        new_mpc_contract_id: &'a AccountId,
+    },
+    MeasurementsApproved {
+        measurements: &'a FullMeasurementsHex,
+        label: &'a String,
+        source: &'a String,
+    },
+    // Active measurements were approved again with a new label or source
+    // The old label and source are None for measurements approved before they were recorded
+    MeasurementsInfoUpdated {
+        measurements: &'a FullMeasurementsHex,
+        old_label: Option<&'a String>,
+        old_source: Option<&'a String>,
+        label: &'a String,
+        source: &'a String,
+    },
+    MeasurementsRemoved {
+        measurements: &'a FullMeasurementsHex,
+    },
+    MeasurementsDeprecated {
+        measurements: &'a FullMeasurementsHex,
+        until_ms: U64,
+    },
+    MeasurementsRevoked {
+        measurements: &'a FullMeasurementsHex,
+    },
+    // Only lists the PPIDs that were not approved before
+    PpidsApproved {
+        ppids: &'a Vec<Ppid>,
+    },
+    PpidsRemoved {
+        ppids: &'a Vec<Ppid>,
+    },
+    AgentWhitelistedForLocal {
+        account_id: &'a AccountId,
+    },
+    AgentRemovedFromWhitelistForLocal {
+        account_id: &'a AccountId,
+    },
+    AcceptedTokenAdded {
+        token_id: &'a AccountId,
+    },
+    AcceptedTokenRemoved {
+        token_id: &'a AccountId,
+    },
+    UpgradeDelayUpdated {
+        old_upgrade_delay_ms: U64,
+        new_upgrade_delay_ms: U64,
+    },
+    // The staged code is being deployed, migrate runs right after
+    ContractUpdated {
+        code_hash: &'a CodeHash,
+        old_state_version: u32,
+    },
+    RepoRegistered {
+        repo_id: &'a String,
+        maintainer_id: &'a AccountId,
+        agent_id: &'a AccountId,
+    },
+    RepoMaintainerProposed {
+        repo_id: &'a String,
+        maintainer_id: &'a AccountId,
+        pending_maintainer_id: &'a AccountId,
+    },
+    RepoMaintainerProposalCancelled {
+        repo_id: &'a String,
+        pending_maintainer_id: &'a AccountId,
+    },
+    // The primary maintainer changed through a handover or an arbiter override
+    RepoMaintainerChanged {
+        repo_id: &'a String,
+        old_maintainer_id: Option<&'a AccountId>,
+        new_maintainer_id: &'a AccountId,
+    },
+    RepoMemberUpdated {
+        repo_id: &'a String,
+        account_id: &'a AccountId,
+        role: MaintainerRole,
+    },
+    RepoMemberRemoved {
+        repo_id: &'a String,
+        account_id: &'a AccountId,
+    },
+    // A new escrow was opened for an issue or PR, followed by a bounty_funded event
+    BountyCreated {
+        bounty_id: BountyId,
+        repo_id: &'a String,
+        number: u64,
+        token_id: &'a Option<AccountId>,
+        creator: &'a AccountId,
+        deadline_ms: Option<U64>,
+    },
+    BountyDeadlineExtended {
+        bounty_id: BountyId,
+        old_deadline_ms: U64,
+        new_deadline_ms: U64,
+    },
+    // The deadline passed and the escrow was refunded to its direct funders pro-rata
+    // The part moved from the repo pool went back to the pool
+    BountyExpired {
+        bounty_id: BountyId,
+        repo_id: &'a String,
+        number: u64,
+        token_id: &'a Option<AccountId>,
+        // The account that opened the bounty
+        funder: &'a AccountId,
+        amount: U128,
+        refunded: U128,
+    },
+    // Funds were added to the repo pool, or to a bounty escrow when bounty_id is set
+    // from_pool is set when the funds were moved out of the repo pool
+    BountyFunded {
+        repo_id: &'a String,
+        bounty_id: Option<BountyId>,
+        number: Option<u64>,
+        token_id: &'a Option<AccountId>,
+        funder: &'a AccountId,
+        amount: U128,
+        from_pool: bool,
+        // The funder has no role in the repo
+        sponsor: bool,
+    },
+    // The escrow of a bounty was refunded to its sponsors pro-rata, the rest went back to the repo pool
+    BountyCancelled {
+        bounty_id: BountyId,
+        repo_id: &'a String,
+        number: u64,
+        token_id: &'a Option<AccountId>,
+        amount: U128,
+        refunded: U128,
+    },
+    // Part of the escrow of a cancelled or expired bounty can be withdrawn by a contributor
+    BountyRefundCredited {
+        bounty_id: BountyId,
+        account_id: &'a AccountId,
+        token_id: &'a Option<AccountId>,
+        amount: U128,
+    },
+    RefundWithdrawn {
+        account_id: &'a AccountId,
+        token_id: &'a Option<AccountId>,
+        amount: U128,
+    },
+    // The transfer failed and the amount can be withdrawn again
+    RefundWithdrawFailed {
+        account_id: &'a AccountId,
+        token_id: &'a Option<AccountId>,
+        amount: U128,
+    },
+    ThirdPartyFundingUpdated {
+        repo_id: &'a String,
+        enabled: bool,
+    },
+    // A payout was started, returned_to_pool is the part of the escrow that is not paid out
+    // Once the payout is made sponsors get their share of it as a refund, the rest goes to the pool
+    BountyReleased {
+        bounty_id: BountyId,
+        repo_id: &'a String,
+        number: u64,
+        recipient: &'a AccountId,
+        // Set when the recipient was resolved from the GitHub account registry
+        github_user_id: Option<GithubUserId>,
+        token_id: &'a Option<AccountId>,
+        amount: U128,
+        returned_to_pool: U128,
+        pr_number: u64,
+        merge_commit_sha: &'a String,
+    },
+    // A payout split between several recipients was started
+    BountySplitReleased {
+        bounty_id: BountyId,
+        repo_id: &'a String,
+        number: u64,
+        token_id: &'a Option<AccountId>,
+        amount: U128,
+        returned_to_pool: U128,
+        pr_number: u64,
+        merge_commit_sha: &'a String,
+        shares: Vec<PayoutShareView>,
+    },
+    // The payout transfer of a released bounty succeeded, once per recipient of a split
+    BountyPaid {
+        bounty_id: BountyId,
+        repo_id: &'a String,
+        number: u64,
+        recipient: &'a AccountId,
+        token_id: &'a Option<AccountId>,
+        amount: U128,
+    },
+    // Funds were taken out of the repo pool by a repo admin
+    BountyWithdrawn {
+        repo_id: &'a String,
+        recipient: &'a AccountId,
+        token_id: &'a Option<AccountId>,
+        amount: U128,
+    },
+    // A payout was held for a GitHub user who has not linked a NEAR account
+    BountyHeldForGithubUser {
+        bounty_id: BountyId,
+        repo_id: &'a String,
+        number: u64,
+        github_user_id: GithubUserId,
+        token_id: &'a Option<AccountId>,
+        amount: U128,
+        returned_to_pool: U128,
+        expires_at_ms: U64,
+        pr_number: u64,
+        merge_commit_sha: &'a String,
+    },
+    // Held balances are being transferred to the account of the GitHub user, once per token
+    ClaimableBalanceClaimed {
+        github_user_id: GithubUserId,
+        account_id: &'a AccountId,
+        token_id: &'a Option<AccountId>,
+        amount: U128,
+    },
+    // A held balance was not claimed in time and went back to the repo pool
+    ClaimableBalanceExpired {
+        github_user_id: GithubUserId,
+        bounty_id: BountyId,
+        repo_id: &'a String,
+        token_id: &'a Option<AccountId>,
+        amount: U128,
+    },
+    ClaimExpiryUpdated {
+        old_claim_expiry_ms: U64,
+        new_claim_expiry_ms: U64,
+    },
+    PayoutThresholdUpdated {
+        repo_id: &'a String,
+        token_id: &'a Option<AccountId>,
+        old_threshold: Option<U128>,
+        new_threshold: Option<U128>,
+    },
+    PoolPayoutAmountUpdated {
+        repo_id: &'a String,
+        old_amount: Option<U128>,
+        new_amount: Option<U128>,
+    },
+    PayoutApprovalTimeoutUpdated {
+        old_payout_approval_timeout_ms: U64,
+        new_payout_approval_timeout_ms: U64,
+    },
+    // A payout above the repo threshold waits for a repo admin, no release event is emitted yet
+    PayoutPendingApproval {
+        bounty_id: BountyId,
+        repo_id: &'a String,
+        number: u64,
+        token_id: &'a Option<AccountId>,
+        amount: U128,
+        returned_to_pool: U128,
+        expires_at_ms: U64,
+        pr_number: u64,
+        merge_commit_sha: &'a String,
+    },
+    PayoutApproved {
+        bounty_id: BountyId,
+        repo_id: &'a String,
+        pr_number: u64,
+        approved_by: &'a AccountId,
+    },
+    // The bounty is reopened with its escrow so the PR can be paid again
+    PayoutRejected {
+        bounty_id: BountyId,
+        repo_id: &'a String,
+        pr_number: u64,
+        rejected_by: &'a AccountId,
+    },
+    // Nobody approved the payout in time, the bounty is reopened like for a rejection
+    PendingPayoutExpired {
+        bounty_id: BountyId,
+        repo_id: &'a String,
+        pr_number: u64,
+    },
+    PayoutLimitsUpdated {
+        old_limits: &'a PayoutLimits,
+        new_limits: &'a PayoutLimits,
+    },
+    RepoPayoutLimitUpdated {
+        repo_id: &'a String,
+        old_limit: Option<&'a RateLimit>,
+        new_limit: Option<&'a RateLimit>,
+    },
+    GithubAccountLinked {
+        github_user_id: GithubUserId,
+        old_account_id: Option<&'a AccountId>,
+        new_account_id: &'a AccountId,
+        // The agent, or the account itself with an agent signature
+        linked_by: &'a AccountId,
+    },
+    GithubAccountUnlinked {
+        github_user_id: GithubUserId,
+        account_id: &'a AccountId,
+        unlinked_by: &'a AccountId,
+    },
+    // A payout transfer failed and the funds were restored to the bounty or repo pool
+    PayoutFailed {
+        repo_id: &'a String,
+        bounty_id: Option<BountyId>,
+        recipient: &'a AccountId,
+        token_id: &'a Option<AccountId>,
+        amount: U128,
+    },
 }
 
 impl Event<'_> {
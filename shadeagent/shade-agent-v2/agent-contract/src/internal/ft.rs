@@ -0,0 +1,37 @@
+use crate::*;
+
+// Message attached to ft_transfer_call when funding a bounty
+#[near(serializers = [json])]
+pub struct FtBountyMessage {
+    pub repo_id: String,
+    pub number: Option<u64>,
+    // Only for a new issue bounty
+    pub deadline_ms: Option<U64>,
+}
+
+#[allow(dead_code)]
+#[ext_contract(ft_contract)]
+trait FungibleToken {
+    fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>);
+}
+
+const FT_TRANSFER_GAS: Gas = Gas::from_tgas(10);
+const ONE_YOCTO: NearToken = NearToken::from_yoctonear(1);
+
+impl Contract {
+    // Transfer NEAR, or tokens of the given contract, to the receiver
+    pub(crate) fn internal_transfer(
+        &self,
+        token_id: &Option<AccountId>,
+        receiver_id: AccountId,
+        amount: u128,
+    ) -> Promise {
+        match token_id {
+            None => Promise::new(receiver_id).transfer(NearToken::from_yoctonear(amount)),
+            Some(token_id) => ft_contract::ext(token_id.clone())
+                .with_static_gas(FT_TRANSFER_GAS)
+                .with_attached_deposit(ONE_YOCTO)
+                .ft_transfer(receiver_id, U128(amount), None),
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use crate::*;
+
+// Message an agent signs after verifying that an account controls a GitHub account
+// Format: "<contract_id>:<action>:<github_user_id>:<account_id>:<expires_at_ms>"
+// The action is "claim" for held payouts and "link" for the GitHub account registry
+pub(crate) fn github_message(
+    action: &str,
+    github_user_id: GithubUserId,
+    account_id: &AccountId,
+    expires_at_ms: u64,
+) -> String {
+    format!(
+        "{}:{}:{}:{}:{}",
+        env::current_account_id(),
+        action,
+        github_user_id,
+        account_id,
+        expires_at_ms
+    )
+}
+
+impl Contract {
+    // Link a GitHub user to a NEAR account, replacing any previous link on either side
+    pub(crate) fn internal_link_github_account(
+        &mut self,
+        github_user_id: GithubUserId,
+        account_id: AccountId,
+    ) {
+        let old_account_id = self.github_accounts.get(&github_user_id).cloned();
+        if let Some(old_account_id) = &old_account_id {
+            self.github_user_ids.remove(old_account_id);
+        }
+        // An account is linked to at most one GitHub user
+        if let Some(old_github_user_id) = self.github_user_ids.get(&account_id).copied() {
+            self.internal_unlink_github_account(old_github_user_id);
+        }
+
+        Event::GithubAccountLinked {
+            github_user_id,
+            old_account_id: old_account_id.as_ref(),
+            new_account_id: &account_id,
+            linked_by: &env::predecessor_account_id(),
+        }
+        .emit();
+        self.internal_record_github_link(github_user_id, Some(account_id.clone()));
+        self.github_user_ids
+            .insert(account_id.clone(), github_user_id);
+        self.github_accounts.insert(github_user_id, account_id);
+    }
+
+    pub(crate) fn internal_unlink_github_account(&mut self, github_user_id: GithubUserId) {
+        let account_id = self
+            .github_accounts
+            .remove(&github_user_id)
+            .expect("GitHub user has no linked account");
+        self.github_user_ids.remove(&account_id);
+
+        Event::GithubAccountUnlinked {
+            github_user_id,
+            account_id: &account_id,
+            unlinked_by: &env::predecessor_account_id(),
+        }
+        .emit();
+        self.internal_record_github_link(github_user_id, None);
+    }
+
+    fn internal_record_github_link(
+        &mut self,
+        github_user_id: GithubUserId,
+        account_id: Option<AccountId>,
+    ) {
+        let mut history = self
+            .github_link_history
+            .get(&github_user_id)
+            .cloned()
+            .unwrap_or_default();
+        history.push(GithubLink {
+            account_id,
+            changed_by: env::predecessor_account_id(),
+            changed_at_ms: block_timestamp_ms(),
+        });
+        self.github_link_history.insert(github_user_id, history);
+    }
+}
@@ -19,25 +19,58 @@ impl Contract {
         );
     }
 
+    // Require the caller to be the owner or to have been granted the role
+    pub(crate) fn require_role(&mut self, role: Role) {
+        let account_id = env::predecessor_account_id();
+        require!(
+            account_id == self.owner_id || self.has_role(role, account_id),
+            &format!(
+                "Caller is not the owner and does not have the {:?} role",
+                role
+            )
+        );
+    }
+
     // Require the caller to be a valid agent or remove it from the agents map
     // Just because an agent is registered does not mean it is currently valid
     // Returns Some(Promise) if agent is invalid (to fail the request), None if valid
     pub(crate) fn require_valid_agent(&mut self) -> Option<Promise> {
-        let account_id = env::predecessor_account_id();
+        self.require_valid_agent_account(&env::predecessor_account_id())
+    }
 
+    // Require a message to be signed by a valid agent, used when the agent is not the caller
+    // Agents are implicit accounts, so the public key is the account id itself
+    // Returns Some(Promise) if agent is invalid (to fail the request), None if valid
+    pub(crate) fn require_agent_signature(
+        &mut self,
+        agent_id: &AccountId,
+        message: &str,
+        signature: &Signature,
+    ) -> Option<Promise> {
+        let public_key = HexBytes::<32>::try_from(agent_id.to_string())
+            .unwrap_or_else(|_| env::panic_str("Agent is not an implicit account"));
+        require!(
+            env::ed25519_verify(signature, message, &public_key),
+            "Invalid agent signature"
+        );
+        self.require_valid_agent_account(agent_id)
+    }
+
+    fn require_valid_agent_account(&mut self, account_id: &AccountId) -> Option<Promise> {
         // Get the agent and check if it is registered
-        let agent = self.agents.get(&account_id).expect("Agent not registered");
+        let agent = self.agents.get(account_id).expect("Agent not registered");
 
         // Check if the agent is invalid and return a promise to panic if it is
-        let removal_reasons = self.check_invalid_reasons(&account_id, &agent);
+        let removal_reasons = self.check_invalid_reasons(account_id, agent);
 
         if !removal_reasons.is_empty() {
-            self.agents.remove(&account_id);
+            self.agents.remove(account_id);
             Event::AgentRemoved {
-                account_id: &account_id,
+                account_id,
                 reasons: removal_reasons.clone(),
             }
             .emit();
+            self.internal_refund_agent_storage(account_id);
 
             let args_json = serde_json::json!({
                 "reasons": removal_reasons
@@ -54,6 +87,31 @@ impl Contract {
         None
     }
 
+    // Status of a set of measurements, None when they are not in the approved list
+    pub(crate) fn measurement_status(
+        &self,
+        measurements: &FullMeasurementsHex,
+    ) -> Option<MeasurementStatus> {
+        if !self.approved_measurements.contains(measurements) {
+            return None;
+        }
+        Some(
+            self.measurement_statuses
+                .get(measurements)
+                .copied()
+                .unwrap_or(MeasurementStatus::Active),
+        )
+    }
+
+    // Whether agents registered with the measurements are still valid
+    pub(crate) fn measurements_valid(&self, measurements: &FullMeasurementsHex) -> bool {
+        match self.measurement_status(measurements) {
+            Some(MeasurementStatus::Active) => true,
+            Some(MeasurementStatus::Deprecated { until_ms }) => block_timestamp_ms() < until_ms.0,
+            Some(MeasurementStatus::Revoked) | None => false,
+        }
+    }
+
     pub(crate) fn check_invalid_reasons(
         &self,
         account_id: &AccountId,
@@ -63,7 +121,7 @@ impl Contract {
         if agent.valid_until_ms < block_timestamp_ms() {
             reasons.push(AgentRemovalReason::ExpiredAttestation);
         }
-        if !self.approved_measurements.contains(&agent.measurements) {
+        if !self.measurements_valid(&agent.measurements) {
             reasons.push(AgentRemovalReason::InvalidMeasurements);
         }
         if !self.approved_ppids.contains(&agent.ppid) {
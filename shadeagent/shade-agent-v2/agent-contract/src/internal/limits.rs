@@ -0,0 +1,235 @@
+use crate::*;
+
+const HOUR_MS: u64 = 60 * 60 * 1000;
+const DAY_MS: u64 = 24 * HOUR_MS;
+
+// Count and total amount of the payouts within the window ending now
+fn usage_in_window(usage: &[PayoutUsage], window_ms: u64, now: u64) -> (u32, u128) {
+    usage
+        .iter()
+        .filter(|payout| payout.at_ms + window_ms > now)
+        .fold((0, 0), |(count, amount), payout| {
+            (count + 1, amount + payout.amount)
+        })
+}
+
+// What is left of a limit within the window ending now
+fn remaining_allowance(
+    limit: &RateLimit,
+    usage: &[PayoutUsage],
+    window_ms: u64,
+    now: u64,
+) -> AllowanceView {
+    let (count, amount) = usage_in_window(usage, window_ms, now);
+    AllowanceView {
+        remaining_count: limit.max_count.map(|max| max.saturating_sub(count)),
+        remaining_amount: limit
+            .max_amount
+            .map(|max| U128(max.0.saturating_sub(amount))),
+    }
+}
+
+// A limit with neither a count nor an amount cap does not need the usage recorded
+fn is_limited(limit: &RateLimit) -> bool {
+    limit.max_count.is_some() || limit.max_amount.is_some()
+}
+
+fn require_within_limit(
+    limit: &RateLimit,
+    usage: &[PayoutUsage],
+    window_ms: u64,
+    payout: &PayoutUsage,
+    name: &str,
+) {
+    let (count, amount) = usage_in_window(usage, window_ms, payout.at_ms);
+    if limit.max_count.is_some_and(|max| count >= max) {
+        env::panic_str(&format!("{name} payout count limit reached"));
+    }
+    if limit
+        .max_amount
+        .is_some_and(|max| amount + payout.amount > max.0)
+    {
+        env::panic_str(&format!("{name} payout amount limit exceeded"));
+    }
+}
+
+impl Contract {
+    // The daily limit of a repo, its maintainer's override or the owner default
+    pub(crate) fn internal_repo_payout_limit(&self, repo_id: &String) -> RateLimit {
+        self.repo_payout_limits
+            .get(repo_id)
+            .copied()
+            .unwrap_or(self.payout_limits.repo_daily)
+    }
+
+    // Payouts of the last day, older ones no longer count towards any window
+    pub(crate) fn internal_agent_payout_usage(&self, agent_id: &AccountId) -> Vec<PayoutUsage> {
+        let now = block_timestamp_ms();
+        let mut usage = self
+            .agent_payout_usage
+            .get(agent_id)
+            .cloned()
+            .unwrap_or_default();
+        usage.retain(|payout| payout.at_ms + DAY_MS > now);
+        usage
+    }
+
+    pub(crate) fn internal_repo_payout_usage(&self, repo_id: &String) -> Vec<PayoutUsage> {
+        let now = block_timestamp_ms();
+        let mut usage = self
+            .repo_payout_usage
+            .get(repo_id)
+            .cloned()
+            .unwrap_or_default();
+        usage.retain(|payout| payout.at_ms + DAY_MS > now);
+        usage
+    }
+
+    // Panics if a payout by the calling agent would go over a limit, without counting it
+    // Amount caps are in yoctoNEAR, so fungible token payouts are refused while one applies
+    pub(crate) fn internal_check_payout_usage(
+        &self,
+        repo_id: &String,
+        token_id: &Option<AccountId>,
+        amount: u128,
+    ) -> PayoutUsage {
+        let agent_limits = [
+            self.payout_limits.agent_hourly,
+            self.payout_limits.agent_daily,
+        ];
+        let repo_limit = self.internal_repo_payout_limit(repo_id);
+        if token_id.is_some() {
+            require!(
+                agent_limits
+                    .iter()
+                    .chain([&repo_limit])
+                    .all(|limit| limit.max_amount.is_none()),
+                "Fungible token payouts are not allowed while a payout amount limit is set"
+            );
+        }
+        let payout = PayoutUsage {
+            at_ms: block_timestamp_ms(),
+            amount: if token_id.is_none() { amount } else { 0 },
+        };
+
+        if agent_limits.iter().any(is_limited) {
+            let agent_usage = self.internal_agent_payout_usage(&env::predecessor_account_id());
+            require_within_limit(
+                &self.payout_limits.agent_hourly,
+                &agent_usage,
+                HOUR_MS,
+                &payout,
+                "Agent hourly",
+            );
+            require_within_limit(
+                &self.payout_limits.agent_daily,
+                &agent_usage,
+                DAY_MS,
+                &payout,
+                "Agent daily",
+            );
+        }
+        if is_limited(&repo_limit) {
+            let repo_usage = self.internal_repo_payout_usage(repo_id);
+            require_within_limit(&repo_limit, &repo_usage, DAY_MS, &payout, "Repo daily");
+        }
+        payout
+    }
+
+    // Count a payout released by the calling agent, panics if it goes over a limit
+    // Usage is only recorded for the agent and the repo when a limit applies to them
+    pub(crate) fn internal_record_payout_usage(
+        &mut self,
+        repo_id: &String,
+        token_id: &Option<AccountId>,
+        amount: u128,
+    ) {
+        let payout = self.internal_check_payout_usage(repo_id, token_id, amount);
+        let agent_limits = [
+            self.payout_limits.agent_hourly,
+            self.payout_limits.agent_daily,
+        ];
+        if agent_limits.iter().any(is_limited) {
+            let agent_id = env::predecessor_account_id();
+            let mut agent_usage = self.internal_agent_payout_usage(&agent_id);
+            agent_usage.push(payout.clone());
+            self.agent_payout_usage.insert(agent_id, agent_usage);
+        }
+        if is_limited(&self.internal_repo_payout_limit(repo_id)) {
+            let mut repo_usage = self.internal_repo_payout_usage(repo_id);
+            repo_usage.push(payout);
+            self.repo_payout_usage.insert(repo_id.clone(), repo_usage);
+        }
+    }
+
+    // Stop counting a payout that was never made: rejected, expired or with every transfer failed
+    // Only the usage entry recorded when the payout was claimed is removed
+    pub(crate) fn internal_release_payout_usage(&mut self, repo_id: &String, payout: &Payout) {
+        let recorded = |usage: &PayoutUsage| {
+            usage.at_ms == payout.paid_at_ms
+                && usage.amount
+                    == if payout.token_id.is_none() {
+                        payout.amount
+                    } else {
+                        0
+                    }
+        };
+        let mut agent_usage = self
+            .agent_payout_usage
+            .get(&payout.released_by)
+            .cloned()
+            .unwrap_or_default();
+        if let Some(index) = agent_usage.iter().position(recorded) {
+            agent_usage.remove(index);
+            if agent_usage.is_empty() {
+                self.agent_payout_usage.remove(&payout.released_by);
+            } else {
+                self.agent_payout_usage
+                    .insert(payout.released_by.clone(), agent_usage);
+            }
+        }
+        let mut repo_usage = self
+            .repo_payout_usage
+            .get(repo_id)
+            .cloned()
+            .unwrap_or_default();
+        if let Some(index) = repo_usage.iter().position(recorded) {
+            repo_usage.remove(index);
+            if repo_usage.is_empty() {
+                self.repo_payout_usage.remove(repo_id);
+            } else {
+                self.repo_payout_usage.insert(repo_id.clone(), repo_usage);
+            }
+        }
+    }
+
+    pub(crate) fn internal_payout_allowance(
+        &self,
+        agent_id: &AccountId,
+        repo_id: &String,
+    ) -> PayoutAllowanceView {
+        let now = block_timestamp_ms();
+        let agent_usage = self.internal_agent_payout_usage(agent_id);
+        let repo_usage = self.internal_repo_payout_usage(repo_id);
+        PayoutAllowanceView {
+            agent_hourly: remaining_allowance(
+                &self.payout_limits.agent_hourly,
+                &agent_usage,
+                HOUR_MS,
+                now,
+            ),
+            agent_daily: remaining_allowance(
+                &self.payout_limits.agent_daily,
+                &agent_usage,
+                DAY_MS,
+                now,
+            ),
+            repo_daily: remaining_allowance(
+                &self.internal_repo_payout_limit(repo_id),
+                &repo_usage,
+                DAY_MS,
+                now,
+            ),
+        }
+    }
+}
@@ -1,6 +1,13 @@
 pub mod attestation;
+pub mod bounty;
 pub mod chainsig;
+pub mod claims;
 pub mod events;
+pub mod ft;
+pub mod github;
 pub mod helpers;
+pub mod limits;
+pub mod sponsors;
+pub mod storage;
 #[cfg(test)]
 mod unit_tests;
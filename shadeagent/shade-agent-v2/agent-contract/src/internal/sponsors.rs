@@ -0,0 +1,155 @@
+use crate::*;
+use near_sdk::IntoStorageKey;
+use primitive_types::U256;
+
+// Bytes NEAR counts for every stored key-value record on top of the key and the value
+const STORAGE_RECORD_OVERHEAD: u64 = 40;
+
+// a * b / c rounded down, for a result that fits in u128 when a * b does not
+pub(crate) fn mul_div(a: u128, b: u128, c: u128) -> u128 {
+    (U256::from(a) * U256::from(b) / U256::from(c)).as_u128()
+}
+
+// Sponsors get their share of a bounty back when it is cancelled or not fully paid out
+// Sponsored pool funds moved into the bounty are not theirs alone, so they go back to the pool
+pub(crate) fn is_sponsor_refundable(contribution: &Contribution) -> bool {
+    contribution.sponsor && !contribution.from_pool
+}
+
+impl Contract {
+    // Anyone can fund a repo unless its admins disabled third-party funding
+    // Returns whether the funder is a sponsor, i.e. has no admin or funder role in the repo
+    pub(crate) fn internal_check_funder(&self, repo_id: &String, funder: &AccountId) -> bool {
+        let role = self.internal_repo_role(repo_id, funder);
+        if matches!(role, Some(MaintainerRole::Admin | MaintainerRole::Funder)) {
+            return false;
+        }
+        require!(
+            !self.third_party_funding_disabled.contains(repo_id),
+            "Only repo admins and funders can fund the bounty, third-party funding is disabled"
+        );
+        true
+    }
+
+    // Add to the contribution of an account to a bounty
+    pub(crate) fn internal_record_contribution(
+        &mut self,
+        bounty_id: BountyId,
+        contribution: Contribution,
+    ) {
+        let mut contributions = self
+            .bounty_contributions
+            .get(&bounty_id)
+            .cloned()
+            .unwrap_or_default();
+        match contributions.iter_mut().find(|existing| {
+            existing.account_id == contribution.account_id
+                && existing.from_pool == contribution.from_pool
+                && existing.sponsor == contribution.sponsor
+        }) {
+            Some(existing) => existing.amount += contribution.amount,
+            None => contributions.push(contribution),
+        }
+        self.bounty_contributions.insert(bounty_id, contributions);
+    }
+
+    // Split an amount of the escrow of a bounty pro-rata over its contributions
+    // The shares of refundable contributions are credited to their accounts, everything else
+    // including the rounding dust goes back to the repo pool
+    // The shares of sponsored pool funds stay sponsored once back in the pool
+    // Returns the total credited to contributors
+    pub(crate) fn internal_refund_contributions(
+        &mut self,
+        bounty_id: BountyId,
+        bounty: &Bounty,
+        amount: u128,
+        is_refundable: impl Fn(&Contribution) -> bool,
+    ) -> u128 {
+        let contributions = self
+            .bounty_contributions
+            .get(&bounty_id)
+            .cloned()
+            .unwrap_or_default();
+        let total: u128 = contributions
+            .iter()
+            .map(|contribution| contribution.amount)
+            .sum();
+
+        let mut refunded = 0;
+        let mut sponsored = 0;
+        if total > 0 {
+            for contribution in &contributions {
+                let share = mul_div(amount, contribution.amount, total);
+                if share == 0 {
+                    continue;
+                }
+                if !is_refundable(contribution) {
+                    if contribution.from_pool && contribution.sponsor {
+                        sponsored += share;
+                    }
+                    continue;
+                }
+                Event::BountyRefundCredited {
+                    bounty_id,
+                    account_id: &contribution.account_id,
+                    token_id: &bounty.token_id,
+                    amount: U128(share),
+                }
+                .emit();
+                self.internal_credit_refund(&contribution.account_id, &bounty.token_id, share);
+                refunded += share;
+            }
+        }
+
+        let returned_to_pool = amount - refunded;
+        if returned_to_pool > 0 {
+            self.internal_credit_pool(
+                bounty.repo_id.clone(),
+                bounty.token_id.clone(),
+                returned_to_pool,
+            );
+        }
+        if sponsored > 0 {
+            self.internal_credit_sponsored_pool(
+                bounty.repo_id.clone(),
+                bounty.token_id.clone(),
+                sponsored,
+            );
+        }
+        refunded
+    }
+
+    // Storage the refunds of the sponsors of a bounty use once credited, for sponsors who have
+    // no refund in that token yet
+    // A payout credits them in on_bounty_released, which has no deposit, so they are paid up front
+    pub(crate) fn internal_refund_storage_bytes(
+        &self,
+        bounty_id: BountyId,
+        token_id: &Option<AccountId>,
+    ) -> u64 {
+        let prefix_len = StorageKey::Refunds.into_storage_key().len() as u64;
+        self.bounty_contributions
+            .get(&bounty_id)
+            .into_iter()
+            .flatten()
+            .filter(|contribution| is_sponsor_refundable(contribution))
+            .map(|contribution| (contribution.account_id.clone(), token_id.clone()))
+            .filter(|key| !self.refunds.contains_key(key))
+            .map(|key| {
+                let key_len = near_sdk::borsh::to_vec(&key).unwrap().len() as u64;
+                prefix_len + key_len + size_of::<u128>() as u64 + STORAGE_RECORD_OVERHEAD
+            })
+            .sum()
+    }
+
+    pub(crate) fn internal_credit_refund(
+        &mut self,
+        account_id: &AccountId,
+        token_id: &Option<AccountId>,
+        amount: u128,
+    ) {
+        let key = (account_id.clone(), token_id.clone());
+        let balance = self.refunds.get(&key).copied().unwrap_or(0);
+        self.refunds.insert(key, balance + amount);
+    }
+}
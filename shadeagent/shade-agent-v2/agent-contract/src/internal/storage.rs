@@ -0,0 +1,204 @@
+use crate::migrate::STATE_KEY;
+use crate::*;
+
+impl Contract {
+    // Write the cached changes of the collections so env::storage_usage() includes them
+    // Every field is named, so a new collection does not compile until it is flushed here
+    fn internal_flush_collections(&mut self) {
+        let Contract {
+            requires_tee: _,
+            attestation_expiration_time_ms: _,
+            owner_id: _,
+            mpc_contract_id: _,
+            approved_measurements,
+            approved_ppids,
+            agents,
+            whitelisted_agents_for_local,
+            bounties,
+            repo_maintainers,
+            bounty_records,
+            issue_bounties,
+            next_bounty_id: _,
+            accepted_tokens,
+            ft_bounties,
+            repo_members,
+            pending_maintainers,
+            upgrade_delay_ms: _,
+            pending_upgrade: _,
+            pending_owner_id: _,
+            role_members,
+            paused: _,
+            payouts,
+            claimable_balances,
+            claim_expiry_ms: _,
+            github_accounts,
+            github_user_ids,
+            github_link_history,
+            payout_thresholds,
+            pending_payouts,
+            payout_approval_timeout_ms: _,
+            payout_limits: _,
+            repo_payout_limits,
+            agent_payout_usage,
+            repo_payout_usage,
+            bounty_contributions,
+            pool_sponsorships,
+            refunds,
+            // A LookupSet writes through and has no cache to flush
+            third_party_funding_disabled: _,
+            approved_agent_changes,
+            measurement_statuses,
+            measurement_infos,
+            agent_storage_deposits,
+            sponsored_pool_balances,
+            storage_balances,
+            pool_payout_amounts,
+            state_version: _,
+        } = self;
+        approved_measurements.flush();
+        approved_ppids.flush();
+        agents.flush();
+        whitelisted_agents_for_local.flush();
+        bounties.flush();
+        repo_maintainers.flush();
+        bounty_records.flush();
+        issue_bounties.flush();
+        accepted_tokens.flush();
+        ft_bounties.flush();
+        repo_members.flush();
+        pending_maintainers.flush();
+        role_members.flush();
+        payouts.flush();
+        claimable_balances.flush();
+        github_accounts.flush();
+        github_user_ids.flush();
+        github_link_history.flush();
+        payout_thresholds.flush();
+        pending_payouts.flush();
+        repo_payout_limits.flush();
+        agent_payout_usage.flush();
+        repo_payout_usage.flush();
+        bounty_contributions.flush();
+        pool_sponsorships.flush();
+        refunds.flush();
+        approved_agent_changes.flush();
+        measurement_statuses.flush();
+        measurement_infos.flush();
+        agent_storage_deposits.flush();
+        sponsored_pool_balances.flush();
+        storage_balances.flush();
+        pool_payout_amounts.flush();
+    }
+
+    // Storage used by the contract including changes not written yet: those cached by the
+    // collections and those to the root state, which is only written at the end of the call
+    // Measure with this before and after a state change to get the storage the change uses
+    pub(crate) fn internal_storage_usage(&mut self) -> u64 {
+        self.internal_flush_collections();
+        let stored_state_len = env::storage_read(STATE_KEY).map_or(0, |state| state.len());
+        let state_len = near_sdk::borsh::to_vec(self).unwrap().len();
+        env::storage_usage() + state_len as u64 - stored_state_len as u64
+    }
+
+    // Charge the caller for the storage used since initial_storage_usage out of the deposit
+    // and refund the rest of it
+    // Returns the storage cost
+    pub(crate) fn internal_charge_storage(
+        &mut self,
+        initial_storage_usage: u64,
+        deposit: u128,
+    ) -> u128 {
+        let bytes = self
+            .internal_storage_usage()
+            .saturating_sub(initial_storage_usage);
+        let cost = env::storage_byte_cost().as_yoctonear() * u128::from(bytes);
+        require!(
+            deposit >= cost,
+            &format!(
+                "Attached deposit must be greater than storage cost: {} bytes cost {} yoctoNEAR, {} yoctoNEAR available",
+                bytes, cost, deposit
+            )
+        );
+
+        let excess = deposit - cost;
+        if excess > 0 {
+            Promise::new(env::predecessor_account_id())
+                .transfer(NearToken::from_yoctonear(excess))
+                .detach();
+        }
+        cost
+    }
+
+    // Charge an agent for the storage used since initial_storage_usage and add it to the
+    // deposit that is returned when the agent is removed
+    pub(crate) fn internal_charge_agent_storage(
+        &mut self,
+        account_id: &AccountId,
+        initial_storage_usage: u64,
+    ) {
+        let mut deposit = self
+            .agent_storage_deposits
+            .get(account_id)
+            .cloned()
+            .unwrap_or(AgentStorageDeposit {
+                amount: 0,
+                refund_to: None,
+            });
+        // The record is written before measuring so its own storage is charged too
+        self.agent_storage_deposits
+            .insert(account_id.clone(), deposit.clone());
+
+        deposit.amount += self.internal_charge_storage(
+            initial_storage_usage,
+            env::attached_deposit().as_yoctonear(),
+        );
+        self.agent_storage_deposits
+            .insert(account_id.clone(), deposit);
+    }
+
+    // Pay for the storage used since initial_storage_usage out of the storage balance of an account
+    // Used where no deposit can be attached, like ft_on_transfer
+    pub(crate) fn internal_charge_storage_balance(
+        &mut self,
+        account_id: &AccountId,
+        initial_storage_usage: u64,
+    ) {
+        let bytes = self
+            .internal_storage_usage()
+            .saturating_sub(initial_storage_usage);
+        if bytes == 0 {
+            return;
+        }
+        let cost = env::storage_byte_cost().as_yoctonear() * u128::from(bytes);
+        let balance = self.storage_balances.get(account_id).copied().unwrap_or(0);
+        require!(
+            balance >= cost,
+            &format!(
+                "Storage balance must cover the storage cost, call storage_deposit first: {} bytes cost {} yoctoNEAR, {} yoctoNEAR available",
+                bytes, cost, balance
+            )
+        );
+        self.storage_balances
+            .insert(account_id.clone(), balance - cost);
+    }
+
+    // Return the storage deposit of a removed agent to its refund account
+    pub(crate) fn internal_refund_agent_storage(&mut self, account_id: &AccountId) {
+        let Some(deposit) = self.agent_storage_deposits.remove(account_id) else {
+            return;
+        };
+        if deposit.amount == 0 {
+            return;
+        }
+        let refund_to = deposit.refund_to.unwrap_or_else(|| account_id.clone());
+        Event::AgentStorageRefunded {
+            account_id,
+            refund_to: &refund_to,
+            amount: U128(deposit.amount),
+        }
+        .emit();
+        Promise::new(refund_to)
+            .transfer(NearToken::from_yoctonear(deposit.amount))
+            .detach();
+    }
+}
@@ -1,6 +1,8 @@
 use crate::*;
 use near_sdk::test_utils::{VMContextBuilder, accounts};
-use near_sdk::{AccountId, NearToken, testing_env};
+use near_sdk::{
+    AccountId, NearToken, PromiseResult, RuntimeFeesConfig, test_vm_config, testing_env,
+};
 use shade_attestation::{
     attestation::create_mock_dstack_attestation,
     measurements::{FullMeasurementsHex, MeasurementsHex, create_mock_full_measurements_hex},
@@ -13,6 +15,12 @@ use shade_attestation::{
 const DEPOSIT_005_NEAR: NearToken = NearToken::from_yoctonear(5_000_000_000_000_000_000_000); // 0.005 NEAR
 const DEPOSIT_003_NEAR: NearToken = NearToken::from_yoctonear(3_000_000_000_000_000_000_000); // 0.003 NEAR
 const DEPOSIT_ZERO: NearToken = NearToken::from_yoctonear(0);
+const DEPOSIT_STORAGE: NearToken = NearToken::from_yoctonear(10_000_000_000_000_000_000_000); // 0.01 NEAR
+
+// Helper function to add enough for the storage a funding uses to a deposit
+fn with_storage(deposit: NearToken) -> NearToken {
+    deposit.saturating_add(DEPOSIT_STORAGE)
+}
 
 // Helper function to create a mock context
 fn get_context(predecessor: AccountId, is_view: bool) -> VMContextBuilder {
@@ -50,6 +58,23 @@ fn get_context_with_deposit_and_timestamp(
     builder
 }
 
+// Helper function to mock the result of the promise a callback is resolving
+fn set_promise_result(result: PromiseResult) {
+    set_promise_results(vec![result]);
+}
+
+// Helper function to mock the results of joint promises, one per promise
+fn set_promise_results(results: Vec<PromiseResult>) {
+    let context = get_context(accounts(0), false);
+    testing_env!(
+        context.build(),
+        test_vm_config(),
+        RuntimeFeesConfig::test(),
+        Default::default(),
+        results
+    );
+}
+
 /// Returns measurements that differ from default (one byte different in mrtd).
 fn non_default_measurements() -> FullMeasurementsHex {
     let mut mrtd = [0u8; 48];
@@ -83,8 +108,13 @@ fn setup_contract() -> Contract {
         U64::from(100000u64), // 100 seconds in milliseconds
         owner,
         mpc_contract,
+        Some(U64::from(1000u64)), // 1 second upgrade delay
+    );
+    contract.approve_measurements(
+        create_mock_full_measurements_hex(),
+        "agent v1".to_string(),
+        "sha256:abc".to_string(),
     );
-    contract.approve_measurements(create_mock_full_measurements_hex());
     contract.approve_ppids(vec![Ppid::default()]);
     contract
 }
@@ -104,6 +134,7 @@ fn test_new() {
         attestation_expiration_time_ms,
         owner.clone(),
         mpc_contract.clone(),
+        None,
     );
 
     let contract_info = contract.get_contract_info();
@@ -111,6 +142,8 @@ fn test_new() {
     assert_eq!(contract_info.mpc_contract_id, mpc_contract);
     assert_eq!(contract_info.requires_tee, false);
     assert_eq!(contract_info.attestation_expiration_time_ms.0, 100000u64);
+    assert_eq!(contract_info.upgrade_delay_ms.0, 24 * 60 * 60 * 1000);
+    assert_eq!(contract_info.upgrade_delay_ms.0, 24 * 60 * 60 * 1000);
     assert_eq!(contract.get_approved_measurements(&None, &None).len(), 0);
     assert_eq!(contract.get_approved_ppids(&None, &None).len(), 0);
     assert_eq!(contract.get_agents(&None, &None).len(), 0);
@@ -123,16 +156,55 @@ fn test_approve_measurements() {
     let mut contract = setup_contract();
 
     let measurements = create_mock_full_measurements_hex();
-    contract.approve_measurements(measurements.clone());
+    contract.approve_measurements(
+        measurements.clone(),
+        "agent v1".to_string(),
+        "sha256:abc".to_string(),
+    );
 
     assert!(
         contract
             .get_approved_measurements(&None, &None)
-            .contains(&measurements)
+            .iter()
+            .any(|m| m.measurements == measurements)
     );
     assert_eq!(contract.get_approved_measurements(&None, &None).len(), 1);
 }
 
+// Test that approving and removing measurements and PPIDs leaves an audit trail
+#[test]
+fn test_measurement_and_ppid_events() {
+    let mut contract = setup_contract();
+    let context = get_context(accounts(0), false);
+    testing_env!(context.build());
+
+    // Already approved in setup, only its info is updated
+    let measurements = create_mock_full_measurements_hex();
+    contract.approve_measurements(
+        measurements.clone(),
+        "agent v2".to_string(),
+        "sha256:def".to_string(),
+    );
+    let logs = near_sdk::test_utils::get_logs();
+    assert_eq!(logs.len(), 1);
+    assert!(logs[0].contains("\"event\":\"measurements_info_updated\""));
+    assert!(logs[0].contains("\"old_label\":\"agent v1\",\"old_source\":\"sha256:abc\""));
+    assert!(logs[0].contains("\"label\":\"agent v2\",\"source\":\"sha256:def\""));
+
+    contract.remove_measurements(measurements);
+    let new_ppid = Ppid::from([1u8; 16]);
+    contract.approve_ppids(vec![Ppid::default(), new_ppid.clone()]);
+    contract.remove_ppids(vec![new_ppid]);
+
+    let logs = near_sdk::test_utils::get_logs();
+    assert_eq!(logs.len(), 4);
+    assert!(logs[1].contains("\"event\":\"measurements_removed\""));
+    assert!(logs[2].contains("\"event\":\"ppids_approved\""));
+    // Only the newly approved PPID is listed
+    assert!(logs[2].contains(&format!("\"ppids\":[\"{}\"]", "01".repeat(16))));
+    assert!(logs[3].contains("\"event\":\"ppids_removed\""));
+}
+
 // Test that non-owner cannot approve measurements
 #[test]
 #[should_panic(expected = "Caller is not the owner")]
@@ -142,7 +214,11 @@ fn test_approve_measurements_not_owner() {
     let context = get_context(non_owner, false);
     testing_env!(context.build());
 
-    contract.approve_measurements(create_mock_full_measurements_hex());
+    contract.approve_measurements(
+        create_mock_full_measurements_hex(),
+        "agent v1".to_string(),
+        "sha256:abc".to_string(),
+    );
 }
 
 // Test that owner can remove measurements from the approved list
@@ -151,7 +227,11 @@ fn test_remove_measurements() {
     let mut contract = setup_contract();
 
     let extra = create_mock_full_measurements_hex();
-    contract.approve_measurements(extra.clone());
+    contract.approve_measurements(
+        extra.clone(),
+        "agent v1".to_string(),
+        "sha256:abc".to_string(),
+    );
     let count_before = contract.get_approved_measurements(&None, &None).len();
     assert_eq!(count_before, 1);
 
@@ -159,7 +239,8 @@ fn test_remove_measurements() {
     assert!(
         !contract
             .get_approved_measurements(&None, &None)
-            .contains(&extra)
+            .iter()
+            .any(|m| m.measurements == extra)
     );
 }
 
@@ -169,7 +250,11 @@ fn test_remove_measurements() {
 fn test_remove_measurements_not_owner() {
     let mut contract = setup_contract();
     let non_owner = accounts(2);
-    contract.approve_measurements(create_mock_full_measurements_hex());
+    contract.approve_measurements(
+        create_mock_full_measurements_hex(),
+        "agent v1".to_string(),
+        "sha256:abc".to_string(),
+    );
 
     let context = get_context(non_owner, false);
     testing_env!(context.build());
@@ -436,28 +521,68 @@ fn test_register_agent_insufficient_deposit_low() {
     contract.register_agent(create_mock_dstack_attestation());
 }
 
-// Test that owner can update the owner ID
+// Test that the ownership is transferred once the proposed owner accepts it
 #[test]
-fn test_update_owner_id() {
+fn test_transfer_ownership() {
     let mut contract = setup_contract();
     let new_owner = accounts(3);
 
-    contract.update_owner_id(new_owner.clone());
+    contract.propose_owner(new_owner.clone());
+    let contract_info = contract.get_contract_info();
+    assert_eq!(contract_info.owner_id, accounts(0));
+    assert_eq!(contract_info.pending_owner_id, Some(new_owner.clone()));
+
+    let context = get_context(new_owner.clone(), false);
+    testing_env!(context.build());
+    contract.accept_ownership();
     let contract_info = contract.get_contract_info();
     assert_eq!(contract_info.owner_id, new_owner);
+    assert!(contract_info.pending_owner_id.is_none());
+
+    let logs = near_sdk::test_utils::get_logs();
+    assert!(
+        logs.iter()
+            .any(|log| log.contains("\"event\":\"ownership_transferred\""))
+    );
 }
 
-// Test that non-owner cannot update the owner ID
+// Test that non-owner cannot propose a new owner
 #[test]
 #[should_panic(expected = "Caller is not the owner")]
-fn test_update_owner_id_not_owner() {
+fn test_propose_owner_not_owner() {
     let mut contract = setup_contract();
     let non_owner = accounts(2);
     let new_owner = accounts(3);
     let context = get_context(non_owner, false);
     testing_env!(context.build());
 
-    contract.update_owner_id(new_owner);
+    contract.propose_owner(new_owner);
+}
+
+// Test that only the pending owner can accept the ownership
+#[test]
+#[should_panic(expected = "Caller is not the pending owner")]
+fn test_accept_ownership_not_pending_owner() {
+    let mut contract = setup_contract();
+    contract.propose_owner(accounts(3));
+
+    let context = get_context(accounts(2), false);
+    testing_env!(context.build());
+    contract.accept_ownership();
+}
+
+// Test that a cancelled proposal cannot be accepted
+#[test]
+#[should_panic(expected = "Caller is not the pending owner")]
+fn test_cancel_owner_proposal() {
+    let mut contract = setup_contract();
+    contract.propose_owner(accounts(3));
+    contract.cancel_owner_proposal();
+    assert!(contract.get_contract_info().pending_owner_id.is_none());
+
+    let context = get_context(accounts(3), false);
+    testing_env!(context.build());
+    contract.accept_ownership();
 }
 
 // Test that owner can update the MPC contract ID
@@ -469,6 +594,12 @@ fn test_update_mpc_contract_id() {
     contract.update_mpc_contract_id(new_mpc.clone());
     let contract_info = contract.get_contract_info();
     assert_eq!(contract_info.mpc_contract_id, new_mpc);
+
+    let logs = near_sdk::test_utils::get_logs();
+    let log = logs.last().unwrap();
+    assert!(log.contains("\"event\":\"mpc_contract_id_updated\""));
+    assert!(log.contains(&format!("\"old_mpc_contract_id\":\"{}\"", accounts(1))));
+    assert!(log.contains(&format!("\"new_mpc_contract_id\":\"{}\"", new_mpc)));
 }
 
 // Test that non-owner cannot update the MPC contract ID
@@ -493,6 +624,11 @@ fn test_update_attestation_expiration_time() {
     contract.update_attestation_expiration_time(new_expiration_time.clone());
     let contract_info = contract.get_contract_info();
     assert_eq!(contract_info.attestation_expiration_time_ms.0, 200000u64);
+
+    let logs = near_sdk::test_utils::get_logs();
+    let log = logs.last().unwrap();
+    assert!(log.contains("\"old_attestation_expiration_time_ms\":\"100000\""));
+    assert!(log.contains("\"new_attestation_expiration_time_ms\":\"200000\""));
 }
 
 // Test that non-owner cannot update the attestation expiration time
@@ -515,6 +651,7 @@ fn test_get_contract_info() {
     let contract_info = contract.get_contract_info();
     assert_eq!(contract_info.requires_tee, false);
     assert_eq!(contract_info.attestation_expiration_time_ms.0, 100000u64);
+    assert_eq!(contract_info.upgrade_delay_ms.0, 1000u64);
 }
 
 // Test that get_approved_measurements returns approved measurements and pagination works
@@ -523,11 +660,16 @@ fn test_get_approved_measurements() {
     let mut contract = setup_contract();
 
     let default = create_mock_full_measurements_hex();
-    contract.approve_measurements(default.clone());
+    contract.approve_measurements(
+        default.clone(),
+        "agent v1".to_string(),
+        "sha256:abc".to_string(),
+    );
 
     let all = contract.get_approved_measurements(&None, &None);
     assert_eq!(all.len(), 1);
-    assert!(all.contains(&default));
+    assert!(all[0].measurements == default);
+    assert_eq!(all[0].status, MeasurementStatus::Active);
 
     // Test pagination
     let first_two = contract.get_approved_measurements(&Some(0), &Some(2));
@@ -1111,3 +1253,3833 @@ fn test_get_agents_expiration_fields() {
     );
     assert!(matches!(agent2_info.validity, AgentValidity::Valid));
 }
+
+// Deposit used to fund bounties in tests
+const DEPOSIT_ONE_NEAR: NearToken = NearToken::from_near(1);
+
+// Helper function to register a repo and a valid agent (accounts(2)), maintainer is accounts(3)
+fn setup_contract_with_repo() -> Contract {
+    let mut contract = setup_contract();
+    let agent = accounts(2);
+    contract.whitelist_agent_for_local(agent.clone());
+
+    let context = get_context_with_deposit(agent.clone(), false, Some(DEPOSIT_005_NEAR));
+    testing_env!(context.build());
+    contract.register_agent(create_mock_dstack_attestation());
+
+    let context = get_context_with_deposit(agent, false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    let _ = contract.register_repo("owner/repo".to_string(), accounts(3));
+    contract
+}
+
+// Helper function to build the recipients of a one NEAR payout for on_bounty_released
+fn one_near_to(recipient: AccountId) -> Vec<(AccountId, U128)> {
+    vec![(recipient, U128(DEPOSIT_ONE_NEAR.as_yoctonear()))]
+}
+
+const MERGE_COMMIT_SHA: &str = "6dcb09b5b57875f334f61aebed695e2e4193db5e";
+
+// Helper function to stop accounts without a role from funding owner/repo
+fn disable_third_party_funding(contract: &mut Contract) {
+    let context = get_context_with_deposit(accounts(3), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    contract.set_third_party_funding("owner/repo".to_string(), false);
+}
+
+// Helper function to fund an issue bounty as the maintainer
+fn fund_issue_bounty(contract: &mut Contract, number: u64, deposit: NearToken) -> BountyId {
+    let context = get_context_with_deposit(accounts(3), false, Some(with_storage(deposit)));
+    testing_env!(context.build());
+    contract
+        .fund_bounty(
+            "owner/repo".to_string(),
+            Some(number),
+            None,
+            U128(deposit.as_yoctonear()),
+        )
+        .unwrap()
+}
+
+// Test that funding an issue creates an open escrow record and topping up reuses it
+#[test]
+fn test_fund_issue_bounty() {
+    let mut contract = setup_contract_with_repo();
+
+    let bounty_id = fund_issue_bounty(&mut contract, 7, DEPOSIT_ONE_NEAR);
+    let top_up_id = fund_issue_bounty(&mut contract, 7, DEPOSIT_ONE_NEAR);
+    assert_eq!(bounty_id, top_up_id);
+
+    let bounty = contract
+        .get_issue_bounty("owner/repo".to_string(), 7)
+        .unwrap();
+    assert_eq!(bounty.bounty_id, bounty_id);
+    assert_eq!(bounty.amount.0, 2 * DEPOSIT_ONE_NEAR.as_yoctonear());
+    assert_eq!(bounty.status, BountyStatus::Open);
+    assert_eq!(bounty.creator, accounts(3));
+
+    // The repo pool is untouched
+    assert_eq!(contract.get_bounty("owner/repo".to_string()).0, 0);
+}
+
+// Test that a maintainer can move pool funds into an issue bounty
+#[test]
+fn test_create_bounty_from_pool() {
+    let mut contract = setup_contract_with_repo();
+
+    let context =
+        get_context_with_deposit(accounts(3), false, Some(with_storage(DEPOSIT_ONE_NEAR)));
+    testing_env!(context.build());
+    assert!(
+        contract
+            .fund_bounty(
+                "owner/repo".to_string(),
+                None,
+                None,
+                U128(DEPOSIT_ONE_NEAR.as_yoctonear())
+            )
+            .is_none()
+    );
+
+    let amount = DEPOSIT_ONE_NEAR.as_yoctonear() / 4;
+    let bounty_id = contract.create_bounty("owner/repo".to_string(), 3, U128(amount), None);
+
+    assert_eq!(
+        contract.get_bounty_by_id(bounty_id).unwrap().amount.0,
+        amount
+    );
+    assert_eq!(
+        contract.get_bounty("owner/repo".to_string()).0,
+        DEPOSIT_ONE_NEAR.as_yoctonear() - amount
+    );
+}
+
+// Test that only the maintainer can fund a bounty once third-party funding is disabled
+#[test]
+#[should_panic(expected = "Only repo admins and funders can fund the bounty")]
+fn test_fund_bounty_not_maintainer() {
+    let mut contract = setup_contract_with_repo();
+    disable_third_party_funding(&mut contract);
+    let context =
+        get_context_with_deposit(accounts(4), false, Some(with_storage(DEPOSIT_ONE_NEAR)));
+    testing_env!(context.build());
+    contract.fund_bounty(
+        "owner/repo".to_string(),
+        Some(1),
+        None,
+        U128(DEPOSIT_ONE_NEAR.as_yoctonear()),
+    );
+}
+
+// Test that releasing a bounty claims it and returns the unpaid remainder to the pool
+#[test]
+fn test_release_bounty() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = fund_issue_bounty(&mut contract, 7, DEPOSIT_ONE_NEAR);
+    let amount = DEPOSIT_ONE_NEAR.as_yoctonear() / 2;
+
+    let context = get_context_with_deposit(accounts(2), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    let _promise = contract.release_bounty(
+        bounty_id,
+        Some(accounts(4)),
+        None,
+        U128(amount),
+        7,
+        MERGE_COMMIT_SHA.to_string(),
+    );
+
+    // The escrow stays in the bounty until the transfer resolves
+    let bounty = contract.get_bounty_by_id(bounty_id).unwrap();
+    assert_eq!(bounty.status, BountyStatus::Claimed);
+    assert_eq!(bounty.amount.0, DEPOSIT_ONE_NEAR.as_yoctonear());
+    assert_eq!(contract.get_bounty("owner/repo".to_string()).0, 0);
+
+    // The transfer succeeds, the bounty is marked as paid and the remainder goes to the pool
+    set_promise_result(PromiseResult::Successful(vec![]));
+    assert!(contract.on_bounty_released(bounty_id, vec![(accounts(4), U128(amount))], 7));
+    let bounty = contract.get_bounty_by_id(bounty_id).unwrap();
+    assert_eq!(bounty.status, BountyStatus::Paid);
+    assert_eq!(bounty.amount.0, amount);
+    assert_eq!(
+        contract.get_bounty("owner/repo".to_string()).0,
+        DEPOSIT_ONE_NEAR.as_yoctonear() - amount
+    );
+}
+
+// Helper function to set the pool payout amount of owner/repo as the maintainer
+fn set_pool_payout_amount(contract: &mut Contract, amount: u128) {
+    let context = get_context_with_deposit(accounts(3), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    contract.set_pool_payout_amount("owner/repo".to_string(), Some(U128(amount)));
+}
+
+// Test that the agent opens a PR bounty out of the pool and releases it like any other bounty
+#[test]
+fn test_create_pool_bounty() {
+    let mut contract = setup_contract_with_repo();
+    sponsor_pool(&mut contract, accounts(3));
+    let amount = DEPOSIT_ONE_NEAR.as_yoctonear() / 4;
+    set_pool_payout_amount(&mut contract, amount);
+    assert_eq!(
+        contract.get_pool_payout_amount("owner/repo".to_string()),
+        Some(U128(amount))
+    );
+
+    let context = get_context_with_deposit(accounts(2), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    let PromiseOrValue::Value(bounty_id) = contract.create_pool_bounty("owner/repo".to_string(), 9)
+    else {
+        panic!("Expected a bounty id");
+    };
+    let bounty = contract
+        .get_issue_bounty("owner/repo".to_string(), 9)
+        .unwrap();
+    assert_eq!(bounty.bounty_id, bounty_id);
+    assert_eq!(bounty.amount.0, amount);
+    assert_eq!(
+        contract.get_bounty("owner/repo".to_string()).0,
+        DEPOSIT_ONE_NEAR.as_yoctonear() - amount
+    );
+
+    let _ = contract.release_bounty(
+        bounty_id,
+        Some(accounts(4)),
+        None,
+        U128(amount),
+        9,
+        MERGE_COMMIT_SHA.to_string(),
+    );
+    assert_eq!(
+        contract.get_bounty_by_id(bounty_id).unwrap().status,
+        BountyStatus::Claimed
+    );
+}
+
+// Test that the agent cannot top up an open bounty out of the pool
+#[test]
+#[should_panic(expected = "Bounty already exists")]
+fn test_create_pool_bounty_for_open_bounty() {
+    let mut contract = setup_contract_with_repo();
+    sponsor_pool(&mut contract, accounts(3));
+    set_pool_payout_amount(&mut contract, 1);
+    fund_issue_bounty(&mut contract, 9, DEPOSIT_ONE_NEAR);
+
+    let context = get_context_with_deposit(accounts(2), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    let _ = contract.create_pool_bounty("owner/repo".to_string(), 9);
+}
+
+// Test that only agents can open a bounty out of the pool
+#[test]
+#[should_panic(expected = "Agent not registered")]
+fn test_create_pool_bounty_not_agent() {
+    let mut contract = setup_contract_with_repo();
+    sponsor_pool(&mut contract, accounts(3));
+    set_pool_payout_amount(&mut contract, 1);
+
+    let context = get_context_with_deposit(accounts(3), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    let _ = contract.create_pool_bounty("owner/repo".to_string(), 9);
+}
+
+// Test that agents cannot open pool bounties until a repo admin sets the amount
+#[test]
+#[should_panic(expected = "Repo has no pool payout amount")]
+fn test_create_pool_bounty_without_amount() {
+    let mut contract = setup_contract_with_repo();
+    sponsor_pool(&mut contract, accounts(3));
+
+    let context = get_context_with_deposit(accounts(2), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    let _ = contract.create_pool_bounty("owner/repo".to_string(), 9);
+}
+
+// Test that a pool bounty above the payout threshold is refused before the pool is debited
+#[test]
+#[should_panic(expected = "Pool payout amount is above the payout threshold")]
+fn test_create_pool_bounty_above_threshold() {
+    let mut contract = setup_contract_with_repo();
+    sponsor_pool(&mut contract, accounts(3));
+    set_pool_payout_amount(&mut contract, DEPOSIT_ONE_NEAR.as_yoctonear() / 2);
+    contract.set_payout_threshold(
+        "owner/repo".to_string(),
+        None,
+        Some(U128(DEPOSIT_ONE_NEAR.as_yoctonear() / 4)),
+    );
+
+    let context = get_context_with_deposit(accounts(2), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    let _ = contract.create_pool_bounty("owner/repo".to_string(), 9);
+}
+
+// Test that a pool bounty over the repo daily limit is refused before the pool is debited
+#[test]
+#[should_panic(expected = "Repo daily payout amount limit exceeded")]
+fn test_create_pool_bounty_over_limit() {
+    let mut contract = setup_contract_with_repo();
+    sponsor_pool(&mut contract, accounts(3));
+    set_pool_payout_amount(&mut contract, DEPOSIT_ONE_NEAR.as_yoctonear() / 2);
+    contract.set_repo_payout_limit(
+        "owner/repo".to_string(),
+        Some(RateLimit {
+            max_count: None,
+            max_amount: Some(U128(DEPOSIT_ONE_NEAR.as_yoctonear() / 4)),
+        }),
+    );
+
+    let context = get_context_with_deposit(accounts(2), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    let _ = contract.create_pool_bounty("owner/repo".to_string(), 9);
+}
+
+// Test that only a repo admin can set the pool payout amount
+#[test]
+#[should_panic(expected = "Only repo admins can set the pool payout amount")]
+fn test_set_pool_payout_amount_not_admin() {
+    let mut contract = setup_contract_with_repo();
+    let context = get_context_with_deposit(accounts(4), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    contract.set_pool_payout_amount("owner/repo".to_string(), Some(U128(1)));
+}
+
+// Test that the agent pays for the storage of the payout record
+#[test]
+#[should_panic(expected = "Attached deposit must be greater than storage cost")]
+fn test_release_bounty_without_storage_deposit() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = fund_issue_bounty(&mut contract, 7, DEPOSIT_ONE_NEAR);
+
+    let context = get_context(accounts(2), false);
+    testing_env!(context.build());
+    let _ = contract.release_bounty(
+        bounty_id,
+        Some(accounts(4)),
+        None,
+        U128(DEPOSIT_ONE_NEAR.as_yoctonear()),
+        7,
+        MERGE_COMMIT_SHA.to_string(),
+    );
+}
+
+// Test that a release of nothing is refused
+#[test]
+#[should_panic(expected = "Amount must be positive")]
+fn test_release_bounty_zero_amount() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = fund_issue_bounty(&mut contract, 7, DEPOSIT_ONE_NEAR);
+
+    let context = get_context_with_deposit(accounts(2), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    let _ = contract.release_bounty(
+        bounty_id,
+        Some(accounts(4)),
+        None,
+        U128(0),
+        7,
+        MERGE_COMMIT_SHA.to_string(),
+    );
+}
+
+// Helper function to release the full escrow of a bounty for PR 7 as the agent
+fn release_for_pr(contract: &mut Contract, bounty_id: BountyId, merge_commit_sha: &str) {
+    let amount = contract.get_bounty_by_id(bounty_id).unwrap().amount;
+    let context = get_context_with_deposit_and_timestamp(
+        accounts(2),
+        false,
+        Some(DEPOSIT_STORAGE),
+        Some(5000),
+    );
+    testing_env!(context.build());
+    let _ = contract.release_bounty(
+        bounty_id,
+        Some(accounts(4)),
+        None,
+        amount,
+        7,
+        merge_commit_sha.to_string(),
+    );
+}
+
+// Test that the payout of a merged PR is recorded and shown by get_payout
+#[test]
+fn test_get_payout() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = fund_issue_bounty(&mut contract, 7, DEPOSIT_ONE_NEAR);
+    assert!(contract.get_payout("owner/repo".to_string(), 7).is_none());
+
+    release_for_pr(&mut contract, bounty_id, MERGE_COMMIT_SHA);
+
+    let payout = contract.get_payout("owner/repo".to_string(), 7).unwrap();
+    assert_eq!(payout.bounty_id, bounty_id);
+    assert_eq!(payout.merge_commit_sha, MERGE_COMMIT_SHA);
+    assert_eq!(payout.recipient, Some(accounts(4)));
+    assert_eq!(payout.amount.0, DEPOSIT_ONE_NEAR.as_yoctonear());
+    assert_eq!(payout.paid_at_ms.0, 5000);
+}
+
+// Test that a redelivered merge webhook cannot pay the same PR twice
+#[test]
+#[should_panic(expected = "Payout already processed for this merge commit")]
+fn test_release_bounty_duplicate_merge_commit() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = fund_issue_bounty(&mut contract, 7, DEPOSIT_ONE_NEAR);
+    release_for_pr(&mut contract, bounty_id, MERGE_COMMIT_SHA);
+    set_promise_result(PromiseResult::Successful(vec![]));
+    contract.on_bounty_released(bounty_id, one_near_to(accounts(4)), 7);
+
+    // The issue is funded again but the PR was already paid
+    let bounty_id = fund_issue_bounty(&mut contract, 7, DEPOSIT_ONE_NEAR);
+    release_for_pr(&mut contract, bounty_id, MERGE_COMMIT_SHA);
+}
+
+// Test that a PR cannot be paid again under a different merge commit
+#[test]
+#[should_panic(expected = "Payout already processed for this PR")]
+fn test_release_bounty_duplicate_pr() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = fund_issue_bounty(&mut contract, 7, DEPOSIT_ONE_NEAR);
+    release_for_pr(&mut contract, bounty_id, MERGE_COMMIT_SHA);
+    set_promise_result(PromiseResult::Successful(vec![]));
+    contract.on_bounty_released(bounty_id, one_near_to(accounts(4)), 7);
+
+    let bounty_id = fund_issue_bounty(&mut contract, 7, DEPOSIT_ONE_NEAR);
+    release_for_pr(&mut contract, bounty_id, "another-sha");
+}
+
+// Test that a failed payout is forgotten so the agent can retry it
+#[test]
+fn test_release_bounty_retry_after_failure() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = fund_issue_bounty(&mut contract, 7, DEPOSIT_ONE_NEAR);
+    release_for_pr(&mut contract, bounty_id, MERGE_COMMIT_SHA);
+
+    set_promise_result(PromiseResult::Failed);
+    assert!(!contract.on_bounty_released(bounty_id, one_near_to(accounts(4)), 7));
+    assert!(contract.get_payout("owner/repo".to_string(), 7).is_none());
+
+    release_for_pr(&mut contract, bounty_id, MERGE_COMMIT_SHA);
+    assert!(contract.get_payout("owner/repo".to_string(), 7).is_some());
+}
+
+// Helper function to split a one NEAR bounty for PR 7 as 70/20/10
+fn release_split(contract: &mut Contract, bounty_id: BountyId) {
+    let context = get_context_with_deposit(accounts(2), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    let _ = contract.release_bounty_split(
+        bounty_id,
+        vec![
+            PayoutShare {
+                account_id: accounts(4),
+                basis_points: 7000,
+            },
+            PayoutShare {
+                account_id: accounts(5),
+                basis_points: 2000,
+            },
+            PayoutShare {
+                account_id: accounts(3),
+                basis_points: 1000,
+            },
+        ],
+        U128(DEPOSIT_ONE_NEAR.as_yoctonear()),
+        7,
+        MERGE_COMMIT_SHA.to_string(),
+    );
+}
+
+// Helper function to build the recipients of the 70/20/10 split for on_bounty_released
+fn split_recipients() -> Vec<(AccountId, U128)> {
+    let tenth = DEPOSIT_ONE_NEAR.as_yoctonear() / 10;
+    vec![
+        (accounts(4), U128(7 * tenth)),
+        (accounts(5), U128(2 * tenth)),
+        (accounts(3), U128(tenth)),
+    ]
+}
+
+// Test that a bounty can be split between several recipients in one call
+#[test]
+fn test_release_bounty_split() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = fund_issue_bounty(&mut contract, 7, DEPOSIT_ONE_NEAR);
+    release_split(&mut contract, bounty_id);
+
+    let logs = near_sdk::test_utils::get_logs();
+    assert_eq!(logs.len(), 1);
+    assert!(logs[0].contains("\"event\":\"bounty_split_released\""));
+
+    let payout = contract.get_payout("owner/repo".to_string(), 7).unwrap();
+    assert_eq!(payout.recipient, Some(accounts(4)));
+    assert_eq!(payout.amount.0, DEPOSIT_ONE_NEAR.as_yoctonear());
+    let expected: Vec<(AccountId, u16, u128)> = split_recipients()
+        .into_iter()
+        .zip([7000, 2000, 1000])
+        .map(|((account_id, amount), basis_points)| (account_id, basis_points, amount.0))
+        .collect();
+    let shares: Vec<(AccountId, u16, u128)> = payout
+        .shares
+        .into_iter()
+        .map(|share| (share.account_id, share.basis_points, share.amount.0))
+        .collect();
+    assert_eq!(shares, expected);
+
+    set_promise_results((0..3).map(|_| PromiseResult::Successful(vec![])).collect());
+    assert!(contract.on_bounty_released(bounty_id, split_recipients(), 7));
+    let bounty = contract.get_bounty_by_id(bounty_id).unwrap();
+    assert_eq!(bounty.status, BountyStatus::Paid);
+    assert_eq!(bounty.amount.0, DEPOSIT_ONE_NEAR.as_yoctonear());
+    let logs = near_sdk::test_utils::get_logs();
+    assert_eq!(
+        logs.iter()
+            .filter(|log| log.contains("\"event\":\"bounty_paid\""))
+            .count(),
+        3
+    );
+}
+
+// Test that the failed shares of a split go back to the repo pool
+#[test]
+fn test_release_bounty_split_partial_failure() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = fund_issue_bounty(&mut contract, 7, DEPOSIT_ONE_NEAR);
+    release_split(&mut contract, bounty_id);
+
+    set_promise_results(vec![
+        PromiseResult::Successful(vec![]),
+        PromiseResult::Failed,
+        PromiseResult::Successful(vec![]),
+    ]);
+    assert!(!contract.on_bounty_released(bounty_id, split_recipients(), 7));
+
+    let tenth = DEPOSIT_ONE_NEAR.as_yoctonear() / 10;
+    let bounty = contract.get_bounty_by_id(bounty_id).unwrap();
+    assert_eq!(bounty.status, BountyStatus::Paid);
+    assert_eq!(contract.get_bounty("owner/repo".to_string()).0, 2 * tenth);
+
+    let payout = contract.get_payout("owner/repo".to_string(), 7).unwrap();
+    assert_eq!(payout.amount.0, 8 * tenth);
+    assert_eq!(payout.shares.len(), 2);
+    assert!(
+        payout
+            .shares
+            .iter()
+            .all(|share| share.account_id != accounts(5))
+    );
+}
+
+// Test that a split where every transfer failed reopens the bounty
+#[test]
+fn test_release_bounty_split_all_failed() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = fund_issue_bounty(&mut contract, 7, DEPOSIT_ONE_NEAR);
+    release_split(&mut contract, bounty_id);
+
+    set_promise_results((0..3).map(|_| PromiseResult::Failed).collect());
+    assert!(!contract.on_bounty_released(bounty_id, split_recipients(), 7));
+
+    let bounty = contract.get_bounty_by_id(bounty_id).unwrap();
+    assert_eq!(bounty.status, BountyStatus::Open);
+    assert_eq!(bounty.amount.0, DEPOSIT_ONE_NEAR.as_yoctonear());
+    assert!(contract.get_payout("owner/repo".to_string(), 7).is_none());
+}
+
+// Test that rounding dust goes to the first share
+#[test]
+fn test_split_amount_dust() {
+    let shares = vec![
+        PayoutShare {
+            account_id: accounts(4),
+            basis_points: 3333,
+        },
+        PayoutShare {
+            account_id: accounts(5),
+            basis_points: 3333,
+        },
+        PayoutShare {
+            account_id: accounts(3),
+            basis_points: 3334,
+        },
+    ];
+    let amounts: Vec<u128> = crate::internal::bounty::split_amount(&shares, 10)
+        .into_iter()
+        .map(|recipient| recipient.amount)
+        .collect();
+    assert_eq!(amounts, vec![4, 3, 3]);
+}
+
+// Test that split shares must add up to 100%
+#[test]
+#[should_panic(expected = "Shares must sum to 10000 basis points")]
+fn test_release_bounty_split_invalid_total() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = fund_issue_bounty(&mut contract, 7, DEPOSIT_ONE_NEAR);
+
+    let context = get_context_with_deposit(accounts(2), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    let _ = contract.release_bounty_split(
+        bounty_id,
+        vec![
+            PayoutShare {
+                account_id: accounts(4),
+                basis_points: 7000,
+            },
+            PayoutShare {
+                account_id: accounts(5),
+                basis_points: 2000,
+            },
+        ],
+        U128(DEPOSIT_ONE_NEAR.as_yoctonear()),
+        7,
+        MERGE_COMMIT_SHA.to_string(),
+    );
+}
+
+// Test that an account cannot appear twice in a split
+#[test]
+#[should_panic(expected = "Duplicate share recipient")]
+fn test_release_bounty_split_duplicate_recipient() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = fund_issue_bounty(&mut contract, 7, DEPOSIT_ONE_NEAR);
+
+    let context = get_context_with_deposit(accounts(2), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    let _ = contract.release_bounty_split(
+        bounty_id,
+        vec![
+            PayoutShare {
+                account_id: accounts(4),
+                basis_points: 5000,
+            },
+            PayoutShare {
+                account_id: accounts(4),
+                basis_points: 5000,
+            },
+        ],
+        U128(DEPOSIT_ONE_NEAR.as_yoctonear()),
+        7,
+        MERGE_COMMIT_SHA.to_string(),
+    );
+}
+
+// Test that funding, release and payout emit the events needed to rebuild the ledger
+#[test]
+fn test_bounty_ledger_events() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = fund_issue_bounty(&mut contract, 7, DEPOSIT_ONE_NEAR);
+
+    let logs = near_sdk::test_utils::get_logs();
+    assert!(logs[0].contains("\"event\":\"bounty_created\""));
+    assert!(logs[1].contains("\"event\":\"bounty_funded\""));
+    assert!(logs[1].contains("\"number\":7"));
+    assert!(logs[1].contains(&format!(
+        "\"amount\":\"{}\"",
+        DEPOSIT_ONE_NEAR.as_yoctonear()
+    )));
+    assert!(logs[1].contains("\"from_pool\":false"));
+
+    // Topping up the open bounty does not create a new one
+    fund_issue_bounty(&mut contract, 7, DEPOSIT_ONE_NEAR);
+    let logs = near_sdk::test_utils::get_logs();
+    assert_eq!(logs.len(), 1);
+    assert!(logs[0].contains("\"event\":\"bounty_funded\""));
+
+    let amount = DEPOSIT_ONE_NEAR.as_yoctonear();
+    let context = get_context_with_deposit(accounts(2), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    let _promise = contract.release_bounty(
+        bounty_id,
+        Some(accounts(4)),
+        None,
+        U128(amount),
+        7,
+        MERGE_COMMIT_SHA.to_string(),
+    );
+    let logs = near_sdk::test_utils::get_logs();
+    assert!(logs[0].contains("\"event\":\"bounty_released\""));
+    assert!(logs[0].contains(&format!("\"returned_to_pool\":\"{}\"", amount)));
+
+    set_promise_result(PromiseResult::Successful(vec![]));
+    assert!(contract.on_bounty_released(bounty_id, vec![(accounts(4), U128(amount))], 7));
+    let logs = near_sdk::test_utils::get_logs();
+    assert!(logs[0].contains("\"event\":\"bounty_paid\""));
+    assert!(logs[0].contains(&format!("\"recipient\":\"{}\"", accounts(4))));
+}
+
+// Test that moving pool funds into an escrow and cancelling it emit events
+#[test]
+fn test_create_and_cancel_bounty_events() {
+    let mut contract = setup_contract_with_repo();
+    let context =
+        get_context_with_deposit(accounts(3), false, Some(with_storage(DEPOSIT_ONE_NEAR)));
+    testing_env!(context.build());
+    contract.fund_bounty(
+        "owner/repo".to_string(),
+        None,
+        None,
+        U128(DEPOSIT_ONE_NEAR.as_yoctonear()),
+    );
+    let logs = near_sdk::test_utils::get_logs();
+    assert!(logs[0].contains("\"event\":\"bounty_funded\""));
+    assert!(logs[0].contains("\"bounty_id\":null"));
+
+    let context = get_context_with_deposit(accounts(3), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    let bounty_id = contract.create_bounty(
+        "owner/repo".to_string(),
+        9,
+        U128(DEPOSIT_ONE_NEAR.as_yoctonear()),
+        None,
+    );
+    let logs = near_sdk::test_utils::get_logs();
+    assert!(logs[1].contains("\"event\":\"bounty_funded\""));
+    assert!(logs[1].contains("\"from_pool\":true"));
+
+    let context = get_context_with_deposit(accounts(3), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    contract.cancel_bounty(bounty_id);
+    let logs = near_sdk::test_utils::get_logs();
+    assert!(logs[0].contains("\"event\":\"bounty_cancelled\""));
+}
+
+// Test that a failed transfer reopens the bounty with its escrow intact
+#[test]
+fn test_release_bounty_transfer_failed() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = fund_issue_bounty(&mut contract, 7, DEPOSIT_ONE_NEAR);
+
+    let context = get_context_with_deposit(accounts(2), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    let _promise = contract.release_bounty(
+        bounty_id,
+        Some(accounts(4)),
+        None,
+        U128(DEPOSIT_ONE_NEAR.as_yoctonear()),
+        7,
+        MERGE_COMMIT_SHA.to_string(),
+    );
+
+    set_promise_result(PromiseResult::Failed);
+    assert!(!contract.on_bounty_released(bounty_id, one_near_to(accounts(4)), 7));
+    let bounty = contract.get_bounty_by_id(bounty_id).unwrap();
+    assert_eq!(bounty.status, BountyStatus::Open);
+    assert_eq!(bounty.amount.0, DEPOSIT_ONE_NEAR.as_yoctonear());
+}
+
+// Test that a bounty cannot pay out more than it holds
+#[test]
+#[should_panic(expected = "Insufficient bounty funds")]
+fn test_release_bounty_more_than_held() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = fund_issue_bounty(&mut contract, 7, DEPOSIT_ONE_NEAR);
+
+    let context = get_context_with_deposit(accounts(2), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    let _ = contract.release_bounty(
+        bounty_id,
+        Some(accounts(4)),
+        None,
+        U128(DEPOSIT_ONE_NEAR.as_yoctonear() + 1),
+        7,
+        MERGE_COMMIT_SHA.to_string(),
+    );
+}
+
+// Test that cancelling a bounty returns the escrow to the repo pool
+#[test]
+fn test_cancel_bounty() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = fund_issue_bounty(&mut contract, 7, DEPOSIT_ONE_NEAR);
+
+    contract.cancel_bounty(bounty_id);
+
+    let bounty = contract.get_bounty_by_id(bounty_id).unwrap();
+    assert_eq!(bounty.status, BountyStatus::Cancelled);
+    assert_eq!(bounty.amount.0, 0);
+    assert_eq!(
+        contract.get_bounty("owner/repo".to_string()).0,
+        DEPOSIT_ONE_NEAR.as_yoctonear()
+    );
+
+    // Funding the issue again opens a new bounty
+    let new_bounty_id = fund_issue_bounty(&mut contract, 7, DEPOSIT_ONE_NEAR);
+    assert_ne!(bounty_id, new_bounty_id);
+}
+
+// Helper function to fund issue 7 as the maintainer with a deadline of 10 seconds
+fn fund_bounty_with_deadline(contract: &mut Contract) -> BountyId {
+    let context = get_context_with_deposit_and_timestamp(
+        accounts(3),
+        false,
+        Some(with_storage(DEPOSIT_ONE_NEAR)),
+        Some(0),
+    );
+    testing_env!(context.build());
+    contract
+        .fund_bounty(
+            "owner/repo".to_string(),
+            Some(7),
+            Some(U64::from(10_000)),
+            U128(DEPOSIT_ONE_NEAR.as_yoctonear()),
+        )
+        .unwrap()
+}
+
+// Test that anyone can expire a bounty, which credits the escrow as a refund to its funder
+#[test]
+fn test_reclaim_expired_bounty() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = fund_bounty_with_deadline(&mut contract);
+
+    let context = get_context_with_deposit_and_timestamp(accounts(4), false, None, Some(4_000));
+    testing_env!(context.build());
+    let bounty = contract.get_bounty_by_id(bounty_id).unwrap();
+    assert_eq!(bounty.deadline_ms, Some(U64::from(10_000)));
+    assert_eq!(bounty.time_remaining_ms, Some(U64::from(6_000)));
+
+    let context = get_context_with_deposit_and_timestamp(
+        accounts(4),
+        false,
+        Some(DEPOSIT_STORAGE),
+        Some(10_000),
+    );
+    testing_env!(context.build());
+    assert_eq!(
+        contract
+            .get_bounty_by_id(bounty_id)
+            .unwrap()
+            .time_remaining_ms,
+        Some(U64::from(0))
+    );
+    contract.reclaim_expired_bounty(bounty_id);
+    let logs = near_sdk::test_utils::get_logs();
+    assert!(logs[0].contains("\"event\":\"bounty_refund_credited\""));
+    assert!(logs[1].contains("\"event\":\"bounty_expired\""));
+    assert!(logs[1].contains(&format!("\"funder\":\"{}\"", accounts(3))));
+
+    let bounty = contract.get_bounty_by_id(bounty_id).unwrap();
+    assert_eq!(bounty.status, BountyStatus::Expired);
+    assert_eq!(bounty.amount.0, 0);
+    assert_eq!(contract.get_bounty("owner/repo".to_string()).0, 0);
+    assert_eq!(
+        contract.get_refund(accounts(3), None).0,
+        DEPOSIT_ONE_NEAR.as_yoctonear()
+    );
+}
+
+// Test that a bounty cannot be reclaimed before its deadline
+#[test]
+#[should_panic(expected = "Bounty deadline has not passed")]
+fn test_reclaim_bounty_before_deadline() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = fund_bounty_with_deadline(&mut contract);
+
+    let context = get_context_with_deposit_and_timestamp(
+        accounts(4),
+        false,
+        Some(DEPOSIT_STORAGE),
+        Some(9_999),
+    );
+    testing_env!(context.build());
+    contract.reclaim_expired_bounty(bounty_id);
+}
+
+// Test that a bounty without a deadline cannot be reclaimed
+#[test]
+#[should_panic(expected = "Bounty has no deadline")]
+fn test_reclaim_bounty_without_deadline() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = fund_issue_bounty(&mut contract, 7, DEPOSIT_ONE_NEAR);
+    contract.reclaim_expired_bounty(bounty_id);
+}
+
+// Test that a failed refund withdrawal can be retried
+#[test]
+fn test_withdraw_refund_failed() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = fund_bounty_with_deadline(&mut contract);
+
+    let context = get_context_with_deposit_and_timestamp(
+        accounts(3),
+        false,
+        Some(DEPOSIT_STORAGE),
+        Some(10_000),
+    );
+    testing_env!(context.build());
+    contract.reclaim_expired_bounty(bounty_id);
+    let _ = contract.withdraw_refund(None);
+    assert_eq!(contract.get_refund(accounts(3), None).0, 0);
+
+    set_promise_result(PromiseResult::Failed);
+    assert!(!contract.on_refund_withdrawn(
+        accounts(3),
+        None,
+        U128(DEPOSIT_ONE_NEAR.as_yoctonear())
+    ));
+    assert_eq!(
+        contract.get_refund(accounts(3), None).0,
+        DEPOSIT_ONE_NEAR.as_yoctonear()
+    );
+    let logs = near_sdk::test_utils::get_logs();
+    assert_eq!(logs.len(), 1);
+    assert!(logs[0].contains("\"event\":\"refund_withdraw_failed\""));
+}
+
+// Test that a refund withdrawal is only reported once the transfer succeeded
+#[test]
+fn test_withdraw_refund_event() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = fund_bounty_with_deadline(&mut contract);
+
+    let context = get_context_with_deposit_and_timestamp(
+        accounts(3),
+        false,
+        Some(DEPOSIT_STORAGE),
+        Some(10_000),
+    );
+    testing_env!(context.build());
+    contract.reclaim_expired_bounty(bounty_id);
+    let _ = contract.withdraw_refund(None);
+
+    set_promise_result(PromiseResult::Successful(vec![]));
+    assert!(contract.on_refund_withdrawn(accounts(3), None, U128(DEPOSIT_ONE_NEAR.as_yoctonear())));
+    assert_eq!(contract.get_refund(accounts(3), None).0, 0);
+    let logs = near_sdk::test_utils::get_logs();
+    assert_eq!(logs.len(), 1);
+    assert!(logs[0].contains("\"event\":\"refund_withdrawn\""));
+}
+
+// Test that the funder can extend the deadline, which delays the refund
+#[test]
+#[should_panic(expected = "Bounty deadline has not passed")]
+fn test_extend_bounty_deadline() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = fund_bounty_with_deadline(&mut contract);
+
+    contract.extend_bounty_deadline(bounty_id, U64::from(20_000));
+    assert_eq!(
+        contract.get_bounty_by_id(bounty_id).unwrap().deadline_ms,
+        Some(U64::from(20_000))
+    );
+
+    let context = get_context_with_deposit_and_timestamp(
+        accounts(4),
+        false,
+        Some(DEPOSIT_STORAGE),
+        Some(10_000),
+    );
+    testing_env!(context.build());
+    contract.reclaim_expired_bounty(bounty_id);
+}
+
+// Test that the deadline cannot be moved earlier
+#[test]
+#[should_panic(expected = "Deadline can only be extended")]
+fn test_extend_bounty_deadline_shorter() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = fund_bounty_with_deadline(&mut contract);
+    contract.extend_bounty_deadline(bounty_id, U64::from(5_000));
+}
+
+// Test that only the funder can extend the deadline
+#[test]
+#[should_panic(expected = "Only the funder can extend the deadline")]
+fn test_extend_bounty_deadline_not_funder() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = fund_bounty_with_deadline(&mut contract);
+
+    let context = get_context(accounts(4), false);
+    testing_env!(context.build());
+    contract.extend_bounty_deadline(bounty_id, U64::from(20_000));
+}
+
+// Test that topping up an existing bounty cannot change its deadline
+#[test]
+#[should_panic(expected = "Bounty already exists, its deadline can only be extended by the funder")]
+fn test_fund_existing_bounty_with_deadline() {
+    let mut contract = setup_contract_with_repo();
+    fund_issue_bounty(&mut contract, 7, DEPOSIT_ONE_NEAR);
+    fund_bounty_with_deadline(&mut contract);
+}
+
+// Test that a cancelled bounty cannot be released
+#[test]
+#[should_panic(expected = "Bounty is not open")]
+fn test_release_cancelled_bounty() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = fund_issue_bounty(&mut contract, 7, DEPOSIT_ONE_NEAR);
+    contract.cancel_bounty(bounty_id);
+
+    let context = get_context_with_deposit(accounts(2), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    let _ = contract.release_bounty(
+        bounty_id,
+        Some(accounts(4)),
+        None,
+        U128(1),
+        7,
+        MERGE_COMMIT_SHA.to_string(),
+    );
+}
+
+// Helper function to accept a mock token contract (accounts(5)) and fund with it through ft_on_transfer
+// The sender first deposits NEAR for the storage the funding uses
+fn ft_fund(contract: &mut Contract, sender: AccountId, amount: u128, msg: &str) {
+    let context = get_context(accounts(0), false);
+    testing_env!(context.build());
+    contract.add_accepted_token(accounts(5));
+
+    let context = get_context_with_deposit(sender.clone(), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    contract.storage_deposit(None);
+
+    let context = get_context(accounts(5), false);
+    testing_env!(context.build());
+    let _ = contract.ft_on_transfer(sender, U128(amount), msg.to_string());
+}
+
+// Test that accepted tokens sent with ft_transfer_call fund the repo pool or an issue bounty
+#[test]
+fn test_ft_on_transfer() {
+    let mut contract = setup_contract_with_repo();
+
+    ft_fund(
+        &mut contract,
+        accounts(3),
+        100,
+        r#"{"repo_id": "owner/repo"}"#,
+    );
+    ft_fund(
+        &mut contract,
+        accounts(3),
+        40,
+        r#"{"repo_id": "owner/repo", "number": 9}"#,
+    );
+
+    assert_eq!(
+        contract
+            .get_ft_bounty("owner/repo".to_string(), accounts(5))
+            .0,
+        100
+    );
+    let bounty = contract
+        .get_issue_bounty("owner/repo".to_string(), 9)
+        .unwrap();
+    assert_eq!(bounty.token_id, Some(accounts(5)));
+    assert_eq!(bounty.amount.0, 40);
+    assert_eq!(bounty.creator, accounts(3));
+    assert_eq!(contract.get_bounty("owner/repo".to_string()).0, 0);
+}
+
+// Test that token funding is refused when the sender has not deposited for its storage
+#[test]
+#[should_panic(expected = "Storage balance must cover the storage cost")]
+fn test_ft_on_transfer_without_storage_balance() {
+    let mut contract = setup_contract_with_repo();
+    let context = get_context(accounts(0), false);
+    testing_env!(context.build());
+    contract.add_accepted_token(accounts(5));
+
+    let context = get_context(accounts(5), false);
+    testing_env!(context.build());
+    let _ = contract.ft_on_transfer(
+        accounts(3),
+        U128(1),
+        r#"{"repo_id": "owner/repo", "number": 9}"#.to_string(),
+    );
+}
+
+// Test that the storage balance pays for token funding and the rest can be withdrawn
+#[test]
+fn test_storage_deposit_and_withdraw() {
+    let mut contract = setup_contract_with_repo();
+    ft_fund(
+        &mut contract,
+        accounts(3),
+        40,
+        r#"{"repo_id": "owner/repo", "number": 9}"#,
+    );
+    let balance = contract.storage_balance_of(accounts(3)).unwrap();
+    assert!(balance.available.0 > 0);
+    assert!(balance.available.0 < DEPOSIT_STORAGE.as_yoctonear());
+    assert!(contract.storage_balance_of(accounts(4)).is_none());
+
+    let context = get_context_with_deposit(accounts(3), false, Some(NearToken::from_yoctonear(1)));
+    testing_env!(context.build());
+    let _ = contract.storage_withdraw(None);
+    assert!(contract.storage_balance_of(accounts(3)).is_none());
+    assert_eq!(transferred_to(&accounts(3)), balance.available.0);
+}
+
+// Test that the storage withdrawal callback reports the outcome and credits failed transfers back
+#[test]
+fn test_storage_withdraw_callback() {
+    let mut contract = setup_contract_with_repo();
+    ft_fund(
+        &mut contract,
+        accounts(3),
+        40,
+        r#"{"repo_id": "owner/repo", "number": 9}"#,
+    );
+    let balance = contract.storage_balance_of(accounts(3)).unwrap();
+    let context = get_context_with_deposit(accounts(3), false, Some(NearToken::from_yoctonear(1)));
+    testing_env!(context.build());
+    let _ = contract.storage_withdraw(None);
+
+    set_promise_result(PromiseResult::Successful(vec![]));
+    assert!(contract.on_storage_withdrawn(accounts(3), balance.available));
+    assert!(contract.storage_balance_of(accounts(3)).is_none());
+    let logs = near_sdk::test_utils::get_logs();
+    assert_eq!(logs.len(), 1);
+    assert!(logs[0].contains("\"event\":\"storage_withdrawn\""));
+
+    set_promise_result(PromiseResult::Failed);
+    assert!(!contract.on_storage_withdrawn(accounts(3), balance.available));
+    assert_eq!(
+        contract.storage_balance_of(accounts(3)).unwrap().available,
+        balance.available
+    );
+    let logs = near_sdk::test_utils::get_logs();
+    assert_eq!(logs.len(), 1);
+    assert!(logs[0].contains("\"event\":\"storage_withdraw_failed\""));
+}
+
+// Test that tokens which are not accepted are refused
+#[test]
+#[should_panic(expected = "Token is not accepted for bounties")]
+fn test_ft_on_transfer_token_not_accepted() {
+    let mut contract = setup_contract_with_repo();
+    let context = get_context(accounts(5), false);
+    testing_env!(context.build());
+    let _ = contract.ft_on_transfer(
+        accounts(3),
+        U128(100),
+        r#"{"repo_id": "owner/repo"}"#.to_string(),
+    );
+}
+
+// Test that only the maintainer can fund with tokens once third-party funding is disabled
+#[test]
+#[should_panic(expected = "Only repo admins and funders can fund the bounty")]
+fn test_ft_on_transfer_not_maintainer() {
+    let mut contract = setup_contract_with_repo();
+    disable_third_party_funding(&mut contract);
+    ft_fund(
+        &mut contract,
+        accounts(4),
+        100,
+        r#"{"repo_id": "owner/repo"}"#,
+    );
+}
+
+// Test that a NEAR bounty cannot be topped up with tokens
+#[test]
+#[should_panic(expected = "Bounty is funded with a different token")]
+fn test_ft_on_transfer_different_token() {
+    let mut contract = setup_contract_with_repo();
+    fund_issue_bounty(&mut contract, 9, DEPOSIT_ONE_NEAR);
+    ft_fund(
+        &mut contract,
+        accounts(3),
+        40,
+        r#"{"repo_id": "owner/repo", "number": 9}"#,
+    );
+}
+
+// Test that a failed token withdrawal restores the pool balance
+#[test]
+fn test_withdraw_ft_bounty_transfer_failed() {
+    let mut contract = setup_contract_with_repo();
+    ft_fund(
+        &mut contract,
+        accounts(3),
+        100,
+        r#"{"repo_id": "owner/repo"}"#,
+    );
+
+    let context = get_context(accounts(3), false);
+    testing_env!(context.build());
+    let _ = contract.withdraw_bounty("owner/repo".to_string(), U128(60), Some(accounts(5)));
+    assert_eq!(
+        contract
+            .get_ft_bounty("owner/repo".to_string(), accounts(5))
+            .0,
+        40
+    );
+
+    set_promise_result(PromiseResult::Failed);
+    assert!(!contract.on_bounty_withdrawn(
+        "owner/repo".to_string(),
+        accounts(3),
+        Some(accounts(5)),
+        U128(60)
+    ));
+    assert_eq!(
+        contract
+            .get_ft_bounty("owner/repo".to_string(), accounts(5))
+            .0,
+        100
+    );
+}
+
+// Test that non-owner cannot accept tokens
+#[test]
+#[should_panic(expected = "Caller is not the owner")]
+fn test_add_accepted_token_not_owner() {
+    let mut contract = setup_contract();
+    let context = get_context(accounts(2), false);
+    testing_env!(context.build());
+    contract.add_accepted_token(accounts(5));
+}
+
+// Test that a failed NEAR withdrawal (e.g. to a deleted account) restores the pool and emits an event
+#[test]
+fn test_withdraw_bounty_transfer_failed() {
+    let mut contract = setup_contract_with_repo();
+
+    let context =
+        get_context_with_deposit(accounts(3), false, Some(with_storage(DEPOSIT_ONE_NEAR)));
+    testing_env!(context.build());
+    contract.fund_bounty(
+        "owner/repo".to_string(),
+        None,
+        None,
+        U128(DEPOSIT_ONE_NEAR.as_yoctonear()),
+    );
+
+    let amount = DEPOSIT_ONE_NEAR.as_yoctonear();
+    let _ = contract.withdraw_bounty("owner/repo".to_string(), U128(amount), None);
+    assert_eq!(contract.get_bounty("owner/repo".to_string()).0, 0);
+
+    set_promise_result(PromiseResult::Failed);
+    assert!(!contract.on_bounty_withdrawn(
+        "owner/repo".to_string(),
+        accounts(3),
+        None,
+        U128(amount)
+    ));
+    assert_eq!(contract.get_bounty("owner/repo".to_string()).0, amount);
+
+    let logs = near_sdk::test_utils::get_logs();
+    assert!(
+        logs.iter()
+            .any(|log| log.contains("\"event\":\"payout_failed\""))
+    );
+}
+
+// Test that a successful withdrawal leaves the pool debited
+#[test]
+fn test_withdraw_bounty_transfer_succeeded() {
+    let mut contract = setup_contract_with_repo();
+
+    let context =
+        get_context_with_deposit(accounts(3), false, Some(with_storage(DEPOSIT_ONE_NEAR)));
+    testing_env!(context.build());
+    contract.fund_bounty(
+        "owner/repo".to_string(),
+        None,
+        None,
+        U128(DEPOSIT_ONE_NEAR.as_yoctonear()),
+    );
+
+    let amount = DEPOSIT_ONE_NEAR.as_yoctonear();
+    let _ = contract.withdraw_bounty("owner/repo".to_string(), U128(amount), None);
+    let logs = near_sdk::test_utils::get_logs();
+    assert!(
+        logs.iter()
+            .any(|log| log.contains("\"event\":\"bounty_withdrawn\""))
+    );
+
+    set_promise_result(PromiseResult::Successful(vec![]));
+    assert!(contract.on_bounty_withdrawn(
+        "owner/repo".to_string(),
+        accounts(3),
+        None,
+        U128(amount)
+    ));
+    assert_eq!(contract.get_bounty("owner/repo".to_string()).0, 0);
+}
+
+// Test that a valid agent registers a repo for the maintainer it vouches for
+#[test]
+fn test_register_repo() {
+    let contract = setup_contract_with_repo();
+    assert!(contract.is_repo_registered("owner/repo".to_string()));
+    assert_eq!(
+        contract.get_repo_maintainer("owner/repo".to_string()),
+        accounts(3)
+    );
+
+    let logs = near_sdk::test_utils::get_logs();
+    assert!(
+        logs.iter()
+            .any(|log| log.contains("\"event\":\"repo_registered\""))
+    );
+}
+
+// Test that an account which is not an agent cannot register (squat) a repo
+#[test]
+#[should_panic(expected = "Agent not registered")]
+fn test_register_repo_not_agent() {
+    let mut contract = setup_contract();
+    let context = get_context(accounts(4), false);
+    testing_env!(context.build());
+    let _ = contract.register_repo("owner/repo".to_string(), accounts(4));
+}
+
+// Test that a repo cannot be registered twice
+#[test]
+#[should_panic(expected = "Repo already registered")]
+fn test_register_repo_twice() {
+    let mut contract = setup_contract_with_repo();
+    let context = get_context(accounts(2), false);
+    testing_env!(context.build());
+    let _ = contract.register_repo("owner/repo".to_string(), accounts(4));
+}
+
+// Test that the owner can reassign a repo to resolve a dispute
+#[test]
+fn test_set_repo_maintainer() {
+    let mut contract = setup_contract_with_repo();
+    let context = get_context(accounts(0), false);
+    testing_env!(context.build());
+    contract.set_repo_maintainer("owner/repo".to_string(), accounts(4));
+    assert_eq!(
+        contract.get_repo_maintainer("owner/repo".to_string()),
+        accounts(4)
+    );
+}
+
+// Test that non-owner cannot reassign a repo
+#[test]
+#[should_panic(expected = "Caller is not the owner")]
+fn test_set_repo_maintainer_not_owner() {
+    let mut contract = setup_contract_with_repo();
+    let context = get_context(accounts(3), false);
+    testing_env!(context.build());
+    contract.set_repo_maintainer("owner/repo".to_string(), accounts(4));
+}
+
+// Test that funders can fund but not withdraw, and admins can withdraw to themselves
+#[test]
+fn test_repo_member_roles() {
+    let mut contract = setup_contract_with_repo();
+    let funder = accounts(4);
+    let admin = accounts(5);
+
+    let context = get_context_with_deposit(accounts(3), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    contract.set_repo_member(
+        "owner/repo".to_string(),
+        funder.clone(),
+        MaintainerRole::Funder,
+    );
+    contract.set_repo_member(
+        "owner/repo".to_string(),
+        admin.clone(),
+        MaintainerRole::Admin,
+    );
+
+    let members = contract.get_repo_members("owner/repo".to_string());
+    assert_eq!(members.len(), 3);
+    assert_eq!(members[0].account_id, accounts(3));
+    assert_eq!(members[0].role, MaintainerRole::Admin);
+
+    let context = get_context_with_deposit(funder, false, Some(with_storage(DEPOSIT_ONE_NEAR)));
+    testing_env!(context.build());
+    contract.fund_bounty(
+        "owner/repo".to_string(),
+        None,
+        None,
+        U128(DEPOSIT_ONE_NEAR.as_yoctonear()),
+    );
+    assert_eq!(
+        contract.get_bounty("owner/repo".to_string()).0,
+        DEPOSIT_ONE_NEAR.as_yoctonear()
+    );
+
+    let context = get_context(admin, false);
+    testing_env!(context.build());
+    let _ = contract.withdraw_bounty(
+        "owner/repo".to_string(),
+        U128(DEPOSIT_ONE_NEAR.as_yoctonear()),
+        None,
+    );
+    assert_eq!(contract.get_bounty("owner/repo".to_string()).0, 0);
+}
+
+// Test that funders cannot withdraw
+#[test]
+#[should_panic(expected = "Only repo admins can withdraw the bounty")]
+fn test_withdraw_bounty_funder() {
+    let mut contract = setup_contract_with_repo();
+    let funder = accounts(4);
+
+    let context = get_context_with_deposit(accounts(3), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    contract.set_repo_member(
+        "owner/repo".to_string(),
+        funder.clone(),
+        MaintainerRole::Funder,
+    );
+
+    let context = get_context_with_deposit(funder, false, Some(with_storage(DEPOSIT_ONE_NEAR)));
+    testing_env!(context.build());
+    contract.fund_bounty(
+        "owner/repo".to_string(),
+        None,
+        None,
+        U128(DEPOSIT_ONE_NEAR.as_yoctonear()),
+    );
+    let _ = contract.withdraw_bounty("owner/repo".to_string(), U128(1), None);
+}
+
+// Test that removed members lose their role
+#[test]
+#[should_panic(expected = "Only repo admins and funders can fund the bounty")]
+fn test_remove_repo_member() {
+    let mut contract = setup_contract_with_repo();
+    disable_third_party_funding(&mut contract);
+    let funder = accounts(4);
+
+    let context = get_context_with_deposit(accounts(3), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    contract.set_repo_member(
+        "owner/repo".to_string(),
+        funder.clone(),
+        MaintainerRole::Funder,
+    );
+    contract.remove_repo_member("owner/repo".to_string(), funder.clone());
+    assert_eq!(contract.get_repo_members("owner/repo".to_string()).len(), 1);
+
+    let context = get_context_with_deposit(funder, false, Some(with_storage(DEPOSIT_ONE_NEAR)));
+    testing_env!(context.build());
+    contract.fund_bounty(
+        "owner/repo".to_string(),
+        None,
+        None,
+        U128(DEPOSIT_ONE_NEAR.as_yoctonear()),
+    );
+}
+
+// Test the two-step maintainer handover
+#[test]
+fn test_maintainer_handover() {
+    let mut contract = setup_contract_with_repo();
+    let new_maintainer = accounts(4);
+
+    let context = get_context_with_deposit(accounts(3), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    contract.propose_maintainer("owner/repo".to_string(), new_maintainer.clone());
+    assert_eq!(
+        contract.get_pending_maintainer("owner/repo".to_string()),
+        Some(new_maintainer.clone())
+    );
+    // Nothing changes until the handover is accepted
+    assert_eq!(
+        contract.get_repo_maintainer("owner/repo".to_string()),
+        accounts(3)
+    );
+
+    let context = get_context(new_maintainer.clone(), false);
+    testing_env!(context.build());
+    contract.accept_maintainer("owner/repo".to_string());
+    assert_eq!(
+        contract.get_repo_maintainer("owner/repo".to_string()),
+        new_maintainer
+    );
+    let logs = near_sdk::test_utils::get_logs();
+    assert!(logs[0].contains("\"event\":\"repo_maintainer_changed\""));
+    assert!(logs[0].contains(&format!("\"old_maintainer_id\":\"{}\"", accounts(3))));
+    assert!(
+        contract
+            .get_pending_maintainer("owner/repo".to_string())
+            .is_none()
+    );
+    let members = contract.get_repo_members("owner/repo".to_string());
+    assert_eq!(members.len(), 1);
+    assert_eq!(members[0].account_id, new_maintainer);
+}
+
+// Test that only the proposed account can accept a handover
+#[test]
+#[should_panic(expected = "Caller is not the pending maintainer")]
+fn test_accept_maintainer_not_pending() {
+    let mut contract = setup_contract_with_repo();
+
+    let context = get_context_with_deposit(accounts(3), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    contract.propose_maintainer("owner/repo".to_string(), accounts(4));
+
+    let context = get_context(accounts(5), false);
+    testing_env!(context.build());
+    contract.accept_maintainer("owner/repo".to_string());
+}
+
+// Test that a cancelled handover cannot be accepted
+#[test]
+#[should_panic(expected = "Caller is not the pending maintainer")]
+fn test_cancel_maintainer_proposal() {
+    let mut contract = setup_contract_with_repo();
+
+    let context = get_context_with_deposit(accounts(3), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    contract.propose_maintainer("owner/repo".to_string(), accounts(4));
+    contract.cancel_maintainer_proposal("owner/repo".to_string());
+
+    let context = get_context(accounts(4), false);
+    testing_env!(context.build());
+    contract.accept_maintainer("owner/repo".to_string());
+}
+
+// Test that only the primary maintainer can propose a handover
+#[test]
+#[should_panic(expected = "Caller is not the primary maintainer")]
+fn test_propose_maintainer_not_primary() {
+    let mut contract = setup_contract_with_repo();
+
+    let context = get_context_with_deposit(accounts(3), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    contract.set_repo_member("owner/repo".to_string(), accounts(5), MaintainerRole::Admin);
+
+    let context = get_context_with_deposit(accounts(5), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    contract.propose_maintainer("owner/repo".to_string(), accounts(5));
+}
+
+// Helper function to write a contract state with the pre-escrow layout, with an agent and a funded repo
+fn write_v1_state() {
+    let context = get_context(accounts(0), false);
+    testing_env!(context.build());
+
+    let mut old = crate::migrate::ContractV1 {
+        requires_tee: false,
+        attestation_expiration_time_ms: 100000,
+        owner_id: accounts(0),
+        mpc_contract_id: accounts(1),
+        approved_measurements: IterableSet::new(StorageKey::ApprovedMeasurements),
+        approved_ppids: IterableSet::new(StorageKey::ApprovedPpids),
+        agents: IterableMap::new(StorageKey::Agents),
+        whitelisted_agents_for_local: IterableSet::new(StorageKey::WhitelistedAgentsForLocal),
+        bounties: LookupMap::new(StorageKey::Bounties),
+        repo_maintainers: LookupMap::new(StorageKey::RepoMaintainers),
+    };
+    old.approved_measurements
+        .insert(create_mock_full_measurements_hex());
+    old.approved_ppids.insert(Ppid::default());
+    old.whitelisted_agents_for_local.insert(accounts(2));
+    old.agents.insert(
+        accounts(2),
+        Agent {
+            measurements: create_mock_full_measurements_hex(),
+            ppid: Ppid::default(),
+            valid_until_ms: 100000,
+        },
+    );
+    old.repo_maintainers
+        .insert("owner/repo".to_string(), accounts(3));
+    old.bounties.insert("owner/repo".to_string(), 42);
+    env::state_write(&old);
+}
+
+// Test that migrate upgrades the pre-escrow layout and keeps agents and bounties
+#[test]
+fn test_migrate_from_v1() {
+    write_v1_state();
+
+    let contract = Contract::migrate();
+    assert_eq!(contract.get_contract_info().state_version, STATE_VERSION);
+    assert_eq!(contract.get_contract_info().owner_id, accounts(0));
+    assert!(matches!(
+        contract.get_agent(accounts(2)).unwrap().validity,
+        AgentValidity::Valid
+    ));
+    assert_eq!(
+        contract.get_repo_maintainer("owner/repo".to_string()),
+        accounts(3)
+    );
+    assert_eq!(contract.get_bounty("owner/repo".to_string()).0, 42);
+    assert!(
+        contract
+            .get_issue_bounty("owner/repo".to_string(), 1)
+            .is_none()
+    );
+}
+
+// Test that migrate upgrades the original template layout
+#[test]
+fn test_migrate_from_v0() {
+    let context = get_context(accounts(0), false);
+    testing_env!(context.build());
+
+    let mut old = crate::migrate::ContractV0 {
+        requires_tee: false,
+        attestation_expiration_time_ms: 100000,
+        owner_id: accounts(0),
+        mpc_contract_id: accounts(1),
+        approved_measurements: IterableSet::new(StorageKey::ApprovedMeasurements),
+        approved_ppids: IterableSet::new(StorageKey::ApprovedPpids),
+        agents: IterableMap::new(StorageKey::Agents),
+        whitelisted_agents_for_local: IterableSet::new(StorageKey::WhitelistedAgentsForLocal),
+    };
+    old.approved_ppids.insert(Ppid::default());
+    env::state_write(&old);
+    // Collections are flushed to storage when dropped
+    drop(old);
+
+    let contract = Contract::migrate();
+    assert_eq!(contract.get_contract_info().state_version, STATE_VERSION);
+    assert_eq!(contract.get_approved_ppids(&None, &None).len(), 1);
+    assert!(!contract.is_repo_registered("owner/repo".to_string()));
+}
+
+// Test that migrate keeps the current layout untouched so code-only upgrades work
+#[test]
+fn test_migrate_current_state() {
+    let contract = setup_contract_with_repo();
+    env::state_write(&contract);
+    drop(contract);
+
+    let migrated = Contract::migrate();
+    assert_eq!(migrated.get_contract_info().state_version, STATE_VERSION);
+    assert_eq!(migrated.get_agents(&None, &None).len(), 1);
+    assert_eq!(
+        migrated.get_repo_maintainer("owner/repo".to_string()),
+        accounts(3)
+    );
+}
+
+// Test that migrate refuses a current layout written with another version
+#[test]
+#[should_panic(expected = "Unknown contract state version")]
+fn test_migrate_other_state_version() {
+    let mut contract = setup_contract_with_repo();
+    contract.state_version = STATE_VERSION + 1;
+    env::state_write(&contract);
+    drop(contract);
+
+    Contract::migrate();
+}
+
+// Test that migrate refuses state it does not recognise
+#[test]
+#[should_panic(expected = "Unknown contract state layout")]
+fn test_migrate_unknown_state() {
+    let context = get_context(accounts(0), false);
+    testing_env!(context.build());
+    env::storage_write(b"STATE", &[7u8; 3]);
+    Contract::migrate();
+}
+
+// Helper function to build update_contract input: 8 bytes of gas followed by the code
+fn update_contract_input(code: &[u8]) -> Vec<u8> {
+    let mut input = 10u64.to_le_bytes().to_vec();
+    input.extend_from_slice(code);
+    input
+}
+
+// Test that staging an upgrade exposes its hash and when it becomes executable
+#[test]
+fn test_propose_upgrade() {
+    let mut contract = setup_contract();
+    let context = get_context_with_deposit_and_timestamp(accounts(0), false, None, Some(5000));
+    testing_env!(context.build());
+
+    let code_hash = CodeHash::from(env::sha256_array(b"new code"));
+    contract.propose_upgrade(code_hash.clone());
+
+    let pending_upgrade = contract.get_pending_upgrade().unwrap();
+    assert_eq!(pending_upgrade.code_hash, code_hash);
+    assert_eq!(pending_upgrade.proposed_at_ms.0, 5000);
+    assert_eq!(pending_upgrade.executable_at_ms.0, 6000);
+
+    let logs = near_sdk::test_utils::get_logs();
+    assert!(
+        logs.iter()
+            .any(|log| log.contains("\"event\":\"upgrade_proposed\""))
+    );
+}
+
+// Test that staged code can be deployed once the delay has passed
+#[test]
+fn test_update_contract_after_delay() {
+    let mut contract = setup_contract();
+    contract.propose_upgrade(CodeHash::from(env::sha256_array(b"new code")));
+
+    let mut context =
+        get_context_with_deposit_and_timestamp(accounts(0), false, None, Some(1000)).build();
+    context.input = update_contract_input(b"new code").into();
+    testing_env!(context);
+    let _ = contract.update_contract();
+    assert!(contract.get_pending_upgrade().is_none());
+}
+
+// Test that staged code cannot be deployed before the delay has passed
+#[test]
+#[should_panic(expected = "Upgrade delay has not passed")]
+fn test_update_contract_before_delay() {
+    let mut contract = setup_contract();
+    contract.propose_upgrade(CodeHash::from(env::sha256_array(b"new code")));
+
+    let mut context =
+        get_context_with_deposit_and_timestamp(accounts(0), false, None, Some(999)).build();
+    context.input = update_contract_input(b"new code").into();
+    testing_env!(context);
+    let _ = contract.update_contract();
+}
+
+// Test that only the staged code can be deployed
+#[test]
+#[should_panic(expected = "Code hash does not match the pending upgrade")]
+fn test_update_contract_wrong_code() {
+    let mut contract = setup_contract();
+    contract.propose_upgrade(CodeHash::from(env::sha256_array(b"new code")));
+
+    let mut context =
+        get_context_with_deposit_and_timestamp(accounts(0), false, None, Some(1000)).build();
+    context.input = update_contract_input(b"other code").into();
+    testing_env!(context);
+    let _ = contract.update_contract();
+}
+
+// Test that a cancelled upgrade cannot be deployed
+#[test]
+#[should_panic(expected = "No pending upgrade")]
+fn test_cancel_upgrade() {
+    let mut contract = setup_contract();
+    contract.propose_upgrade(CodeHash::from(env::sha256_array(b"new code")));
+    contract.cancel_upgrade();
+    assert!(contract.get_pending_upgrade().is_none());
+
+    let mut context =
+        get_context_with_deposit_and_timestamp(accounts(0), false, None, Some(1000)).build();
+    context.input = update_contract_input(b"new code").into();
+    testing_env!(context);
+    let _ = contract.update_contract();
+}
+
+// Test that an upgrader cannot lower the upgrade delay
+#[test]
+#[should_panic(expected = "Only the owner can lower the upgrade delay")]
+fn test_update_upgrade_delay_lower() {
+    let mut contract = setup_contract();
+    grant_role(&mut contract, Role::Upgrader, accounts(3));
+
+    let context = get_context(accounts(3), false);
+    testing_env!(context.build());
+    contract.update_upgrade_delay(U64::from(2000u64));
+    assert_eq!(contract.get_contract_info().upgrade_delay_ms.0, 2000);
+    contract.update_upgrade_delay(U64::from(1999u64));
+}
+
+// Test that the owner can lower the delay without shortening a staged upgrade
+#[test]
+fn test_update_upgrade_delay_lower_by_owner() {
+    let mut contract = setup_contract();
+    contract.update_upgrade_delay(U64::from(5000u64));
+    contract.propose_upgrade(CodeHash::from(env::sha256_array(b"new code")));
+
+    contract.update_upgrade_delay(U64::from(0u64));
+    assert_eq!(contract.get_contract_info().upgrade_delay_ms.0, 0);
+    assert_eq!(
+        contract.get_pending_upgrade().unwrap().executable_at_ms.0,
+        5000
+    );
+}
+
+// Test that the upgrade delay is capped
+#[test]
+#[should_panic(expected = "Upgrade delay cannot be more than 30 days")]
+fn test_update_upgrade_delay_above_max() {
+    let mut contract = setup_contract();
+    contract.update_upgrade_delay(U64::from(MAX_UPGRADE_DELAY_MS + 1));
+}
+
+// Test that non-owner cannot stage an upgrade
+#[test]
+#[should_panic(expected = "Caller is not the owner")]
+fn test_propose_upgrade_not_owner() {
+    let mut contract = setup_contract();
+    let context = get_context(accounts(2), false);
+    testing_env!(context.build());
+    contract.propose_upgrade(CodeHash::from([0u8; 32]));
+}
+
+// Helper function to grant a role as the owner, who pays for its storage
+fn grant_role(contract: &mut Contract, role: Role, account_id: AccountId) {
+    let context = get_context_with_deposit(accounts(0), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    contract.grant_role(role, account_id);
+}
+
+// Test that a granted role allows its admin methods and nothing else
+#[test]
+fn test_grant_role() {
+    let mut contract = setup_contract();
+    let admin = accounts(3);
+
+    grant_role(&mut contract, Role::MeasurementAdmin, admin.clone());
+    assert!(contract.has_role(Role::MeasurementAdmin, admin.clone()));
+    assert_eq!(
+        contract.get_role_members(Role::MeasurementAdmin),
+        vec![admin.clone()]
+    );
+    assert!(contract.get_role_members(Role::Upgrader).is_empty());
+
+    let context = get_context(admin, false);
+    testing_env!(context.build());
+    contract.approve_measurements(
+        non_default_measurements(),
+        "agent v1".to_string(),
+        "sha256:abc".to_string(),
+    );
+    contract.approve_ppids(vec![non_default_ppid()]);
+    assert_eq!(contract.get_approved_measurements(&None, &None).len(), 2);
+}
+
+// Test that a role does not give access to the methods of other roles
+#[test]
+#[should_panic(expected = "Caller is not the owner and does not have the AgentOperator role")]
+fn test_role_is_least_privilege() {
+    let mut contract = setup_contract();
+    let admin = accounts(3);
+    contract.whitelist_agent_for_local(accounts(2));
+    grant_role(&mut contract, Role::MeasurementAdmin, admin.clone());
+
+    let context = get_context(admin, false);
+    testing_env!(context.build());
+    contract.remove_agent_from_whitelist_for_local(accounts(2));
+}
+
+// Test that a revoked role no longer gives access
+#[test]
+#[should_panic(expected = "Caller is not the owner and does not have the Upgrader role")]
+fn test_revoke_role() {
+    let mut contract = setup_contract();
+    let upgrader = accounts(3);
+    grant_role(&mut contract, Role::Upgrader, upgrader.clone());
+    contract.revoke_role(Role::Upgrader, upgrader.clone());
+    assert!(!contract.has_role(Role::Upgrader, upgrader.clone()));
+
+    let logs = near_sdk::test_utils::get_logs();
+    assert!(
+        logs.iter()
+            .any(|log| log.contains("\"event\":\"role_revoked\""))
+    );
+
+    let context = get_context(upgrader, false);
+    testing_env!(context.build());
+    contract.propose_upgrade(CodeHash::from([0u8; 32]));
+}
+
+// Test that only the owner can grant roles, even to role members
+#[test]
+#[should_panic(expected = "Caller is not the owner")]
+fn test_grant_role_not_owner() {
+    let mut contract = setup_contract();
+    grant_role(&mut contract, Role::AgentOperator, accounts(3));
+
+    let context = get_context_with_deposit(accounts(3), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    contract.grant_role(Role::AgentOperator, accounts(4));
+}
+
+// Test that a role cannot be granted twice
+#[test]
+#[should_panic(expected = "Account already has the role")]
+fn test_grant_role_twice() {
+    let mut contract = setup_contract();
+    grant_role(&mut contract, Role::BountyArbiter, accounts(3));
+    grant_role(&mut contract, Role::BountyArbiter, accounts(3));
+}
+
+// Helper function to build pause flags for a single operation
+fn pause_payouts() -> PauseFlags {
+    PauseFlags {
+        payouts: true,
+        ..Default::default()
+    }
+}
+
+// Test that a PauseGuardian can pause payouts and it shows in the contract info
+#[test]
+#[should_panic(expected = "Payouts are paused")]
+fn test_pause_payouts() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = fund_issue_bounty(&mut contract, 7, DEPOSIT_ONE_NEAR);
+
+    grant_role(&mut contract, Role::PauseGuardian, accounts(5));
+
+    let context = get_context(accounts(5), false);
+    testing_env!(context.build());
+    contract.pause(pause_payouts());
+    assert_eq!(contract.get_contract_info().paused, pause_payouts());
+
+    let context = get_context_with_deposit(accounts(2), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    let _ = contract.release_bounty(
+        bounty_id,
+        Some(accounts(4)),
+        None,
+        U128(1),
+        7,
+        MERGE_COMMIT_SHA.to_string(),
+    );
+}
+
+// Test that maintainers can still withdraw while everything is paused
+#[test]
+fn test_withdraw_bounty_while_paused() {
+    let mut contract = setup_contract_with_repo();
+
+    let context =
+        get_context_with_deposit(accounts(3), false, Some(with_storage(DEPOSIT_ONE_NEAR)));
+    testing_env!(context.build());
+    contract.fund_bounty(
+        "owner/repo".to_string(),
+        None,
+        None,
+        U128(DEPOSIT_ONE_NEAR.as_yoctonear()),
+    );
+
+    let context = get_context(accounts(0), false);
+    testing_env!(context.build());
+    contract.pause(PauseFlags {
+        payouts: true,
+        signatures: true,
+        agent_registration: true,
+        funding: true,
+    });
+
+    let context = get_context(accounts(3), false);
+    testing_env!(context.build());
+    let _ = contract.withdraw_bounty(
+        "owner/repo".to_string(),
+        U128(DEPOSIT_ONE_NEAR.as_yoctonear()),
+        None,
+    );
+    assert_eq!(contract.get_bounty("owner/repo".to_string()).0, 0);
+}
+
+// Test that paused signing blocks request_signature
+#[test]
+#[should_panic(expected = "Signing is paused")]
+fn test_pause_signatures() {
+    let mut contract = setup_contract_with_repo();
+
+    let context = get_context(accounts(0), false);
+    testing_env!(context.build());
+    contract.pause(PauseFlags {
+        signatures: true,
+        ..Default::default()
+    });
+
+    let context = get_context(accounts(2), false);
+    testing_env!(context.build());
+    let _ = contract.request_signature(
+        "path".to_string(),
+        "payload".to_string(),
+        "Ecdsa".to_string(),
+    );
+}
+
+// Test that paused funding blocks deposits
+#[test]
+#[should_panic(expected = "Funding is paused")]
+fn test_pause_funding() {
+    let mut contract = setup_contract_with_repo();
+
+    let context = get_context(accounts(0), false);
+    testing_env!(context.build());
+    contract.pause(PauseFlags {
+        funding: true,
+        ..Default::default()
+    });
+
+    fund_issue_bounty(&mut contract, 7, DEPOSIT_ONE_NEAR);
+}
+
+// Test that paused registration blocks register_agent
+#[test]
+#[should_panic(expected = "Agent registration is paused")]
+fn test_pause_agent_registration() {
+    let mut contract = setup_contract();
+    contract.whitelist_agent_for_local(accounts(2));
+    contract.pause(PauseFlags {
+        agent_registration: true,
+        ..Default::default()
+    });
+
+    let context = get_context_with_deposit(accounts(2), false, Some(DEPOSIT_005_NEAR));
+    testing_env!(context.build());
+    contract.register_agent(create_mock_dstack_attestation());
+}
+
+// Test that the owner can unpause a single operation
+#[test]
+fn test_unpause() {
+    let mut contract = setup_contract();
+    contract.pause(PauseFlags {
+        payouts: true,
+        funding: true,
+        ..Default::default()
+    });
+    contract.unpause(pause_payouts());
+    assert_eq!(
+        contract.get_contract_info().paused,
+        PauseFlags {
+            funding: true,
+            ..Default::default()
+        }
+    );
+}
+
+// Test that a PauseGuardian cannot unpause
+#[test]
+#[should_panic(expected = "Caller is not the owner")]
+fn test_unpause_guardian() {
+    let mut contract = setup_contract();
+    grant_role(&mut contract, Role::PauseGuardian, accounts(5));
+    contract.pause(pause_payouts());
+
+    let context = get_context(accounts(5), false);
+    testing_env!(context.build());
+    contract.unpause(pause_payouts());
+}
+
+// Helper function to register an agent with an implicit account whose key signs claim messages
+fn register_signing_agent(contract: &mut Contract) -> (AccountId, ed25519_dalek::SigningKey) {
+    let signing_key = ed25519_dalek::SigningKey::from_bytes(&[7u8; 32]);
+    let agent: AccountId = hex::encode(signing_key.verifying_key().as_bytes())
+        .parse()
+        .unwrap();
+
+    let context = get_context(accounts(0), false);
+    testing_env!(context.build());
+    contract.whitelist_agent_for_local(agent.clone());
+
+    let context = get_context_with_deposit(agent.clone(), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    contract.register_agent(create_mock_dstack_attestation());
+    (agent, signing_key)
+}
+
+// Helper function to sign a github_message of a GitHub user for an account
+fn sign_github_message(
+    signing_key: &ed25519_dalek::SigningKey,
+    action: &str,
+    github_user_id: GithubUserId,
+    account_id: &AccountId,
+    expires_at_ms: u64,
+) -> Signature {
+    use ed25519_dalek::Signer;
+    let message =
+        crate::internal::github::github_message(action, github_user_id, account_id, expires_at_ms);
+    Signature::from(signing_key.sign(message.as_bytes()).to_bytes())
+}
+
+// Helper function to hold a one NEAR bounty for PR 7 for GitHub user 42
+fn hold_for_github_user(contract: &mut Contract) -> BountyId {
+    let bounty_id = fund_issue_bounty(contract, 7, DEPOSIT_ONE_NEAR);
+    let context = get_context_with_deposit(accounts(2), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    let _ = contract.release_bounty_to_github_user(
+        bounty_id,
+        42,
+        U128(DEPOSIT_ONE_NEAR.as_yoctonear()),
+        7,
+        MERGE_COMMIT_SHA.to_string(),
+    );
+    bounty_id
+}
+
+// Test that a payout for an unlinked GitHub user is held as a claimable balance
+#[test]
+fn test_release_bounty_to_github_user() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = hold_for_github_user(&mut contract);
+
+    let bounty = contract.get_bounty_by_id(bounty_id).unwrap();
+    assert_eq!(bounty.status, BountyStatus::Paid);
+    assert_eq!(bounty.amount.0, DEPOSIT_ONE_NEAR.as_yoctonear());
+
+    let balances = contract.get_claimable_balances(42);
+    assert_eq!(balances.len(), 1);
+    assert_eq!(balances[0].amount.0, DEPOSIT_ONE_NEAR.as_yoctonear());
+    assert_eq!(
+        balances[0].expires_at_ms.0,
+        contract.get_contract_info().claim_expiry_ms.0
+    );
+
+    let payout = contract.get_payout("owner/repo".to_string(), 7).unwrap();
+    assert!(payout.recipient.is_none());
+    assert_eq!(payout.github_user_id, Some(42));
+}
+
+// Test that a contributor claims held balances with an agent signed message
+#[test]
+fn test_claim() {
+    let mut contract = setup_contract_with_repo();
+    let (agent, signing_key) = register_signing_agent(&mut contract);
+    hold_for_github_user(&mut contract);
+
+    let context = get_context(accounts(4), false);
+    testing_env!(context.build());
+    let signature = sign_github_message(&signing_key, "claim", 42, &accounts(4), 1000);
+    let _ = contract.claim(42, agent, U64::from(1000), signature);
+
+    assert!(contract.get_claimable_balances(42).is_empty());
+    let logs = near_sdk::test_utils::get_logs();
+    assert!(logs[0].contains("\"event\":\"claimable_balance_claimed\""));
+    assert!(logs[0].contains(&format!(
+        "\"amount\":\"{}\"",
+        DEPOSIT_ONE_NEAR.as_yoctonear()
+    )));
+}
+
+// Test that a signature for another account cannot be used to claim
+#[test]
+#[should_panic(expected = "Invalid agent signature")]
+fn test_claim_signed_for_other_account() {
+    let mut contract = setup_contract_with_repo();
+    let (agent, signing_key) = register_signing_agent(&mut contract);
+    hold_for_github_user(&mut contract);
+
+    let context = get_context(accounts(5), false);
+    testing_env!(context.build());
+    let signature = sign_github_message(&signing_key, "claim", 42, &accounts(4), 1000);
+    let _ = contract.claim(42, agent, U64::from(1000), signature);
+}
+
+// Test that a claim message cannot be used after it expired
+#[test]
+#[should_panic(expected = "Claim message has expired")]
+fn test_claim_message_expired() {
+    let mut contract = setup_contract_with_repo();
+    let (agent, signing_key) = register_signing_agent(&mut contract);
+    hold_for_github_user(&mut contract);
+
+    let context = get_context_with_deposit_and_timestamp(accounts(4), false, None, Some(1000));
+    testing_env!(context.build());
+    let signature = sign_github_message(&signing_key, "claim", 42, &accounts(4), 1000);
+    let _ = contract.claim(42, agent, U64::from(1000), signature);
+}
+
+// Test that a failed claim transfer holds the balance for the GitHub user again
+#[test]
+fn test_claim_transfer_failed() {
+    let mut contract = setup_contract_with_repo();
+    let (agent, signing_key) = register_signing_agent(&mut contract);
+    hold_for_github_user(&mut contract);
+
+    let context = get_context(accounts(4), false);
+    testing_env!(context.build());
+    let signature = sign_github_message(&signing_key, "claim", 42, &accounts(4), 1000);
+    let _ = contract.claim(42, agent, U64::from(1000), signature);
+    let balances = contract.get_claimable_balances(42);
+    assert!(balances.is_empty());
+
+    let held = vec![ClaimableBalanceView {
+        bounty_id: 0,
+        repo_id: "owner/repo".to_string(),
+        token_id: None,
+        amount: U128(DEPOSIT_ONE_NEAR.as_yoctonear()),
+        expires_at_ms: U64::from(5000),
+    }];
+    set_promise_result(PromiseResult::Failed);
+    assert!(!contract.on_claimed(42, accounts(4), held));
+    let balances = contract.get_claimable_balances(42);
+    assert_eq!(balances.len(), 1);
+    assert_eq!(balances[0].expires_at_ms.0, 5000);
+}
+
+// Test that unclaimed balances go back to the repo pool once they expire
+#[test]
+fn test_return_expired_claims() {
+    let mut contract = setup_contract_with_repo();
+    let context = get_context(accounts(0), false);
+    testing_env!(context.build());
+    contract.update_claim_expiry(U64::from(1000));
+    hold_for_github_user(&mut contract);
+
+    // Nothing expired yet
+    contract.return_expired_claims(42);
+    assert_eq!(contract.get_claimable_balances(42).len(), 1);
+    assert_eq!(contract.get_bounty("owner/repo".to_string()).0, 0);
+
+    let context = get_context_with_deposit_and_timestamp(accounts(5), false, None, Some(1000));
+    testing_env!(context.build());
+    contract.return_expired_claims(42);
+    assert!(contract.get_claimable_balances(42).is_empty());
+    assert_eq!(
+        contract.get_bounty("owner/repo".to_string()).0,
+        DEPOSIT_ONE_NEAR.as_yoctonear()
+    );
+    let logs = near_sdk::test_utils::get_logs();
+    assert!(logs[0].contains("\"event\":\"claimable_balance_expired\""));
+}
+
+// Test that only a BountyArbiter or the owner can change the claim expiry
+#[test]
+#[should_panic(expected = "Caller is not the owner and does not have the BountyArbiter role")]
+fn test_update_claim_expiry_not_arbiter() {
+    let mut contract = setup_contract();
+    let context = get_context(accounts(4), false);
+    testing_env!(context.build());
+    contract.update_claim_expiry(U64::from(1000));
+}
+
+// Helper function to link GitHub user 42 to an account as the agent
+fn link_github_user(contract: &mut Contract, account_id: AccountId) {
+    let context = get_context_with_deposit(accounts(2), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    let _ = contract.link_github_account(42, account_id);
+}
+
+// Test that an agent can link a GitHub user to an account
+#[test]
+fn test_link_github_account() {
+    let mut contract = setup_contract_with_repo();
+    link_github_user(&mut contract, accounts(4));
+
+    assert_eq!(contract.get_github_account(42), Some(accounts(4)));
+    assert_eq!(contract.get_github_user_id(accounts(4)), Some(42));
+    let history = contract.get_github_link_history(42);
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].account_id, Some(accounts(4)));
+    assert_eq!(history[0].changed_by, accounts(2));
+    let logs = near_sdk::test_utils::get_logs();
+    assert!(logs[0].contains("\"event\":\"github_account_linked\""));
+}
+
+// Test that an agent link leaves the balances held for the GitHub user to the account to claim
+#[test]
+fn test_link_github_account_keeps_held_balances() {
+    let mut contract = setup_contract_with_repo();
+    hold_for_github_user(&mut contract);
+    link_github_user(&mut contract, accounts(4));
+
+    assert_eq!(contract.get_claimable_balances(42).len(), 1);
+}
+
+// Test that an agent cannot move an account linked to another GitHub user
+#[test]
+#[should_panic(
+    expected = "Account is linked to another GitHub user, only the account can replace its link"
+)]
+fn test_link_github_account_linked_to_other_user() {
+    let mut contract = setup_contract_with_repo();
+    link_github_user(&mut contract, accounts(4));
+
+    let context = get_context_with_deposit(accounts(2), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    let _ = contract.link_github_account(43, accounts(4));
+}
+
+// Test that relinking a GitHub user replaces the previous account and keeps the history
+#[test]
+fn test_relink_github_account() {
+    let mut contract = setup_contract_with_repo();
+    link_github_user(&mut contract, accounts(4));
+    link_github_user(&mut contract, accounts(5));
+
+    assert_eq!(contract.get_github_account(42), Some(accounts(5)));
+    assert_eq!(contract.get_github_user_id(accounts(4)), None);
+    assert_eq!(contract.get_github_user_id(accounts(5)), Some(42));
+    assert_eq!(contract.get_github_link_history(42).len(), 2);
+    let logs = near_sdk::test_utils::get_logs();
+    assert!(logs[0].contains(&format!("\"old_account_id\":\"{}\"", accounts(4))));
+}
+
+// Test that only a valid agent can link GitHub users directly
+#[test]
+#[should_panic(expected = "Agent not registered")]
+fn test_link_github_account_not_agent() {
+    let mut contract = setup_contract_with_repo();
+    let context = get_context(accounts(4), false);
+    testing_env!(context.build());
+    let _ = contract.link_github_account(42, accounts(4));
+}
+
+// Test that an account can link itself with an agent signature
+#[test]
+fn test_link_own_github_account() {
+    let mut contract = setup_contract_with_repo();
+    let (agent, signing_key) = register_signing_agent(&mut contract);
+
+    let context = get_context_with_deposit(accounts(4), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    let signature = sign_github_message(&signing_key, "link", 42, &accounts(4), 1000);
+    let _ = contract.link_own_github_account(42, agent, U64::from(1000), signature);
+
+    assert_eq!(contract.get_github_account(42), Some(accounts(4)));
+    assert_eq!(
+        contract.get_github_link_history(42)[0].changed_by,
+        accounts(4)
+    );
+}
+
+// Test that an account linking itself gets the balances held for the GitHub user
+#[test]
+fn test_link_own_github_account_releases_held_balances() {
+    let mut contract = setup_contract_with_repo();
+    let (agent, signing_key) = register_signing_agent(&mut contract);
+    hold_for_github_user(&mut contract);
+
+    let context = get_context_with_deposit(accounts(4), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    let signature = sign_github_message(&signing_key, "link", 42, &accounts(4), 1000);
+    let _ = contract.link_own_github_account(42, agent, U64::from(1000), signature);
+
+    assert!(contract.get_claimable_balances(42).is_empty());
+    let logs = near_sdk::test_utils::get_logs();
+    assert!(logs[1].contains("\"event\":\"claimable_balance_claimed\""));
+    assert!(logs[1].contains(&format!("\"account_id\":\"{}\"", accounts(4))));
+}
+
+// Test that an account linking itself keeps the held balances while payouts are paused
+#[test]
+fn test_link_own_github_account_while_payouts_paused() {
+    let mut contract = setup_contract_with_repo();
+    let (agent, signing_key) = register_signing_agent(&mut contract);
+    hold_for_github_user(&mut contract);
+    let context = get_context(accounts(0), false);
+    testing_env!(context.build());
+    contract.pause(pause_payouts());
+
+    let context = get_context_with_deposit(accounts(4), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    let signature = sign_github_message(&signing_key, "link", 42, &accounts(4), 1000);
+    let _ = contract.link_own_github_account(42, agent, U64::from(1000), signature);
+
+    assert_eq!(contract.get_github_account(42), Some(accounts(4)));
+    assert_eq!(contract.get_claimable_balances(42).len(), 1);
+}
+
+// Test that an account linked to a GitHub user can replace its link itself
+#[test]
+fn test_link_own_github_account_replaces_link() {
+    let mut contract = setup_contract_with_repo();
+    let (agent, signing_key) = register_signing_agent(&mut contract);
+    link_github_user(&mut contract, accounts(4));
+
+    let context = get_context_with_deposit(accounts(4), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    let signature = sign_github_message(&signing_key, "link", 43, &accounts(4), 1000);
+    let _ = contract.link_own_github_account(43, agent, U64::from(1000), signature);
+
+    assert_eq!(contract.get_github_account(42), None);
+    assert_eq!(contract.get_github_account(43), Some(accounts(4)));
+    assert_eq!(contract.get_github_user_id(accounts(4)), Some(43));
+}
+
+// Test that a claim signature cannot be used to link an account
+#[test]
+#[should_panic(expected = "Invalid agent signature")]
+fn test_link_own_github_account_with_claim_signature() {
+    let mut contract = setup_contract_with_repo();
+    let (agent, signing_key) = register_signing_agent(&mut contract);
+
+    let context = get_context_with_deposit(accounts(4), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    let signature = sign_github_message(&signing_key, "claim", 42, &accounts(4), 1000);
+    let _ = contract.link_own_github_account(42, agent, U64::from(1000), signature);
+}
+
+// Test that an account can remove its own link
+#[test]
+fn test_unlink_github_account() {
+    let mut contract = setup_contract_with_repo();
+    link_github_user(&mut contract, accounts(4));
+
+    let context = get_context(accounts(4), false);
+    testing_env!(context.build());
+    contract.unlink_github_account();
+
+    assert_eq!(contract.get_github_account(42), None);
+    assert_eq!(contract.get_github_user_id(accounts(4)), None);
+    let history = contract.get_github_link_history(42);
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[1].account_id, None);
+    let logs = near_sdk::test_utils::get_logs();
+    assert!(logs[0].contains("\"event\":\"github_account_unlinked\""));
+}
+
+// Test that release_bounty resolves the recipient from the GitHub account registry
+#[test]
+fn test_release_bounty_to_linked_github_user() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = fund_issue_bounty(&mut contract, 7, DEPOSIT_ONE_NEAR);
+    link_github_user(&mut contract, accounts(4));
+
+    let _ = contract.release_bounty(
+        bounty_id,
+        None,
+        Some(42),
+        U128(DEPOSIT_ONE_NEAR.as_yoctonear()),
+        7,
+        MERGE_COMMIT_SHA.to_string(),
+    );
+
+    let payout = contract.get_payout("owner/repo".to_string(), 7).unwrap();
+    assert_eq!(payout.recipient, Some(accounts(4)));
+    assert_eq!(payout.github_user_id, Some(42));
+}
+
+// Test that a recipient given with a GitHub user id must match the linked account
+#[test]
+#[should_panic(expected = "Recipient does not match the linked account")]
+fn test_release_bounty_linked_recipient_mismatch() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = fund_issue_bounty(&mut contract, 7, DEPOSIT_ONE_NEAR);
+    link_github_user(&mut contract, accounts(4));
+
+    let _ = contract.release_bounty(
+        bounty_id,
+        Some(accounts(5)),
+        Some(42),
+        U128(1),
+        7,
+        MERGE_COMMIT_SHA.to_string(),
+    );
+}
+
+// Test that release_bounty panics for a GitHub user without a linked account
+#[test]
+#[should_panic(expected = "GitHub user has no linked account")]
+fn test_release_bounty_unlinked_github_user() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = fund_issue_bounty(&mut contract, 7, DEPOSIT_ONE_NEAR);
+
+    let context = get_context_with_deposit(accounts(2), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    let _ = contract.release_bounty(
+        bounty_id,
+        None,
+        Some(42),
+        U128(1),
+        7,
+        MERGE_COMMIT_SHA.to_string(),
+    );
+}
+
+// Helper function to fund issue 7 with one NEAR and request a payout above a half NEAR threshold
+fn release_above_threshold(contract: &mut Contract) -> BountyId {
+    let bounty_id = fund_issue_bounty(contract, 7, DEPOSIT_ONE_NEAR);
+    contract.set_payout_threshold(
+        "owner/repo".to_string(),
+        None,
+        Some(U128(DEPOSIT_ONE_NEAR.as_yoctonear() / 2)),
+    );
+
+    let context = get_context_with_deposit(accounts(2), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    let _ = contract.release_bounty(
+        bounty_id,
+        Some(accounts(4)),
+        None,
+        U128(DEPOSIT_ONE_NEAR.as_yoctonear()),
+        7,
+        MERGE_COMMIT_SHA.to_string(),
+    );
+    bounty_id
+}
+
+// Test that a payout above the repo threshold waits for approval instead of transferring
+#[test]
+fn test_release_bounty_above_threshold() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = release_above_threshold(&mut contract);
+
+    let bounty = contract.get_bounty_by_id(bounty_id).unwrap();
+    assert_eq!(bounty.status, BountyStatus::PendingApproval);
+    let pending = contract.get_pending_payouts("owner/repo".to_string());
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].pr_number, 7);
+    let logs = near_sdk::test_utils::get_logs();
+    assert!(logs[0].contains("\"event\":\"payout_pending_approval\""));
+    assert!(!logs.iter().any(|log| log.contains("bounty_released")));
+}
+
+// Test that a payout at the threshold is released right away
+#[test]
+fn test_release_bounty_at_threshold() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = fund_issue_bounty(&mut contract, 7, DEPOSIT_ONE_NEAR);
+    contract.set_payout_threshold(
+        "owner/repo".to_string(),
+        None,
+        Some(U128(DEPOSIT_ONE_NEAR.as_yoctonear())),
+    );
+    release_for_pr(&mut contract, bounty_id, MERGE_COMMIT_SHA);
+
+    let bounty = contract.get_bounty_by_id(bounty_id).unwrap();
+    assert_eq!(bounty.status, BountyStatus::Claimed);
+}
+
+// Test that a repo admin approving a pending payout starts the transfer
+#[test]
+fn test_approve_payout() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = release_above_threshold(&mut contract);
+
+    let context = get_context(accounts(3), false);
+    testing_env!(context.build());
+    let _ = contract.approve_payout(bounty_id);
+
+    let bounty = contract.get_bounty_by_id(bounty_id).unwrap();
+    assert_eq!(bounty.status, BountyStatus::Claimed);
+    assert!(
+        contract
+            .get_pending_payouts("owner/repo".to_string())
+            .is_empty()
+    );
+    let logs = near_sdk::test_utils::get_logs();
+    assert!(logs[0].contains("\"event\":\"payout_approved\""));
+}
+
+// Test that only a repo admin can approve a pending payout
+#[test]
+#[should_panic(expected = "Only repo admins can approve payouts")]
+fn test_approve_payout_not_admin() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = release_above_threshold(&mut contract);
+
+    let _ = contract.approve_payout(bounty_id);
+}
+
+// Test that approving a held payout holds it for the GitHub user
+#[test]
+fn test_approve_held_payout() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = fund_issue_bounty(&mut contract, 7, DEPOSIT_ONE_NEAR);
+    contract.set_payout_threshold("owner/repo".to_string(), None, Some(U128(1)));
+    let context = get_context_with_deposit(accounts(2), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    let _ = contract.release_bounty_to_github_user(
+        bounty_id,
+        42,
+        U128(DEPOSIT_ONE_NEAR.as_yoctonear()),
+        7,
+        MERGE_COMMIT_SHA.to_string(),
+    );
+    assert!(contract.get_claimable_balances(42).is_empty());
+
+    let context = get_context_with_deposit(accounts(3), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    let _ = contract.approve_payout(bounty_id);
+
+    assert_eq!(contract.get_claimable_balances(42).len(), 1);
+    let bounty = contract.get_bounty_by_id(bounty_id).unwrap();
+    assert_eq!(bounty.status, BountyStatus::Paid);
+}
+
+// Test that rejecting a pending payout reopens the bounty so the PR can be paid again
+#[test]
+fn test_reject_payout() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = release_above_threshold(&mut contract);
+
+    let context = get_context(accounts(3), false);
+    testing_env!(context.build());
+    contract.reject_payout(bounty_id);
+
+    let bounty = contract.get_bounty_by_id(bounty_id).unwrap();
+    assert_eq!(bounty.status, BountyStatus::Open);
+    assert_eq!(bounty.amount.0, DEPOSIT_ONE_NEAR.as_yoctonear());
+    assert!(contract.get_payout("owner/repo".to_string(), 7).is_none());
+    let logs = near_sdk::test_utils::get_logs();
+    assert!(logs[0].contains("\"event\":\"payout_rejected\""));
+}
+
+// Helper function to request a payout of half of a one NEAR bounty above a quarter NEAR threshold
+fn release_partial_above_threshold(contract: &mut Contract) -> BountyId {
+    let bounty_id = fund_issue_bounty(contract, 7, DEPOSIT_ONE_NEAR);
+    contract.set_payout_threshold(
+        "owner/repo".to_string(),
+        None,
+        Some(U128(DEPOSIT_ONE_NEAR.as_yoctonear() / 4)),
+    );
+
+    let context = get_context_with_deposit(accounts(2), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    let _ = contract.release_bounty(
+        bounty_id,
+        Some(accounts(4)),
+        None,
+        U128(DEPOSIT_ONE_NEAR.as_yoctonear() / 2),
+        7,
+        MERGE_COMMIT_SHA.to_string(),
+    );
+    bounty_id
+}
+
+// Test that rejecting a partial payout keeps the whole escrow in the bounty
+#[test]
+fn test_reject_partial_payout() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = release_partial_above_threshold(&mut contract);
+    assert_eq!(
+        contract.get_bounty_by_id(bounty_id).unwrap().amount.0,
+        DEPOSIT_ONE_NEAR.as_yoctonear()
+    );
+    assert_eq!(contract.get_bounty("owner/repo".to_string()).0, 0);
+
+    let context = get_context(accounts(3), false);
+    testing_env!(context.build());
+    contract.reject_payout(bounty_id);
+
+    let bounty = contract.get_bounty_by_id(bounty_id).unwrap();
+    assert_eq!(bounty.status, BountyStatus::Open);
+    assert_eq!(bounty.amount.0, DEPOSIT_ONE_NEAR.as_yoctonear());
+    assert_eq!(contract.get_bounty("owner/repo".to_string()).0, 0);
+}
+
+// Test that the remainder of an approved partial payout goes to the pool once it is paid
+#[test]
+fn test_approve_partial_payout() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = release_partial_above_threshold(&mut contract);
+    let amount = DEPOSIT_ONE_NEAR.as_yoctonear() / 2;
+
+    let context = get_context(accounts(3), false);
+    testing_env!(context.build());
+    let _ = contract.approve_payout(bounty_id);
+    assert_eq!(contract.get_bounty("owner/repo".to_string()).0, 0);
+
+    set_promise_result(PromiseResult::Successful(vec![]));
+    assert!(contract.on_bounty_released(bounty_id, vec![(accounts(4), U128(amount))], 7));
+    let bounty = contract.get_bounty_by_id(bounty_id).unwrap();
+    assert_eq!(bounty.status, BountyStatus::Paid);
+    assert_eq!(bounty.amount.0, amount);
+    assert_eq!(contract.get_bounty("owner/repo".to_string()).0, amount);
+}
+
+// Test that approving a payout after the timeout cancels it instead
+#[test]
+fn test_approve_payout_after_timeout() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = release_above_threshold(&mut contract);
+
+    let context = get_context_with_deposit_and_timestamp(
+        accounts(3),
+        false,
+        None,
+        Some(DEFAULT_PAYOUT_APPROVAL_TIMEOUT_MS),
+    );
+    testing_env!(context.build());
+    let result = contract.approve_payout(bounty_id);
+
+    assert!(matches!(result, PromiseOrValue::Value(false)));
+    let bounty = contract.get_bounty_by_id(bounty_id).unwrap();
+    assert_eq!(bounty.status, BountyStatus::Open);
+    assert_eq!(bounty.amount.0, DEPOSIT_ONE_NEAR.as_yoctonear());
+    let logs = near_sdk::test_utils::get_logs();
+    assert!(logs[0].contains("\"event\":\"pending_payout_expired\""));
+}
+
+// Test that anyone can cancel a pending payout, but only after the timeout
+#[test]
+#[should_panic(expected = "Payout approval has not timed out")]
+fn test_cancel_expired_payout_too_early() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = release_above_threshold(&mut contract);
+
+    let context = get_context(accounts(5), false);
+    testing_env!(context.build());
+    contract.cancel_expired_payout(bounty_id);
+}
+
+// Test that only a repo admin can set the payout threshold
+#[test]
+#[should_panic(expected = "Only repo admins can set the payout threshold")]
+fn test_set_payout_threshold_not_admin() {
+    let mut contract = setup_contract_with_repo();
+    let context = get_context_with_deposit(accounts(4), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    contract.set_payout_threshold("owner/repo".to_string(), None, Some(U128(1)));
+}
+
+// Helper function to fund issue `number` with one NEAR and release it for a PR of the same number
+fn release_issue_at(contract: &mut Contract, number: u64, timestamp_ms: u64) {
+    let bounty_id = fund_issue_bounty(contract, number, DEPOSIT_ONE_NEAR);
+    let context = get_context_with_deposit_and_timestamp(
+        accounts(2),
+        false,
+        Some(DEPOSIT_STORAGE),
+        Some(timestamp_ms),
+    );
+    testing_env!(context.build());
+    let _ = contract.release_bounty(
+        bounty_id,
+        Some(accounts(4)),
+        None,
+        U128(DEPOSIT_ONE_NEAR.as_yoctonear()),
+        number,
+        MERGE_COMMIT_SHA.to_string(),
+    );
+}
+
+// Helper function to set the owner payout limits
+fn set_payout_limits(contract: &mut Contract, payout_limits: PayoutLimits) {
+    let context = get_context(accounts(0), false);
+    testing_env!(context.build());
+    contract.set_payout_limits(payout_limits);
+}
+
+// Test that an agent cannot release more payouts per hour than the limit
+#[test]
+#[should_panic(expected = "Agent hourly payout count limit reached")]
+fn test_agent_hourly_count_limit() {
+    let mut contract = setup_contract_with_repo();
+    set_payout_limits(
+        &mut contract,
+        PayoutLimits {
+            agent_hourly: RateLimit {
+                max_count: Some(1),
+                max_amount: None,
+            },
+            ..Default::default()
+        },
+    );
+
+    release_issue_at(&mut contract, 7, 1000);
+    release_issue_at(&mut contract, 8, 2000);
+}
+
+// Test that the hourly window rolls over
+#[test]
+fn test_agent_hourly_limit_rolls_over() {
+    let mut contract = setup_contract_with_repo();
+    set_payout_limits(
+        &mut contract,
+        PayoutLimits {
+            agent_hourly: RateLimit {
+                max_count: Some(1),
+                max_amount: None,
+            },
+            ..Default::default()
+        },
+    );
+
+    release_issue_at(&mut contract, 7, 1000);
+    let allowance = contract.get_payout_allowance(accounts(2), "owner/repo".to_string());
+    assert_eq!(allowance.agent_hourly.remaining_count, Some(0));
+
+    let context = get_context_with_deposit_and_timestamp(
+        accounts(2),
+        false,
+        None,
+        Some(1000 + 60 * 60 * 1000),
+    );
+    testing_env!(context.build());
+    let allowance = contract.get_payout_allowance(accounts(2), "owner/repo".to_string());
+    assert_eq!(allowance.agent_hourly.remaining_count, Some(1));
+}
+
+// Test that an agent cannot release more than the daily amount limit
+#[test]
+#[should_panic(expected = "Agent daily payout amount limit exceeded")]
+fn test_agent_daily_amount_limit() {
+    let mut contract = setup_contract_with_repo();
+    set_payout_limits(
+        &mut contract,
+        PayoutLimits {
+            agent_daily: RateLimit {
+                max_count: None,
+                max_amount: Some(U128(DEPOSIT_ONE_NEAR.as_yoctonear() * 3 / 2)),
+            },
+            ..Default::default()
+        },
+    );
+
+    release_issue_at(&mut contract, 7, 1000);
+    release_issue_at(&mut contract, 8, 2000);
+}
+
+// Test that a repo admin can override the repo daily limit
+#[test]
+#[should_panic(expected = "Repo daily payout count limit reached")]
+fn test_repo_payout_limit_override() {
+    let mut contract = setup_contract_with_repo();
+    set_payout_limits(
+        &mut contract,
+        PayoutLimits {
+            repo_daily: RateLimit {
+                max_count: Some(5),
+                max_amount: None,
+            },
+            ..Default::default()
+        },
+    );
+    let context = get_context_with_deposit(accounts(3), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    let limit = RateLimit {
+        max_count: Some(1),
+        max_amount: None,
+    };
+    contract.set_repo_payout_limit("owner/repo".to_string(), Some(limit));
+    assert_eq!(
+        contract.get_repo_payout_limit("owner/repo".to_string()),
+        limit
+    );
+
+    release_issue_at(&mut contract, 7, 1000);
+    release_issue_at(&mut contract, 8, 2000);
+}
+
+// Test that only a repo admin can override the repo limit
+#[test]
+#[should_panic(expected = "Only repo admins can set the payout limit")]
+fn test_set_repo_payout_limit_not_admin() {
+    let mut contract = setup_contract_with_repo();
+    let context = get_context_with_deposit(accounts(4), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    contract.set_repo_payout_limit("owner/repo".to_string(), None);
+}
+
+// Test that only a BountyArbiter or the owner can set the payout limits
+#[test]
+#[should_panic(expected = "Caller is not the owner and does not have the BountyArbiter role")]
+fn test_set_payout_limits_not_arbiter() {
+    let mut contract = setup_contract();
+    let context = get_context(accounts(4), false);
+    testing_env!(context.build());
+    contract.set_payout_limits(PayoutLimits::default());
+}
+
+// Test that the allowance view shows what is left in each window
+#[test]
+fn test_get_payout_allowance() {
+    let mut contract = setup_contract_with_repo();
+    set_payout_limits(
+        &mut contract,
+        PayoutLimits {
+            agent_hourly: RateLimit {
+                max_count: Some(3),
+                max_amount: Some(U128(DEPOSIT_ONE_NEAR.as_yoctonear() * 2)),
+            },
+            ..Default::default()
+        },
+    );
+    release_issue_at(&mut contract, 7, 1000);
+
+    let allowance = contract.get_payout_allowance(accounts(2), "owner/repo".to_string());
+    assert_eq!(allowance.agent_hourly.remaining_count, Some(2));
+    assert_eq!(
+        allowance.agent_hourly.remaining_amount,
+        Some(U128(DEPOSIT_ONE_NEAR.as_yoctonear()))
+    );
+    assert_eq!(allowance.agent_daily.remaining_count, None);
+    assert_eq!(allowance.repo_daily.remaining_amount, None);
+}
+
+// Test that token payouts are refused while an amount cap applies, as caps are in yoctoNEAR
+#[test]
+#[should_panic(
+    expected = "Fungible token payouts are not allowed while a payout amount limit is set"
+)]
+fn test_token_payout_with_amount_limit() {
+    let mut contract = setup_contract_with_repo();
+    ft_fund(
+        &mut contract,
+        accounts(3),
+        40,
+        r#"{"repo_id": "owner/repo", "number": 7}"#,
+    );
+    set_payout_limits(
+        &mut contract,
+        PayoutLimits {
+            repo_daily: RateLimit {
+                max_count: None,
+                max_amount: Some(U128(DEPOSIT_ONE_NEAR.as_yoctonear())),
+            },
+            ..Default::default()
+        },
+    );
+
+    let bounty_id = contract
+        .get_issue_bounty("owner/repo".to_string(), 7)
+        .unwrap()
+        .bounty_id;
+    let context = get_context_with_deposit(accounts(2), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    let _ = contract.release_bounty(
+        bounty_id,
+        Some(accounts(4)),
+        None,
+        U128(40),
+        7,
+        MERGE_COMMIT_SHA.to_string(),
+    );
+}
+
+// Test that usage is only recorded where a limit applies
+#[test]
+fn test_payout_usage_without_limits() {
+    let mut contract = setup_contract_with_repo();
+    release_issue_at(&mut contract, 7, 1000);
+    assert!(contract.agent_payout_usage.get(&accounts(2)).is_none());
+    assert!(contract.repo_payout_usage.get("owner/repo").is_none());
+
+    set_payout_limits(
+        &mut contract,
+        PayoutLimits {
+            repo_daily: RateLimit {
+                max_count: Some(5),
+                max_amount: None,
+            },
+            ..Default::default()
+        },
+    );
+    release_issue_at(&mut contract, 8, 2000);
+    assert!(contract.agent_payout_usage.get(&accounts(2)).is_none());
+    assert_eq!(
+        contract.repo_payout_usage.get("owner/repo").unwrap().len(),
+        1
+    );
+}
+
+// Helper function to allow one payout a day per agent and per repo
+fn limit_to_one_payout(contract: &mut Contract) {
+    let one_payout = RateLimit {
+        max_count: Some(1),
+        max_amount: None,
+    };
+    set_payout_limits(
+        contract,
+        PayoutLimits {
+            agent_daily: one_payout,
+            repo_daily: one_payout,
+            ..Default::default()
+        },
+    );
+}
+
+// Helper function to assert the daily payout count left for agent accounts(2) and the repo
+fn assert_daily_payouts_left(contract: &Contract, count: u32) {
+    let allowance = contract.get_payout_allowance(accounts(2), "owner/repo".to_string());
+    assert_eq!(allowance.agent_daily.remaining_count, Some(count));
+    assert_eq!(allowance.repo_daily.remaining_count, Some(count));
+}
+
+// Test that a payout whose transfers all failed no longer counts towards the limits
+#[test]
+fn test_failed_payout_releases_usage() {
+    let mut contract = setup_contract_with_repo();
+    limit_to_one_payout(&mut contract);
+    release_issue_at(&mut contract, 7, 1000);
+    assert_daily_payouts_left(&contract, 0);
+
+    let bounty_id = contract
+        .get_issue_bounty("owner/repo".to_string(), 7)
+        .unwrap()
+        .bounty_id;
+    set_promise_result(PromiseResult::Failed);
+    assert!(!contract.on_bounty_released(bounty_id, one_near_to(accounts(4)), 7));
+    assert_daily_payouts_left(&contract, 1);
+    assert!(contract.agent_payout_usage.get(&accounts(2)).is_none());
+    assert!(contract.repo_payout_usage.get("owner/repo").is_none());
+}
+
+// Test that a rejected payout no longer counts towards the limits
+#[test]
+fn test_rejected_payout_releases_usage() {
+    let mut contract = setup_contract_with_repo();
+    limit_to_one_payout(&mut contract);
+    let bounty_id = release_above_threshold(&mut contract);
+    assert_daily_payouts_left(&contract, 0);
+
+    let context = get_context(accounts(3), false);
+    testing_env!(context.build());
+    contract.reject_payout(bounty_id);
+    assert_daily_payouts_left(&contract, 1);
+}
+
+// Test that a payout cancelled after the approval timeout no longer counts towards the limits
+#[test]
+fn test_expired_payout_releases_usage() {
+    let mut contract = setup_contract_with_repo();
+    limit_to_one_payout(&mut contract);
+    let bounty_id = release_above_threshold(&mut contract);
+    // The timeout is longer than the daily window, so only the stored usage shows the release
+    assert!(contract.repo_payout_usage.get("owner/repo").is_some());
+
+    let context = get_context_with_deposit_and_timestamp(
+        accounts(5),
+        false,
+        None,
+        Some(DEFAULT_PAYOUT_APPROVAL_TIMEOUT_MS),
+    );
+    testing_env!(context.build());
+    contract.cancel_expired_payout(bounty_id);
+    assert!(contract.agent_payout_usage.get(&accounts(2)).is_none());
+    assert!(contract.repo_payout_usage.get("owner/repo").is_none());
+}
+
+// Helper function to fund issue 7 as a sponsor without a role in the repo
+fn sponsor_issue_bounty(
+    contract: &mut Contract,
+    sponsor: AccountId,
+    deposit: NearToken,
+) -> BountyId {
+    let context = get_context_with_deposit_and_timestamp(
+        sponsor,
+        false,
+        Some(with_storage(deposit)),
+        Some(0),
+    );
+    testing_env!(context.build());
+    contract
+        .fund_bounty(
+            "owner/repo".to_string(),
+            Some(7),
+            None,
+            U128(deposit.as_yoctonear()),
+        )
+        .unwrap()
+}
+
+// Test that any account can sponsor a bounty and its contribution is tracked
+#[test]
+fn test_sponsor_bounty() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = fund_issue_bounty(&mut contract, 7, DEPOSIT_ONE_NEAR);
+    sponsor_issue_bounty(&mut contract, accounts(4), DEPOSIT_ONE_NEAR);
+    let logs = near_sdk::test_utils::get_logs();
+    assert!(logs[0].contains("\"sponsor\":true"));
+
+    let contributions = contract.get_bounty_contributions(bounty_id);
+    assert_eq!(contributions.len(), 2);
+    assert_eq!(contributions[0].account_id, accounts(3));
+    assert!(!contributions[0].sponsor);
+    assert_eq!(contributions[1].account_id, accounts(4));
+    assert!(contributions[1].sponsor);
+    assert_eq!(
+        contract.get_bounty_by_id(bounty_id).unwrap().amount.0,
+        DEPOSIT_ONE_NEAR.as_yoctonear() * 2
+    );
+}
+
+// Test that cancelling refunds sponsors pro-rata and returns the rest to the pool
+#[test]
+fn test_cancel_sponsored_bounty() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = fund_issue_bounty(&mut contract, 7, DEPOSIT_ONE_NEAR);
+    sponsor_issue_bounty(&mut contract, accounts(4), DEPOSIT_ONE_NEAR);
+    sponsor_issue_bounty(&mut contract, accounts(5), NearToken::from_near(2));
+
+    let context = get_context_with_deposit(accounts(3), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    contract.cancel_bounty(bounty_id);
+
+    assert_eq!(
+        contract.get_refund(accounts(4), None).0,
+        DEPOSIT_ONE_NEAR.as_yoctonear()
+    );
+    assert_eq!(
+        contract.get_refund(accounts(5), None).0,
+        DEPOSIT_ONE_NEAR.as_yoctonear() * 2
+    );
+    assert_eq!(contract.get_refund(accounts(3), None).0, 0);
+    assert_eq!(
+        contract.get_bounty("owner/repo".to_string()).0,
+        DEPOSIT_ONE_NEAR.as_yoctonear()
+    );
+}
+
+// Test that a bounty reopened after a partial payout is refunded pro-rata on what is left
+#[test]
+fn test_cancel_sponsored_bounty_after_failed_payout() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = fund_issue_bounty(&mut contract, 7, DEPOSIT_ONE_NEAR);
+    sponsor_issue_bounty(&mut contract, accounts(4), DEPOSIT_ONE_NEAR);
+
+    // The failed payout reopens the bounty with its whole escrow
+    let context = get_context_with_deposit(accounts(2), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    let _ = contract.release_bounty(
+        bounty_id,
+        Some(accounts(5)),
+        None,
+        U128(DEPOSIT_ONE_NEAR.as_yoctonear()),
+        7,
+        MERGE_COMMIT_SHA.to_string(),
+    );
+    set_promise_result(PromiseResult::Failed);
+    contract.on_bounty_released(bounty_id, one_near_to(accounts(5)), 7);
+
+    let context = get_context_with_deposit(accounts(3), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    contract.cancel_bounty(bounty_id);
+    assert_eq!(
+        contract.get_refund(accounts(4), None).0,
+        DEPOSIT_ONE_NEAR.as_yoctonear()
+    );
+    assert_eq!(
+        contract.get_bounty("owner/repo".to_string()).0,
+        DEPOSIT_ONE_NEAR.as_yoctonear()
+    );
+}
+
+// Test that an expired bounty refunds every direct funder pro-rata but pool funds go back to the pool
+#[test]
+fn test_reclaim_expired_sponsored_bounty() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = fund_bounty_with_deadline(&mut contract);
+    sponsor_issue_bounty(&mut contract, accounts(4), DEPOSIT_ONE_NEAR);
+
+    // Move one NEAR from the pool into the escrow
+    let context =
+        get_context_with_deposit(accounts(3), false, Some(with_storage(DEPOSIT_ONE_NEAR)));
+    testing_env!(context.build());
+    contract.fund_bounty(
+        "owner/repo".to_string(),
+        None,
+        None,
+        U128(DEPOSIT_ONE_NEAR.as_yoctonear()),
+    );
+    contract.create_bounty(
+        "owner/repo".to_string(),
+        7,
+        U128(DEPOSIT_ONE_NEAR.as_yoctonear()),
+        None,
+    );
+
+    let context = get_context_with_deposit_and_timestamp(
+        accounts(5),
+        false,
+        Some(DEPOSIT_STORAGE),
+        Some(10_000),
+    );
+    testing_env!(context.build());
+    contract.reclaim_expired_bounty(bounty_id);
+
+    assert_eq!(
+        contract.get_refund(accounts(3), None).0,
+        DEPOSIT_ONE_NEAR.as_yoctonear()
+    );
+    assert_eq!(
+        contract.get_refund(accounts(4), None).0,
+        DEPOSIT_ONE_NEAR.as_yoctonear()
+    );
+    assert_eq!(
+        contract.get_bounty("owner/repo".to_string()).0,
+        DEPOSIT_ONE_NEAR.as_yoctonear()
+    );
+}
+
+// Test that sponsoring the pool is tracked per sponsor
+#[test]
+fn test_sponsor_pool() {
+    let mut contract = setup_contract_with_repo();
+    let context =
+        get_context_with_deposit(accounts(4), false, Some(with_storage(DEPOSIT_ONE_NEAR)));
+    testing_env!(context.build());
+    contract.fund_bounty(
+        "owner/repo".to_string(),
+        None,
+        None,
+        U128(DEPOSIT_ONE_NEAR.as_yoctonear()),
+    );
+
+    assert_eq!(
+        contract
+            .get_pool_sponsorship("owner/repo".to_string(), accounts(4), None)
+            .0,
+        DEPOSIT_ONE_NEAR.as_yoctonear()
+    );
+    assert_eq!(
+        contract.get_bounty("owner/repo".to_string()).0,
+        DEPOSIT_ONE_NEAR.as_yoctonear()
+    );
+}
+
+// Helper function to fund the pool of the repo with one NEAR as a sponsor
+fn sponsor_pool(contract: &mut Contract, sponsor: AccountId) {
+    let context = get_context_with_deposit(sponsor, false, Some(with_storage(DEPOSIT_ONE_NEAR)));
+    testing_env!(context.build());
+    contract.fund_bounty(
+        "owner/repo".to_string(),
+        None,
+        None,
+        U128(DEPOSIT_ONE_NEAR.as_yoctonear()),
+    );
+}
+
+// Test that repo admins can only withdraw the part of the pool they did not get from sponsors
+#[test]
+#[should_panic(expected = "Sponsored pool funds cannot be withdrawn")]
+fn test_withdraw_sponsored_pool() {
+    let mut contract = setup_contract_with_repo();
+    sponsor_pool(&mut contract, accounts(4));
+    sponsor_pool(&mut contract, accounts(3));
+    assert_eq!(
+        contract
+            .get_sponsored_pool_balance("owner/repo".to_string(), None)
+            .0,
+        DEPOSIT_ONE_NEAR.as_yoctonear()
+    );
+
+    let context = get_context(accounts(3), false);
+    testing_env!(context.build());
+    let _ = contract.withdraw_bounty(
+        "owner/repo".to_string(),
+        U128(DEPOSIT_ONE_NEAR.as_yoctonear()),
+        None,
+    );
+    let _ = contract.withdraw_bounty("owner/repo".to_string(), U128(1), None);
+}
+
+// Test that sponsored pool funds moved into a bounty stay sponsored when it is cancelled
+#[test]
+fn test_cancel_bounty_from_sponsored_pool() {
+    let mut contract = setup_contract_with_repo();
+    sponsor_pool(&mut contract, accounts(4));
+
+    let context = get_context_with_deposit(accounts(3), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    let bounty_id = contract.create_bounty(
+        "owner/repo".to_string(),
+        7,
+        U128(DEPOSIT_ONE_NEAR.as_yoctonear()),
+        None,
+    );
+    assert_eq!(
+        contract
+            .get_sponsored_pool_balance("owner/repo".to_string(), None)
+            .0,
+        0
+    );
+    let contributions = contract.get_bounty_contributions(bounty_id);
+    assert_eq!(contributions.len(), 1);
+    assert!(contributions[0].from_pool && contributions[0].sponsor);
+
+    contract.cancel_bounty(bounty_id);
+    assert_eq!(contract.get_refund(accounts(3), None).0, 0);
+    assert_eq!(
+        contract
+            .get_sponsored_pool_balance("owner/repo".to_string(), None)
+            .0,
+        DEPOSIT_ONE_NEAR.as_yoctonear()
+    );
+}
+
+// Test that sponsors get their share of what a partial payout leaves in the escrow
+#[test]
+fn test_partial_payout_of_sponsored_bounty() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = fund_issue_bounty(&mut contract, 7, DEPOSIT_ONE_NEAR);
+    sponsor_issue_bounty(&mut contract, accounts(4), DEPOSIT_ONE_NEAR);
+
+    let context = get_context_with_deposit(accounts(2), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    let _ = contract.release_bounty(
+        bounty_id,
+        Some(accounts(5)),
+        None,
+        U128(DEPOSIT_ONE_NEAR.as_yoctonear()),
+        7,
+        MERGE_COMMIT_SHA.to_string(),
+    );
+    set_promise_result(PromiseResult::Successful(vec![]));
+    assert!(contract.on_bounty_released(bounty_id, one_near_to(accounts(5)), 7));
+
+    assert_eq!(
+        contract.get_refund(accounts(4), None).0,
+        DEPOSIT_ONE_NEAR.as_yoctonear() / 2
+    );
+    assert_eq!(
+        contract.get_bounty("owner/repo".to_string()).0,
+        DEPOSIT_ONE_NEAR.as_yoctonear() / 2
+    );
+}
+
+// Test that only a repo admin can disable third-party funding
+#[test]
+#[should_panic(expected = "Only repo admins can change third-party funding")]
+fn test_set_third_party_funding_not_admin() {
+    let mut contract = setup_contract_with_repo();
+    let context = get_context(accounts(4), false);
+    testing_env!(context.build());
+    contract.set_third_party_funding("owner/repo".to_string(), false);
+}
+
+// Test that a withdrawn refund cannot be withdrawn twice
+#[test]
+#[should_panic(expected = "Nothing to refund")]
+fn test_withdraw_refund_twice() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = fund_bounty_with_deadline(&mut contract);
+
+    let context = get_context_with_deposit_and_timestamp(
+        accounts(3),
+        false,
+        Some(DEPOSIT_STORAGE),
+        Some(10_000),
+    );
+    testing_env!(context.build());
+    contract.reclaim_expired_bounty(bounty_id);
+    let _ = contract.withdraw_refund(None);
+    let _ = contract.withdraw_refund(None);
+}
+
+// Test that the pro-rata split does not overflow on large amounts
+#[test]
+fn test_mul_div() {
+    use crate::internal::sponsors::mul_div;
+    assert_eq!(mul_div(10, 3, 4), 7);
+    assert_eq!(mul_div(u128::MAX, u128::MAX, u128::MAX), u128::MAX);
+    assert_eq!(mul_div(u128::MAX, 2, 4), u128::MAX / 2);
+    let near = DEPOSIT_ONE_NEAR.as_yoctonear();
+    assert_eq!(mul_div(near * 3, near, near * 4), near * 3 / 4);
+    assert_eq!(mul_div(u128::MAX, u128::MAX - 1, u128::MAX), u128::MAX - 1);
+    assert_eq!(mul_div(u128::MAX - 1, u128::MAX, u128::MAX - 1), u128::MAX);
+    assert_eq!(mul_div(u128::MAX, u128::MAX / 3, u128::MAX), u128::MAX / 3);
+    assert_eq!(mul_div(u128::MAX / 2, 3, u128::MAX), 1);
+    assert_eq!(mul_div(u128::MAX, 1, 2), u128::MAX / 2);
+}
+
+// Test that the rounding dust of a pro-rata refund goes back to the pool and nothing is lost
+#[test]
+fn test_refund_contributions_rounding_dust() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = fund_issue_bounty(&mut contract, 7, NearToken::from_yoctonear(1));
+    sponsor_issue_bounty(&mut contract, accounts(4), NearToken::from_yoctonear(2));
+    sponsor_issue_bounty(&mut contract, accounts(5), NearToken::from_yoctonear(4));
+    let pool_before = contract.get_bounty("owner/repo".to_string()).0;
+
+    // 5 of the 7 yoctoNEAR are split 5/7, 10/7 and 20/7
+    let bounty = contract.bounty_records.get(&bounty_id).unwrap().clone();
+    let refunded = contract.internal_refund_contributions(
+        bounty_id,
+        &bounty,
+        5,
+        crate::internal::sponsors::is_sponsor_refundable,
+    );
+
+    assert_eq!(contract.get_refund(accounts(4), None).0, 1);
+    assert_eq!(contract.get_refund(accounts(5), None).0, 2);
+    assert_eq!(refunded, 3);
+    let returned_to_pool = contract.get_bounty("owner/repo".to_string()).0 - pool_before;
+    assert_eq!(refunded + returned_to_pool, 5);
+}
+
+// Helper function to register agent accounts(2) at time 0, valid for 100 seconds
+fn setup_contract_with_agent() -> Contract {
+    let mut contract = setup_contract();
+    contract.whitelist_agent_for_local(accounts(2));
+    let context =
+        get_context_with_deposit_and_timestamp(accounts(2), false, Some(DEPOSIT_005_NEAR), Some(0));
+    testing_env!(context.build());
+    contract.register_agent(create_mock_dstack_attestation());
+    contract
+}
+
+// Test that a registered agent can renew its attestation without a deposit
+#[test]
+fn test_renew_attestation() {
+    let mut contract = setup_contract_with_agent();
+
+    let context = get_context_with_deposit_and_timestamp(accounts(2), false, None, Some(90_000));
+    testing_env!(context.build());
+    assert!(contract.renew_attestation(create_mock_dstack_attestation()));
+
+    let agent = contract.get_agent(accounts(2)).unwrap();
+    assert_eq!(agent.valid_until_ms.0, 190_000);
+    let logs = near_sdk::test_utils::get_logs();
+    assert!(logs[0].contains("\"event\":\"agent_renewed\""));
+    assert!(logs[0].contains("\"old_valid_until_ms\":\"100000\""));
+}
+
+// Test that an account that is not registered cannot renew
+#[test]
+#[should_panic(expected = "Agent not registered")]
+fn test_renew_attestation_not_registered() {
+    let mut contract = setup_contract();
+    contract.whitelist_agent_for_local(accounts(2));
+    let context = get_context(accounts(2), false);
+    testing_env!(context.build());
+    contract.renew_attestation(create_mock_dstack_attestation());
+}
+
+// Test that renewal with different measurements needs an approved change
+#[test]
+#[should_panic(expected = "Measurements or PPID changed without approval")]
+fn test_renew_attestation_changed_measurements() {
+    let mut contract = setup_contract_with_agent();
+    contract.agents.get_mut(&accounts(2)).unwrap().measurements = non_default_measurements();
+
+    let context = get_context(accounts(2), false);
+    testing_env!(context.build());
+    contract.renew_attestation(create_mock_dstack_attestation());
+}
+
+// Test that an approved change lets the agent renew with the new measurements once
+#[test]
+fn test_renew_attestation_approved_change() {
+    let mut contract = setup_contract_with_agent();
+    contract.agents.get_mut(&accounts(2)).unwrap().measurements = non_default_measurements();
+
+    let context = get_context_with_deposit(accounts(0), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    contract.approve_agent_change(
+        accounts(2),
+        create_mock_full_measurements_hex(),
+        Ppid::default(),
+    );
+
+    let context = get_context(accounts(2), false);
+    testing_env!(context.build());
+    assert!(contract.renew_attestation(create_mock_dstack_attestation()));
+    assert_eq!(
+        contract.get_agent(accounts(2)).unwrap().measurements,
+        create_mock_full_measurements_hex()
+    );
+    assert!(contract.approved_agent_changes.get(&accounts(2)).is_none());
+}
+
+// Test that the expiring view lists agents whose attestation ends within the window
+#[test]
+fn test_get_agents_expiring_within() {
+    let contract = setup_contract_with_agent();
+
+    let context = get_context_with_deposit_and_timestamp(accounts(0), false, None, Some(50_000));
+    testing_env!(context.build());
+    assert!(
+        contract
+            .get_agents_expiring_within(U64::from(49_999), &None, &None)
+            .is_empty()
+    );
+    let expiring = contract.get_agents_expiring_within(U64::from(50_000), &None, &None);
+    assert_eq!(expiring.len(), 1);
+    assert_eq!(expiring[0].account_id, accounts(2));
+    assert!(
+        contract
+            .get_agents_expiring_within(U64::from(u64::MAX), &Some(1), &None)
+            .is_empty()
+    );
+}
+
+// Test that deprecated measurements keep registered agents valid until the deadline
+#[test]
+fn test_deprecate_measurements() {
+    let mut contract = setup_contract_with_agent();
+
+    let context = get_context_with_deposit_and_timestamp(accounts(0), false, None, Some(10_000));
+    testing_env!(context.build());
+    contract.deprecate_measurements(create_mock_full_measurements_hex(), U64::from(50_000));
+    let logs = near_sdk::test_utils::get_logs();
+    assert!(logs[0].contains("\"event\":\"measurements_deprecated\""));
+    assert_eq!(
+        contract.get_approved_measurements(&None, &None)[0].status,
+        MeasurementStatus::Deprecated {
+            until_ms: U64::from(50_000)
+        }
+    );
+
+    let context = get_context_with_deposit_and_timestamp(accounts(0), false, None, Some(49_999));
+    testing_env!(context.build());
+    assert!(matches!(
+        contract.get_agent(accounts(2)).unwrap().validity,
+        AgentValidity::Valid
+    ));
+
+    let context = get_context_with_deposit_and_timestamp(accounts(0), false, None, Some(50_000));
+    testing_env!(context.build());
+    assert!(matches!(
+        contract.get_agent(accounts(2)).unwrap().validity,
+        AgentValidity::Invalid(ref r) if r == &vec![AgentRemovalReason::InvalidMeasurements]
+    ));
+}
+
+// Test that deprecated measurements block new registrations
+#[test]
+#[should_panic(expected = "Default measurements must be approved for local mode")]
+fn test_register_agent_deprecated_measurements() {
+    let mut contract = setup_contract();
+    contract.whitelist_agent_for_local(accounts(2));
+    contract.deprecate_measurements(create_mock_full_measurements_hex(), U64::from(50_000));
+
+    let context =
+        get_context_with_deposit_and_timestamp(accounts(2), false, Some(DEPOSIT_005_NEAR), Some(0));
+    testing_env!(context.build());
+    contract.register_agent(create_mock_dstack_attestation());
+}
+
+// Test that revoked measurements invalidate agents immediately and stay listed
+#[test]
+fn test_revoke_measurements() {
+    let mut contract = setup_contract_with_agent();
+
+    let context = get_context(accounts(0), false);
+    testing_env!(context.build());
+    contract.revoke_measurements(create_mock_full_measurements_hex());
+    let logs = near_sdk::test_utils::get_logs();
+    assert!(logs[0].contains("\"event\":\"measurements_revoked\""));
+
+    assert_eq!(
+        contract.get_approved_measurements(&None, &None)[0].status,
+        MeasurementStatus::Revoked
+    );
+    assert!(matches!(
+        contract.get_agent(accounts(2)).unwrap().validity,
+        AgentValidity::Invalid(ref r) if r.contains(&AgentRemovalReason::InvalidMeasurements)
+    ));
+
+    // Approving the measurements again makes them active
+    contract.approve_measurements(
+        create_mock_full_measurements_hex(),
+        "agent v1".to_string(),
+        "sha256:abc".to_string(),
+    );
+    assert_eq!(
+        contract.get_approved_measurements(&None, &None)[0].status,
+        MeasurementStatus::Active
+    );
+    assert!(matches!(
+        contract.get_agent(accounts(2)).unwrap().validity,
+        AgentValidity::Valid
+    ));
+}
+
+// Test that only active measurements can be deprecated
+#[test]
+#[should_panic(expected = "Only active measurements can be deprecated")]
+fn test_deprecate_revoked_measurements() {
+    let mut contract = setup_contract();
+    contract.revoke_measurements(create_mock_full_measurements_hex());
+    contract.deprecate_measurements(create_mock_full_measurements_hex(), U64::from(50_000));
+}
+
+// Test that approved measurements record their release metadata and agents show the label
+#[test]
+fn test_measurements_info() {
+    let mut contract = setup_contract_with_agent();
+
+    let context = get_context_with_deposit_and_timestamp(accounts(0), false, None, Some(5_000));
+    testing_env!(context.build());
+    contract.approve_measurements(
+        non_default_measurements(),
+        "agent v1.4.2".to_string(),
+        "git:3f2a9c1".to_string(),
+    );
+    let logs = near_sdk::test_utils::get_logs();
+    assert!(logs[0].contains("\"label\":\"agent v1.4.2\""));
+
+    let listed = contract.get_approved_measurements(&None, &None);
+    let info = listed
+        .iter()
+        .find(|m| m.measurements == non_default_measurements())
+        .unwrap()
+        .info
+        .clone()
+        .unwrap();
+    assert_eq!(info.label, "agent v1.4.2");
+    assert_eq!(info.source, "git:3f2a9c1");
+    assert_eq!(info.approved_by, accounts(0));
+    assert_eq!(info.approved_at_ms.0, 5_000);
+
+    assert_eq!(
+        contract.get_agent(accounts(2)).unwrap().measurements_label,
+        Some("agent v1".to_string())
+    );
+
+    // Approving the active measurements of the agent again updates their info in place
+    contract.approve_measurements(
+        create_mock_full_measurements_hex(),
+        "agent v1.0.1".to_string(),
+        "git:77aa01e".to_string(),
+    );
+    assert_eq!(
+        contract.get_agent(accounts(2)).unwrap().measurements_label,
+        Some("agent v1.0.1".to_string())
+    );
+    let info = contract
+        .measurement_infos
+        .get(&create_mock_full_measurements_hex())
+        .unwrap();
+    assert_eq!(info.source, "git:77aa01e");
+    assert_eq!(info.approved_at_ms.0, 5_000);
+}
+
+// Test that measurements cannot be approved without a label
+#[test]
+#[should_panic(expected = "Label cannot be empty")]
+fn test_approve_measurements_empty_label() {
+    let mut contract = setup_contract();
+    contract.approve_measurements(
+        non_default_measurements(),
+        String::new(),
+        "sha256:abc".to_string(),
+    );
+}
+
+// Test that registration records the measured storage cost and does not charge a registered agent again
+#[test]
+fn test_register_agent_storage_deposit() {
+    let mut contract = setup_contract_with_agent();
+    let storage_deposit = contract.get_agent(accounts(2)).unwrap().storage_deposit.0;
+    assert!(storage_deposit > 0);
+    assert!(storage_deposit < DEPOSIT_005_NEAR.as_yoctonear());
+    let recorded = contract.get_agent_storage_deposit(accounts(2)).unwrap();
+    assert_eq!(recorded.amount.0, storage_deposit);
+    assert_eq!(recorded.refund_to, accounts(2));
+    assert!(contract.get_agent_storage_deposit(accounts(3)).is_none());
+
+    // Registering again uses no new storage
+    let context = get_context_with_deposit_and_timestamp(accounts(2), false, None, Some(0));
+    testing_env!(context.build());
+    assert!(contract.register_agent(create_mock_dstack_attestation()));
+    assert_eq!(
+        contract.get_agent(accounts(2)).unwrap().storage_deposit.0,
+        storage_deposit
+    );
+}
+
+// Test that a manually removed agent gets its storage deposit back at its refund account
+#[test]
+fn test_remove_agent_refunds_storage() {
+    let mut contract = setup_contract_with_agent();
+
+    let context = get_context_with_deposit(accounts(2), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    contract.set_storage_refund_account(Some(accounts(4)));
+    let storage_cost = contract.get_agent(accounts(2)).unwrap().storage_deposit.0;
+
+    let context = get_context(accounts(0), false);
+    testing_env!(context.build());
+    contract.remove_agent(accounts(2));
+    let logs = near_sdk::test_utils::get_logs();
+    assert!(logs[1].contains("\"event\":\"agent_storage_refunded\""));
+    assert!(logs[1].contains(&format!("\"refund_to\":\"{}\"", accounts(4))));
+    assert!(logs[1].contains(&format!("\"amount\":\"{}\"", storage_cost)));
+    assert!(contract.agent_storage_deposits.get(&accounts(2)).is_none());
+}
+
+// Test that an agent removed for being invalid gets its storage deposit back
+#[test]
+fn test_invalid_agent_refunds_storage() {
+    let mut contract = setup_contract_with_agent();
+    let context = get_context(accounts(0), false);
+    testing_env!(context.build());
+    contract.remove_measurements(create_mock_full_measurements_hex());
+
+    let context = get_context(accounts(2), false);
+    testing_env!(context.build());
+    assert!(contract.require_valid_agent().is_some());
+    let logs = near_sdk::test_utils::get_logs();
+    assert!(logs[1].contains("\"event\":\"agent_storage_refunded\""));
+    assert!(logs[1].contains(&format!("\"refund_to\":\"{}\"", accounts(2))));
+}
+
+// Helper function to sum the NEAR transferred to an account by the current call
+fn transferred_to(account_id: &AccountId) -> u128 {
+    near_sdk::test_utils::get_created_receipts()
+        .iter()
+        .filter(|receipt| &receipt.receiver_id == account_id)
+        .flat_map(|receipt| receipt.actions.iter())
+        .map(|action| match action {
+            near_sdk::mock::MockAction::Transfer { deposit, .. } => deposit.as_yoctonear(),
+            _ => 0,
+        })
+        .sum()
+}
+
+// Test that funding charges the measured storage and refunds the rest of the deposit
+#[test]
+fn test_fund_bounty_storage_refund() {
+    let mut contract = setup_contract_with_repo();
+
+    fund_issue_bounty(&mut contract, 7, DEPOSIT_ONE_NEAR);
+    let refunded = transferred_to(&accounts(3));
+    assert!(refunded > 0 && refunded < DEPOSIT_STORAGE.as_yoctonear());
+
+    // Topping up the same bounty uses no new storage
+    fund_issue_bounty(&mut contract, 7, DEPOSIT_ONE_NEAR);
+    assert_eq!(transferred_to(&accounts(3)), DEPOSIT_STORAGE.as_yoctonear());
+    assert_eq!(
+        contract
+            .get_issue_bounty("owner/repo".to_string(), 7)
+            .unwrap()
+            .amount
+            .0,
+        2 * DEPOSIT_ONE_NEAR.as_yoctonear()
+    );
+}
+
+// Test that the attached deposit must cover the funded amount
+#[test]
+#[should_panic(expected = "Attached deposit must cover the amount")]
+fn test_fund_bounty_deposit_below_amount() {
+    let mut contract = setup_contract_with_repo();
+    let context = get_context_with_deposit(accounts(3), false, Some(DEPOSIT_005_NEAR));
+    testing_env!(context.build());
+    contract.fund_bounty(
+        "owner/repo".to_string(),
+        Some(7),
+        None,
+        U128(DEPOSIT_ONE_NEAR.as_yoctonear()),
+    );
+}
+
+// Test that registering a repo without a deposit for its storage panics with the cost
+#[test]
+#[should_panic(expected = "Attached deposit must be greater than storage cost: ")]
+fn test_register_repo_without_storage_deposit() {
+    let mut contract = setup_contract_with_repo();
+    let context = get_context(accounts(2), false);
+    testing_env!(context.build());
+    let _ = contract.register_repo("owner/other".to_string(), accounts(3));
+}
+
+// Helper function to make a call with DEPOSIT_STORAGE attached and check it charged the storage
+// it used out of the deposit and refunded the rest to the caller
+fn assert_charges_storage(
+    contract: &mut Contract,
+    caller: AccountId,
+    block_timestamp_ms: Option<u64>,
+    call: impl FnOnce(&mut Contract),
+) {
+    let context = get_context_with_deposit_and_timestamp(
+        caller.clone(),
+        false,
+        Some(DEPOSIT_STORAGE),
+        block_timestamp_ms,
+    );
+    testing_env!(context.build());
+    let initial_storage_usage = contract.internal_storage_usage();
+    call(contract);
+    let bytes = contract.internal_storage_usage() - initial_storage_usage;
+    assert!(bytes > 0);
+    let cost = env::storage_byte_cost().as_yoctonear() * u128::from(bytes);
+    assert_eq!(
+        transferred_to(&caller),
+        DEPOSIT_STORAGE.as_yoctonear() - cost
+    );
+}
+
+// Test that the repo settings an admin stores are paid for by the admin
+#[test]
+fn test_repo_settings_charge_storage() {
+    let mut contract = setup_contract_with_repo();
+    let repo_id = "owner/repo".to_string();
+
+    assert_charges_storage(&mut contract, accounts(3), None, |contract| {
+        contract.set_repo_member(repo_id.clone(), accounts(4), MaintainerRole::Funder)
+    });
+    assert_charges_storage(&mut contract, accounts(3), None, |contract| {
+        contract.propose_maintainer(repo_id.clone(), accounts(5))
+    });
+    assert_charges_storage(&mut contract, accounts(3), None, |contract| {
+        contract.set_payout_threshold(repo_id.clone(), None, Some(U128(1)))
+    });
+    assert_charges_storage(&mut contract, accounts(3), None, |contract| {
+        contract.set_repo_payout_limit(
+            repo_id.clone(),
+            Some(RateLimit {
+                max_count: Some(1),
+                max_amount: None,
+            }),
+        )
+    });
+    assert_charges_storage(&mut contract, accounts(3), None, |contract| {
+        contract.set_pool_payout_amount(repo_id.clone(), Some(U128(1)))
+    });
+}
+
+// Test that granted roles and approved agent changes are paid for by the caller
+#[test]
+fn test_owner_settings_charge_storage() {
+    let mut contract = setup_contract_with_agent();
+
+    assert_charges_storage(&mut contract, accounts(0), None, |contract| {
+        contract.grant_role(Role::MeasurementAdmin, accounts(3))
+    });
+    assert_charges_storage(&mut contract, accounts(3), None, |contract| {
+        contract.approve_agent_change(accounts(2), non_default_measurements(), Ppid::default())
+    });
+}
+
+// Test that the sponsor refunds credited by cancelling a bounty are paid for by the admin
+#[test]
+fn test_cancel_bounty_charges_refund_storage() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = fund_issue_bounty(&mut contract, 7, DEPOSIT_ONE_NEAR);
+    sponsor_issue_bounty(&mut contract, accounts(4), DEPOSIT_ONE_NEAR);
+    assert_charges_storage(&mut contract, accounts(3), None, |contract| {
+        contract.cancel_bounty(bounty_id)
+    });
+}
+
+// Test that the funder refunds credited by expiring a bounty are paid for by the caller
+#[test]
+fn test_reclaim_expired_bounty_charges_refund_storage() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = fund_bounty_with_deadline(&mut contract);
+    assert_charges_storage(&mut contract, accounts(4), Some(10_000), |contract| {
+        contract.reclaim_expired_bounty(bounty_id)
+    });
+}
+
+// Test that the agent pays up front for the sponsor refunds a partial payout credits
+// once its transfer resolves
+#[test]
+fn test_partial_payout_charges_refund_storage() {
+    let mut contract = setup_contract_with_repo();
+    let bounty_id = sponsor_issue_bounty(&mut contract, accounts(4), DEPOSIT_ONE_NEAR);
+    let amount = DEPOSIT_ONE_NEAR.as_yoctonear() / 2;
+
+    let context = get_context_with_deposit(accounts(2), false, Some(DEPOSIT_STORAGE));
+    testing_env!(context.build());
+    let initial_storage_usage = contract.internal_storage_usage();
+    let _ = contract.release_bounty(
+        bounty_id,
+        Some(accounts(5)),
+        None,
+        U128(amount),
+        7,
+        MERGE_COMMIT_SHA.to_string(),
+    );
+    let record_bytes = contract.internal_storage_usage() - initial_storage_usage;
+    let refund_bytes = contract.internal_refund_storage_bytes(bounty_id, &None);
+    assert!(refund_bytes > 0);
+    let cost = env::storage_byte_cost().as_yoctonear() * u128::from(record_bytes + refund_bytes);
+    assert_eq!(
+        transferred_to(&accounts(2)),
+        DEPOSIT_STORAGE.as_yoctonear() - cost
+    );
+
+    // The refund credited by the callback uses exactly the storage that was charged
+    set_promise_result(PromiseResult::Successful(vec![]));
+    let initial_storage_usage = contract.internal_storage_usage();
+    assert!(contract.on_bounty_released(bounty_id, vec![(accounts(5), U128(amount))], 7));
+    assert_eq!(contract.get_refund(accounts(4), None).0, amount);
+    assert_eq!(
+        contract.internal_storage_usage() - initial_storage_usage,
+        refund_bytes
+    );
+}
+
+// Test that changes to the root state count towards the measured storage
+#[test]
+fn test_storage_usage_counts_root_state() {
+    let mut contract = setup_contract();
+    let initial_storage_usage = contract.internal_storage_usage();
+    contract.propose_owner(accounts(5));
+    let pending_owner_bytes = near_sdk::borsh::to_vec(&accounts(5)).unwrap().len() as u64;
+    assert_eq!(
+        contract.internal_storage_usage() - initial_storage_usage,
+        pending_owner_bytes
+    );
+
+    let initial_storage_usage = contract.internal_storage_usage();
+    contract.propose_upgrade(CodeHash::from([0u8; 32]));
+    let pending_upgrade_bytes = near_sdk::borsh::to_vec(contract.pending_upgrade.as_ref().unwrap())
+        .unwrap()
+        .len() as u64;
+    assert_eq!(
+        contract.internal_storage_usage() - initial_storage_usage,
+        pending_upgrade_bytes
+    );
+}
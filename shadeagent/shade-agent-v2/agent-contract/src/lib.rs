@@ -1,13 +1,14 @@
 use hex;
 use near_sdk::{
-    AccountId, BorshStorageKey, Gas, NearToken, PanicOnDefault, Promise,
+    AccountId, BorshStorageKey, Gas, NearToken, PanicOnDefault, Promise, PromiseError,
+    PromiseOrValue,
     env::{self, block_timestamp_ms},
-    ext_contract,
+    ext_contract, is_promise_success,
     json_types::{U64, U128},
     log, near, require,
     serde::Serialize,
     serde_json,
-    store::{IterableMap, IterableSet, LookupMap},
+    store::{IterableMap, IterableSet, LookupMap, LookupSet},
 };
 use shade_attestation::{
     attestation::DstackAttestation,
@@ -17,15 +18,26 @@ use shade_attestation::{
 };
 
 pub use internal::events::Event;
+pub use internal::ft::FtBountyMessage;
 pub use internal::helpers::AgentRemovalReason;
-pub use views::{AgentValidity, AgentView, ContractInfo};
+pub use migrate::STATE_VERSION;
+pub use views::{
+    AgentStorageDepositView, AgentValidity, AgentView, AllowanceView, BountyView,
+    ClaimableBalanceView, ContractInfo, ContributionView, GithubLinkView, MeasurementsView,
+    PayoutAllowanceView, PayoutShareView, PayoutView, PendingPayoutView, PendingUpgradeView,
+    StorageBalance,
+};
 
 mod internal;
+pub mod migrate;
 mod owner;
 pub mod views;
 mod your_functions;
 
 pub type Ppid = HexBytes<16>;
+pub type CodeHash = HexBytes<32>;
+pub type Signature = HexBytes<64>;
+pub type GithubUserId = u64;
 
 #[near(contract_state)]
 #[derive(PanicOnDefault)]
@@ -40,6 +52,54 @@ pub struct Contract {
     pub whitelisted_agents_for_local: IterableSet<AccountId>,
     pub bounties: LookupMap<String, u128>,
     pub repo_maintainers: LookupMap<String, AccountId>,
+    pub bounty_records: LookupMap<BountyId, Bounty>,
+    pub issue_bounties: LookupMap<(String, u64), BountyId>,
+    pub next_bounty_id: BountyId,
+    pub accepted_tokens: IterableSet<AccountId>,
+    pub ft_bounties: LookupMap<(String, AccountId), u128>,
+    pub repo_members: LookupMap<String, Vec<RepoMember>>,
+    pub pending_maintainers: LookupMap<String, AccountId>,
+    pub upgrade_delay_ms: u64,
+    pub pending_upgrade: Option<PendingUpgrade>,
+    pub pending_owner_id: Option<AccountId>,
+    pub role_members: LookupMap<Role, Vec<AccountId>>,
+    pub paused: PauseFlags,
+    pub payouts: LookupMap<(String, u64), Payout>,
+    pub claimable_balances: LookupMap<GithubUserId, Vec<ClaimableBalance>>,
+    pub claim_expiry_ms: u64,
+    pub github_accounts: LookupMap<GithubUserId, AccountId>,
+    pub github_user_ids: LookupMap<AccountId, GithubUserId>,
+    pub github_link_history: LookupMap<GithubUserId, Vec<GithubLink>>,
+    pub payout_thresholds: LookupMap<(String, Option<AccountId>), u128>,
+    pub pending_payouts: IterableMap<BountyId, PendingPayout>,
+    pub payout_approval_timeout_ms: u64,
+    pub payout_limits: PayoutLimits,
+    pub repo_payout_limits: LookupMap<String, RateLimit>,
+    // Payouts of the last day, per agent and per repo
+    pub agent_payout_usage: LookupMap<AccountId, Vec<PayoutUsage>>,
+    pub repo_payout_usage: LookupMap<String, Vec<PayoutUsage>>,
+    pub bounty_contributions: LookupMap<BountyId, Vec<Contribution>>,
+    // Total given to a repo pool by a sponsor, per token
+    pub pool_sponsorships: LookupMap<(String, Option<AccountId>, AccountId), u128>,
+    // Refunds of cancelled and expired bounties, withdrawn by their owner per token
+    pub refunds: LookupMap<(AccountId, Option<AccountId>), u128>,
+    pub third_party_funding_disabled: LookupSet<String>,
+    pub approved_agent_changes: LookupMap<AccountId, AgentChange>,
+    // Approved measurements without an entry are active
+    pub measurement_statuses: LookupMap<FullMeasurementsHex, MeasurementStatus>,
+    // Measurements approved before labels were recorded have no entry
+    pub measurement_infos: LookupMap<FullMeasurementsHex, MeasurementsInfo>,
+    // Agents registered before deposits were recorded have no entry and get no refund
+    pub agent_storage_deposits: LookupMap<AccountId, AgentStorageDeposit>,
+    // Part of a repo pool given by sponsors, per token
+    // It can only be spent on bounties, repo admins cannot withdraw it
+    pub sponsored_pool_balances: LookupMap<(String, Option<AccountId>), u128>,
+    // NEAR deposited with storage_deposit, pays for the storage of funding with fungible tokens
+    pub storage_balances: LookupMap<AccountId, u128>,
+    // Amount in yoctoNEAR an agent opens a pool bounty for, set by the repo admins
+    pub pool_payout_amounts: LookupMap<String, u128>,
+    // Layout version the state was written with, see migrate.rs
+    pub state_version: u32,
 }
 
 #[near(serializers = [borsh])]
@@ -49,6 +109,235 @@ pub struct Agent {
     pub valid_until_ms: u64,
 }
 
+// Storage deposit paid by an agent, returned when the agent is removed
+#[near(serializers = [borsh])]
+#[derive(Clone)]
+pub struct AgentStorageDeposit {
+    pub amount: u128,
+    // Account the deposit is returned to, the agent account when None
+    pub refund_to: Option<AccountId>,
+}
+
+// Measurements and PPID an agent is allowed to switch to when renewing its attestation
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone, PartialEq)]
+pub struct AgentChange {
+    pub measurements: FullMeasurementsHex,
+    pub ppid: Ppid,
+}
+
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeasurementStatus {
+    Active,
+    // Agents already registered stay valid until the deadline, new registrations are refused
+    Deprecated { until_ms: U64 },
+    // Agents registered with the measurements are invalid
+    Revoked,
+}
+
+// Which agent release a set of measurements belongs to
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeasurementsInfo {
+    // Human readable name of the release, e.g. "agent v1.4.2"
+    pub label: String,
+    // Docker image digest or git commit the measurements were built from
+    pub source: String,
+    pub approved_by: AccountId,
+    pub approved_at_ms: U64,
+}
+
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaintainerRole {
+    // Can fund, create, cancel and withdraw bounties and manage members
+    Admin,
+    // Can fund bounties
+    Funder,
+    // Listed for dashboards, no on-chain permissions
+    Viewer,
+}
+
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone)]
+pub struct RepoMember {
+    pub account_id: AccountId,
+    pub role: MaintainerRole,
+}
+
+// Admin roles granted by the owner, who can call every admin method
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    // Approves and removes measurements and PPIDs, sets the attestation expiration time
+    MeasurementAdmin,
+    // Removes agents and manages the local mode whitelist
+    AgentOperator,
+    // Stages and deploys contract upgrades
+    Upgrader,
+    // Pauses the contract in an emergency
+    PauseGuardian,
+    // Resolves repo disputes and manages accepted bounty tokens
+    BountyArbiter,
+}
+
+// Operations that can be paused in an emergency, maintainer withdrawals are never paused
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PauseFlags {
+    pub payouts: bool,
+    pub signatures: bool,
+    pub agent_registration: bool,
+    pub funding: bool,
+}
+
+// Limit on the payouts released in a rolling window, None means unlimited
+// Amounts are in yoctoNEAR, fungible token payouts are refused while an amount cap applies
+// and otherwise only count towards max_count
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RateLimit {
+    pub max_count: Option<u32>,
+    pub max_amount: Option<U128>,
+}
+
+// Payout limits set by the owner, a repo maintainer can override repo_daily for their repo
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PayoutLimits {
+    pub agent_hourly: RateLimit,
+    pub agent_daily: RateLimit,
+    pub repo_daily: RateLimit,
+}
+
+// A payout counted in the rolling windows, amount is 0 for fungible token payouts
+#[near(serializers = [borsh])]
+#[derive(Clone)]
+pub struct PayoutUsage {
+    pub at_ms: u64,
+    pub amount: u128,
+}
+
+// Contract code staged by the owner, deployable once executable_at_ms has passed
+#[near(serializers = [borsh])]
+#[derive(Clone)]
+pub struct PendingUpgrade {
+    pub code_hash: CodeHash,
+    pub proposed_at_ms: u64,
+    pub executable_at_ms: u64,
+}
+
+pub type BountyId = u64;
+
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BountyStatus {
+    Open,
+    Claimed,
+    Paid,
+    Cancelled,
+    // The deadline passed without a payout and the escrow was refunded to the funder
+    Expired,
+    // The payout is above the repo threshold and waits for a repo admin to approve it
+    PendingApproval,
+}
+
+// Escrow record for a single issue or PR of a repo
+#[near(serializers = [borsh])]
+#[derive(Clone)]
+pub struct Bounty {
+    pub repo_id: String,
+    pub number: u64,
+    // None for bounties paid in NEAR
+    pub token_id: Option<AccountId>,
+    // The escrow while the bounty is open, the amount paid out once it is paid
+    pub amount: u128,
+    pub status: BountyStatus,
+    pub creator: AccountId,
+    pub created_at_ms: u64,
+    pub updated_at_ms: u64,
+    // After this time anyone can refund the escrow to the creator
+    pub deadline_ms: Option<u64>,
+}
+
+// Contribution to the escrow of a bounty, used to split refunds pro-rata
+// A sponsor is a funder without a role in the repo at the time of funding
+// Sponsored pool funds moved by a repo admin are marked both from_pool and sponsor
+#[near(serializers = [borsh])]
+#[derive(Clone)]
+pub struct Contribution {
+    pub account_id: AccountId,
+    pub amount: u128,
+    // Moved from the repo pool by a repo admin, refunds of it go back to the pool
+    pub from_pool: bool,
+    pub sponsor: bool,
+}
+
+// Payout recorded per merged PR so a redelivered webhook or a retry cannot pay twice
+#[near(serializers = [borsh])]
+#[derive(Clone)]
+pub struct Payout {
+    pub bounty_id: BountyId,
+    pub merge_commit_sha: String,
+    // A single recipient unless the bounty was split, empty if held for a GitHub user
+    pub recipients: Vec<PayoutRecipient>,
+    pub github_user_id: Option<GithubUserId>,
+    pub token_id: Option<AccountId>,
+    pub amount: u128,
+    pub paid_at_ms: u64,
+    // The agent whose rate limits the payout counts towards
+    pub released_by: AccountId,
+}
+
+#[near(serializers = [borsh])]
+#[derive(Clone)]
+pub struct PayoutRecipient {
+    pub account_id: AccountId,
+    pub basis_points: u16,
+    pub amount: u128,
+}
+
+// Part of a split payout in basis points, all shares of a payout sum to 10000
+#[near(serializers = [json])]
+#[derive(Clone)]
+pub struct PayoutShare {
+    pub account_id: AccountId,
+    pub basis_points: u16,
+}
+
+// Payout held for a GitHub user who has not linked a NEAR account yet
+// Goes back to the repo pool if it is not claimed before expires_at_ms
+#[near(serializers = [borsh])]
+#[derive(Clone)]
+pub struct ClaimableBalance {
+    pub bounty_id: BountyId,
+    pub repo_id: String,
+    pub token_id: Option<AccountId>,
+    pub amount: u128,
+    pub expires_at_ms: u64,
+}
+
+// Payout above the repo threshold, the transfer only happens once a repo admin approves it
+// The Payout record is kept under (repo_id, pr_number) meanwhile
+#[near(serializers = [borsh])]
+#[derive(Clone)]
+pub struct PendingPayout {
+    pub pr_number: u64,
+    pub requested_at_ms: u64,
+    // After this time the payout can no longer be approved and the bounty is reopened
+    pub expires_at_ms: u64,
+}
+
+// Entry in the link history of a GitHub user, account_id is None when the link was removed
+#[near(serializers = [borsh])]
+#[derive(Debug, Clone)]
+pub struct GithubLink {
+    pub account_id: Option<AccountId>,
+    pub changed_by: AccountId,
+    pub changed_at_ms: u64,
+}
+
 #[derive(BorshStorageKey)]
 #[near]
 pub enum StorageKey {
@@ -58,9 +347,47 @@ pub enum StorageKey {
     WhitelistedAgentsForLocal,
     Bounties,
     RepoMaintainers,
+    BountyRecords,
+    IssueBounties,
+    AcceptedTokens,
+    FtBounties,
+    RepoMembers,
+    PendingMaintainers,
+    RoleMembers,
+    Payouts,
+    ClaimableBalances,
+    GithubAccounts,
+    GithubUserIds,
+    GithubLinkHistory,
+    PayoutThresholds,
+    PendingPayouts,
+    RepoPayoutLimits,
+    AgentPayoutUsage,
+    RepoPayoutUsage,
+    BountyContributions,
+    PoolSponsorships,
+    Refunds,
+    ThirdPartyFundingDisabled,
+    ApprovedAgentChanges,
+    MeasurementStatuses,
+    MeasurementInfos,
+    AgentStorageDeposits,
+    SponsoredPoolBalances,
+    StorageBalances,
+    PoolPayoutAmounts,
 }
 
-const STORAGE_BYTES_TO_REGISTER: u128 = 486;
+// Delay between staging and deploying an upgrade when none is given: 1 day
+const DEFAULT_UPGRADE_DELAY_MS: u64 = 24 * 60 * 60 * 1000;
+
+// Longest delay between staging and deploying an upgrade: 30 days
+pub(crate) const MAX_UPGRADE_DELAY_MS: u64 = 30 * 24 * 60 * 60 * 1000;
+
+// Time a GitHub user has to claim a held payout: 90 days
+const DEFAULT_CLAIM_EXPIRY_MS: u64 = 90 * 24 * 60 * 60 * 1000;
+
+// Time a repo admin has to approve a payout above the threshold: 7 days
+const DEFAULT_PAYOUT_APPROVAL_TIMEOUT_MS: u64 = 7 * 24 * 60 * 60 * 1000;
 
 #[near]
 impl Contract {
@@ -71,7 +398,13 @@ impl Contract {
         attestation_expiration_time_ms: U64,
         owner_id: AccountId,
         mpc_contract_id: AccountId,
+        upgrade_delay_ms: Option<U64>,
     ) -> Self {
+        let upgrade_delay_ms = upgrade_delay_ms.map_or(DEFAULT_UPGRADE_DELAY_MS, Into::into);
+        require!(
+            upgrade_delay_ms <= MAX_UPGRADE_DELAY_MS,
+            "Upgrade delay cannot be more than 30 days"
+        );
         Self {
             requires_tee,
             attestation_expiration_time_ms: attestation_expiration_time_ms.into(),
@@ -83,25 +416,56 @@ impl Contract {
             whitelisted_agents_for_local: IterableSet::new(StorageKey::WhitelistedAgentsForLocal),
             bounties: LookupMap::new(StorageKey::Bounties),
             repo_maintainers: LookupMap::new(StorageKey::RepoMaintainers),
+            bounty_records: LookupMap::new(StorageKey::BountyRecords),
+            issue_bounties: LookupMap::new(StorageKey::IssueBounties),
+            next_bounty_id: 0,
+            accepted_tokens: IterableSet::new(StorageKey::AcceptedTokens),
+            ft_bounties: LookupMap::new(StorageKey::FtBounties),
+            repo_members: LookupMap::new(StorageKey::RepoMembers),
+            pending_maintainers: LookupMap::new(StorageKey::PendingMaintainers),
+            upgrade_delay_ms,
+            pending_upgrade: None,
+            pending_owner_id: None,
+            role_members: LookupMap::new(StorageKey::RoleMembers),
+            paused: PauseFlags::default(),
+            payouts: LookupMap::new(StorageKey::Payouts),
+            claimable_balances: LookupMap::new(StorageKey::ClaimableBalances),
+            claim_expiry_ms: DEFAULT_CLAIM_EXPIRY_MS,
+            github_accounts: LookupMap::new(StorageKey::GithubAccounts),
+            github_user_ids: LookupMap::new(StorageKey::GithubUserIds),
+            github_link_history: LookupMap::new(StorageKey::GithubLinkHistory),
+            payout_thresholds: LookupMap::new(StorageKey::PayoutThresholds),
+            pending_payouts: IterableMap::new(StorageKey::PendingPayouts),
+            payout_approval_timeout_ms: DEFAULT_PAYOUT_APPROVAL_TIMEOUT_MS,
+            payout_limits: PayoutLimits::default(),
+            repo_payout_limits: LookupMap::new(StorageKey::RepoPayoutLimits),
+            agent_payout_usage: LookupMap::new(StorageKey::AgentPayoutUsage),
+            repo_payout_usage: LookupMap::new(StorageKey::RepoPayoutUsage),
+            bounty_contributions: LookupMap::new(StorageKey::BountyContributions),
+            pool_sponsorships: LookupMap::new(StorageKey::PoolSponsorships),
+            refunds: LookupMap::new(StorageKey::Refunds),
+            third_party_funding_disabled: LookupSet::new(StorageKey::ThirdPartyFundingDisabled),
+            approved_agent_changes: LookupMap::new(StorageKey::ApprovedAgentChanges),
+            measurement_statuses: LookupMap::new(StorageKey::MeasurementStatuses),
+            measurement_infos: LookupMap::new(StorageKey::MeasurementInfos),
+            agent_storage_deposits: LookupMap::new(StorageKey::AgentStorageDeposits),
+            sponsored_pool_balances: LookupMap::new(StorageKey::SponsoredPoolBalances),
+            storage_balances: LookupMap::new(StorageKey::StorageBalances),
+            pool_payout_amounts: LookupMap::new(StorageKey::PoolPayoutAmounts),
+            state_version: STATE_VERSION,
         }
     }
 
     // Register an agent, this needs to be called by the agent itself
     #[payable]
     pub fn register_agent(&mut self, attestation: DstackAttestation) -> bool {
-        // Require the agent to pay for the storage cost
-        // You should update the STORAGE_BYTES_TO_REGISTER const if you store more data
-        let storage_cost = env::storage_byte_cost()
-            .checked_mul(STORAGE_BYTES_TO_REGISTER)
-            .unwrap();
         require!(
-            env::attached_deposit() >= storage_cost,
-            &format!(
-                "Attached deposit must be greater than storage cost {:?}",
-                storage_cost.exact_amount_display()
-            )
+            !self.paused.agent_registration,
+            "Agent registration is paused"
         );
 
+        let initial_storage_usage = self.internal_storage_usage();
+
         // Verify the attestation and get the measurements and PPID for the agent
         let (measurements, ppid) = self.verify_attestation(attestation.clone());
 
@@ -126,6 +490,91 @@ impl Contract {
             },
         );
 
+        // Require the agent to pay for the storage it uses, the excess is refunded
+        // Registering again only charges for storage the agent did not pay for yet
+        self.internal_charge_agent_storage(&env::predecessor_account_id(), initial_storage_usage);
+
+        true
+    }
+
+    // Extend the validity of a registered agent with a fresh attestation
+    // The storage deposit paid at registration is kept, so no deposit is needed
+    // The measurements and PPID must stay the same unless the change was approved with
+    // approve_agent_change
+    pub fn renew_attestation(&mut self, attestation: DstackAttestation) -> bool {
+        require!(
+            !self.paused.agent_registration,
+            "Agent registration is paused"
+        );
+        let account_id = env::predecessor_account_id();
+        let old_valid_until_ms = self
+            .agents
+            .get(&account_id)
+            .expect("Agent not registered")
+            .valid_until_ms;
+
+        // Verify the attestation and get the measurements and PPID for the agent
+        let (measurements, ppid) = self.verify_attestation(attestation);
+        let agent = self.agents.get(&account_id).unwrap();
+        if agent.measurements != measurements || agent.ppid != ppid {
+            let change = AgentChange {
+                measurements: measurements.clone(),
+                ppid: ppid.clone(),
+            };
+            require!(
+                self.approved_agent_changes.get(&account_id) == Some(&change),
+                "Measurements or PPID changed without approval"
+            );
+            self.approved_agent_changes.remove(&account_id);
+        }
+
+        let valid_until_ms = block_timestamp_ms() + self.attestation_expiration_time_ms;
+        Event::AgentRenewed {
+            account_id: &account_id,
+            measurements: &measurements,
+            ppid: &ppid,
+            old_valid_until_ms: U64::from(old_valid_until_ms),
+            valid_until_ms: U64::from(valid_until_ms),
+        }
+        .emit();
+
+        self.agents.insert(
+            account_id,
+            Agent {
+                measurements,
+                ppid,
+                valid_until_ms,
+            },
+        );
         true
     }
+
+    // Set the account the storage deposit is returned to when the agent is removed
+    // None returns it to the agent account, the agent pays for the storage the account id uses
+    #[payable]
+    pub fn set_storage_refund_account(&mut self, refund_to: Option<AccountId>) {
+        let initial_storage_usage = self.internal_storage_usage();
+        let account_id = env::predecessor_account_id();
+        require!(
+            self.agents.contains_key(&account_id),
+            "Agent not registered"
+        );
+        let mut deposit = self
+            .agent_storage_deposits
+            .get(&account_id)
+            .cloned()
+            .unwrap_or(AgentStorageDeposit {
+                amount: 0,
+                refund_to: None,
+            });
+        Event::StorageRefundAccountUpdated {
+            account_id: &account_id,
+            refund_to: refund_to.as_ref(),
+        }
+        .emit();
+        deposit.refund_to = refund_to;
+        self.agent_storage_deposits
+            .insert(account_id.clone(), deposit);
+        self.internal_charge_agent_storage(&account_id, initial_storage_usage);
+    }
 }
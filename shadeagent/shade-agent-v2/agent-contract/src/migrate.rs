@@ -0,0 +1,133 @@
+use crate::*;
+use near_sdk::borsh::BorshDeserialize;
+
+// Layout version of the current contract state, stored in Contract::state_version
+// Bump it whenever the fields of Contract or of a value it stores change
+pub const STATE_VERSION: u32 = 5;
+
+pub(crate) const STATE_KEY: &[u8] = b"STATE";
+
+// State layout of the original template, before bounties were added
+#[near(serializers = [borsh])]
+pub struct ContractV0 {
+    pub requires_tee: bool,
+    pub attestation_expiration_time_ms: u64,
+    pub owner_id: AccountId,
+    pub mpc_contract_id: AccountId,
+    pub approved_measurements: IterableSet<FullMeasurementsHex>,
+    pub approved_ppids: IterableSet<Ppid>,
+    pub agents: IterableMap<AccountId, Agent>,
+    pub whitelisted_agents_for_local: IterableSet<AccountId>,
+}
+
+// State layout with a single bounty pool and maintainer per repo
+#[near(serializers = [borsh])]
+pub struct ContractV1 {
+    pub requires_tee: bool,
+    pub attestation_expiration_time_ms: u64,
+    pub owner_id: AccountId,
+    pub mpc_contract_id: AccountId,
+    pub approved_measurements: IterableSet<FullMeasurementsHex>,
+    pub approved_ppids: IterableSet<Ppid>,
+    pub agents: IterableMap<AccountId, Agent>,
+    pub whitelisted_agents_for_local: IterableSet<AccountId>,
+    pub bounties: LookupMap<String, u128>,
+    pub repo_maintainers: LookupMap<String, AccountId>,
+}
+
+impl From<ContractV0> for ContractV1 {
+    fn from(old: ContractV0) -> Self {
+        Self {
+            requires_tee: old.requires_tee,
+            attestation_expiration_time_ms: old.attestation_expiration_time_ms,
+            owner_id: old.owner_id,
+            mpc_contract_id: old.mpc_contract_id,
+            approved_measurements: old.approved_measurements,
+            approved_ppids: old.approved_ppids,
+            agents: old.agents,
+            whitelisted_agents_for_local: old.whitelisted_agents_for_local,
+            bounties: LookupMap::new(StorageKey::Bounties),
+            repo_maintainers: LookupMap::new(StorageKey::RepoMaintainers),
+        }
+    }
+}
+
+impl From<ContractV1> for Contract {
+    fn from(old: ContractV1) -> Self {
+        Self {
+            requires_tee: old.requires_tee,
+            attestation_expiration_time_ms: old.attestation_expiration_time_ms,
+            owner_id: old.owner_id,
+            mpc_contract_id: old.mpc_contract_id,
+            approved_measurements: old.approved_measurements,
+            approved_ppids: old.approved_ppids,
+            agents: old.agents,
+            whitelisted_agents_for_local: old.whitelisted_agents_for_local,
+            bounties: old.bounties,
+            repo_maintainers: old.repo_maintainers,
+            bounty_records: LookupMap::new(StorageKey::BountyRecords),
+            issue_bounties: LookupMap::new(StorageKey::IssueBounties),
+            next_bounty_id: 0,
+            accepted_tokens: IterableSet::new(StorageKey::AcceptedTokens),
+            ft_bounties: LookupMap::new(StorageKey::FtBounties),
+            repo_members: LookupMap::new(StorageKey::RepoMembers),
+            pending_maintainers: LookupMap::new(StorageKey::PendingMaintainers),
+            upgrade_delay_ms: DEFAULT_UPGRADE_DELAY_MS,
+            pending_upgrade: None,
+            pending_owner_id: None,
+            role_members: LookupMap::new(StorageKey::RoleMembers),
+            paused: PauseFlags::default(),
+            payouts: LookupMap::new(StorageKey::Payouts),
+            claimable_balances: LookupMap::new(StorageKey::ClaimableBalances),
+            claim_expiry_ms: DEFAULT_CLAIM_EXPIRY_MS,
+            github_accounts: LookupMap::new(StorageKey::GithubAccounts),
+            github_user_ids: LookupMap::new(StorageKey::GithubUserIds),
+            github_link_history: LookupMap::new(StorageKey::GithubLinkHistory),
+            payout_thresholds: LookupMap::new(StorageKey::PayoutThresholds),
+            pending_payouts: IterableMap::new(StorageKey::PendingPayouts),
+            payout_approval_timeout_ms: DEFAULT_PAYOUT_APPROVAL_TIMEOUT_MS,
+            payout_limits: PayoutLimits::default(),
+            repo_payout_limits: LookupMap::new(StorageKey::RepoPayoutLimits),
+            agent_payout_usage: LookupMap::new(StorageKey::AgentPayoutUsage),
+            repo_payout_usage: LookupMap::new(StorageKey::RepoPayoutUsage),
+            bounty_contributions: LookupMap::new(StorageKey::BountyContributions),
+            pool_sponsorships: LookupMap::new(StorageKey::PoolSponsorships),
+            refunds: LookupMap::new(StorageKey::Refunds),
+            third_party_funding_disabled: LookupSet::new(StorageKey::ThirdPartyFundingDisabled),
+            approved_agent_changes: LookupMap::new(StorageKey::ApprovedAgentChanges),
+            measurement_statuses: LookupMap::new(StorageKey::MeasurementStatuses),
+            measurement_infos: LookupMap::new(StorageKey::MeasurementInfos),
+            agent_storage_deposits: LookupMap::new(StorageKey::AgentStorageDeposits),
+            sponsored_pool_balances: LookupMap::new(StorageKey::SponsoredPoolBalances),
+            storage_balances: LookupMap::new(StorageKey::StorageBalances),
+            pool_payout_amounts: LookupMap::new(StorageKey::PoolPayoutAmounts),
+            state_version: STATE_VERSION,
+        }
+    }
+}
+
+#[near]
+impl Contract {
+    // Called by update_contract after the new code is deployed
+    // Upgrades the state from the baseline layout or the original template, the current
+    // layout is kept as is
+    // Borsh requires all bytes to be consumed, so only the matching layout deserializes
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let state = env::storage_read(STATE_KEY).expect("Contract state not found");
+
+        if let Ok(contract) = Contract::try_from_slice(&state) {
+            require!(
+                contract.state_version == STATE_VERSION,
+                "Unknown contract state version"
+            );
+            return contract;
+        }
+        if let Ok(old) = ContractV1::try_from_slice(&state) {
+            return old.into();
+        }
+        let old = ContractV0::try_from_slice(&state).expect("Unknown contract state layout");
+        ContractV1::from(old).into()
+    }
+}
@@ -4,56 +4,283 @@ use crate::*;
 impl Contract {
     // Update the attestation expiration time
     pub fn update_attestation_expiration_time(&mut self, attestation_expiration_time_ms: U64) {
-        self.require_owner();
+        self.require_role(Role::MeasurementAdmin);
+        Event::AttestationExpirationTimeUpdated {
+            old_attestation_expiration_time_ms: U64::from(self.attestation_expiration_time_ms),
+            new_attestation_expiration_time_ms: attestation_expiration_time_ms,
+        }
+        .emit();
         self.attestation_expiration_time_ms = attestation_expiration_time_ms.into();
     }
 
-    // Update the owner account ID
-    pub fn update_owner_id(&mut self, owner_id: AccountId) {
+    // Propose a new owner, the ownership is transferred once they accept
+    pub fn propose_owner(&mut self, owner_id: AccountId) {
         self.require_owner();
-        self.owner_id = owner_id;
+        Event::OwnerProposed {
+            owner_id: &self.owner_id,
+            pending_owner_id: &owner_id,
+        }
+        .emit();
+        self.pending_owner_id = Some(owner_id);
+    }
+
+    // Accept the ownership, needs to be called by the pending owner
+    pub fn accept_ownership(&mut self) {
+        let new_owner_id = env::predecessor_account_id();
+        require!(
+            self.pending_owner_id.as_ref() == Some(&new_owner_id),
+            "Caller is not the pending owner"
+        );
+        Event::OwnershipTransferred {
+            old_owner_id: &self.owner_id,
+            new_owner_id: &new_owner_id,
+        }
+        .emit();
+        self.pending_owner_id = None;
+        self.owner_id = new_owner_id;
+    }
+
+    // Cancel the pending ownership transfer
+    pub fn cancel_owner_proposal(&mut self) {
+        self.require_owner();
+        let pending_owner_id = self.pending_owner_id.take().expect("No pending owner");
+        Event::OwnerProposalCancelled {
+            pending_owner_id: &pending_owner_id,
+        }
+        .emit();
     }
 
     // Update the MPC contract ID
     pub fn update_mpc_contract_id(&mut self, mpc_contract_id: AccountId) {
         self.require_owner();
+        Event::MpcContractIdUpdated {
+            old_mpc_contract_id: &self.mpc_contract_id,
+            new_mpc_contract_id: &mpc_contract_id,
+        }
+        .emit();
         self.mpc_contract_id = mpc_contract_id;
     }
 
-    // Add a new set of measurements to the approved list
-    pub fn approve_measurements(&mut self, measurements: FullMeasurementsHex) {
-        self.require_owner();
-        self.approved_measurements.insert(measurements);
+    // Add a new set of measurements to the approved list, or make deprecated or revoked ones active again
+    // The label and source identify the agent release the measurements belong to
+    pub fn approve_measurements(
+        &mut self,
+        measurements: FullMeasurementsHex,
+        label: String,
+        source: String,
+    ) {
+        self.require_role(Role::MeasurementAdmin);
+        require!(!label.is_empty(), "Label cannot be empty");
+        require!(!source.is_empty(), "Source cannot be empty");
+        // Approving measurements that are already active only updates their label and source
+        let reactivated = self.measurement_statuses.remove(&measurements).is_some();
+        if !self.approved_measurements.contains(&measurements) || reactivated {
+            Event::MeasurementsApproved {
+                measurements: &measurements,
+                label: &label,
+                source: &source,
+            }
+            .emit();
+            self.approved_measurements.insert(measurements.clone());
+        } else {
+            let old_info = self.measurement_infos.get(&measurements);
+            Event::MeasurementsInfoUpdated {
+                measurements: &measurements,
+                old_label: old_info.map(|info| &info.label),
+                old_source: old_info.map(|info| &info.source),
+                label: &label,
+                source: &source,
+            }
+            .emit();
+        }
+        self.measurement_infos.insert(
+            measurements,
+            MeasurementsInfo {
+                label,
+                source,
+                approved_by: env::predecessor_account_id(),
+                approved_at_ms: U64::from(block_timestamp_ms()),
+            },
+        );
     }
 
     // Remove a set of measurements from the approved list
     pub fn remove_measurements(&mut self, measurements: FullMeasurementsHex) {
-        self.require_owner();
+        self.require_role(Role::MeasurementAdmin);
         require!(
             self.approved_measurements.remove(&measurements),
             "Measurements not in approved list"
         );
+        self.measurement_statuses.remove(&measurements);
+        self.measurement_infos.remove(&measurements);
+        Event::MeasurementsRemoved {
+            measurements: &measurements,
+        }
+        .emit();
+    }
+
+    // Refuse new registrations with a set of measurements, agents already registered stay valid until the deadline
+    pub fn deprecate_measurements(&mut self, measurements: FullMeasurementsHex, until_ms: U64) {
+        self.require_role(Role::MeasurementAdmin);
+        require!(
+            self.measurement_status(&measurements) == Some(MeasurementStatus::Active),
+            "Only active measurements can be deprecated"
+        );
+        require!(
+            until_ms.0 > block_timestamp_ms(),
+            "Deprecation deadline must be in the future"
+        );
+        Event::MeasurementsDeprecated {
+            measurements: &measurements,
+            until_ms,
+        }
+        .emit();
+        self.measurement_statuses
+            .insert(measurements, MeasurementStatus::Deprecated { until_ms });
+    }
+
+    // Invalidate agents registered with a set of measurements, the measurements stay listed as revoked
+    pub fn revoke_measurements(&mut self, measurements: FullMeasurementsHex) {
+        self.require_role(Role::MeasurementAdmin);
+        match self.measurement_status(&measurements) {
+            None => panic!("Measurements not in approved list"),
+            Some(MeasurementStatus::Revoked) => panic!("Measurements are already revoked"),
+            Some(_) => {}
+        }
+        Event::MeasurementsRevoked {
+            measurements: &measurements,
+        }
+        .emit();
+        self.measurement_statuses
+            .insert(measurements, MeasurementStatus::Revoked);
     }
 
     // Add an array of PPIDs to the approved list
     pub fn approve_ppids(&mut self, ppids: Vec<Ppid>) {
-        self.require_owner();
+        self.require_role(Role::MeasurementAdmin);
+        let mut added = Vec::new();
         for id in ppids {
-            self.approved_ppids.insert(id);
+            if self.approved_ppids.insert(id.clone()) {
+                added.push(id);
+            }
+        }
+        if !added.is_empty() {
+            Event::PpidsApproved { ppids: &added }.emit();
         }
     }
 
     // Remove an array of PPIDs from the approved list.
     pub fn remove_ppids(&mut self, ppids: Vec<Ppid>) {
-        self.require_owner();
-        for id in ppids {
-            require!(self.approved_ppids.remove(&id), "PPID not in approved list");
+        self.require_role(Role::MeasurementAdmin);
+        for id in &ppids {
+            require!(self.approved_ppids.remove(id), "PPID not in approved list");
+        }
+        Event::PpidsRemoved { ppids: &ppids }.emit();
+    }
+
+    // Register a repo or replace its maintainer, used to resolve ownership disputes
+    pub fn set_repo_maintainer(&mut self, repo_id: String, maintainer_id: AccountId) {
+        self.require_role(Role::BountyArbiter);
+        self.pending_maintainers.remove(&repo_id);
+        Event::RepoMaintainerChanged {
+            repo_id: &repo_id,
+            old_maintainer_id: self.repo_maintainers.get(&repo_id),
+            new_maintainer_id: &maintainer_id,
+        }
+        .emit();
+        self.repo_maintainers.insert(repo_id, maintainer_id);
+    }
+
+    // Accept a fungible token contract for funding bounties
+    pub fn add_accepted_token(&mut self, token_id: AccountId) {
+        self.require_role(Role::BountyArbiter);
+        if self.accepted_tokens.insert(token_id.clone()) {
+            Event::AcceptedTokenAdded {
+                token_id: &token_id,
+            }
+            .emit();
+        }
+    }
+
+    // Stop accepting a fungible token contract, existing balances can still be paid out
+    pub fn remove_accepted_token(&mut self, token_id: AccountId) {
+        self.require_role(Role::BountyArbiter);
+        require!(
+            self.accepted_tokens.remove(&token_id),
+            "Token not in accepted list"
+        );
+        Event::AcceptedTokenRemoved {
+            token_id: &token_id,
+        }
+        .emit();
+    }
+
+    // Update how long GitHub users have to claim held payouts, applies to new payouts only
+    pub fn update_claim_expiry(&mut self, claim_expiry_ms: U64) {
+        self.require_role(Role::BountyArbiter);
+        Event::ClaimExpiryUpdated {
+            old_claim_expiry_ms: U64::from(self.claim_expiry_ms),
+            new_claim_expiry_ms: claim_expiry_ms,
+        }
+        .emit();
+        self.claim_expiry_ms = claim_expiry_ms.into();
+    }
+
+    // Update how long repo admins have to approve payouts above their threshold
+    // Applies to new pending payouts only
+    pub fn update_payout_approval_timeout(&mut self, payout_approval_timeout_ms: U64) {
+        self.require_role(Role::BountyArbiter);
+        Event::PayoutApprovalTimeoutUpdated {
+            old_payout_approval_timeout_ms: U64::from(self.payout_approval_timeout_ms),
+            new_payout_approval_timeout_ms: payout_approval_timeout_ms,
+        }
+        .emit();
+        self.payout_approval_timeout_ms = payout_approval_timeout_ms.into();
+    }
+
+    // Set the rolling-window payout limits, repo maintainers can override the repo daily limit
+    pub fn set_payout_limits(&mut self, payout_limits: PayoutLimits) {
+        self.require_role(Role::BountyArbiter);
+        Event::PayoutLimitsUpdated {
+            old_limits: &self.payout_limits,
+            new_limits: &payout_limits,
+        }
+        .emit();
+        self.payout_limits = payout_limits;
+    }
+
+    // Allow a registered agent to renew its attestation with different measurements or PPID
+    // The approval is used up by the renewal
+    // The caller pays for the storage the approval uses, the excess deposit is refunded
+    #[payable]
+    pub fn approve_agent_change(
+        &mut self,
+        account_id: AccountId,
+        measurements: FullMeasurementsHex,
+        ppid: Ppid,
+    ) {
+        self.require_role(Role::MeasurementAdmin);
+        require!(
+            self.agents.contains_key(&account_id),
+            "Agent not registered"
+        );
+        let initial_storage_usage = self.internal_storage_usage();
+        let change = AgentChange { measurements, ppid };
+        Event::AgentChangeApproved {
+            account_id: &account_id,
+            change: &change,
         }
+        .emit();
+        self.approved_agent_changes.insert(account_id, change);
+        self.internal_charge_storage(
+            initial_storage_usage,
+            env::attached_deposit().as_yoctonear(),
+        );
     }
 
     // Remove an agent from the registered list
     pub fn remove_agent(&mut self, account_id: AccountId) {
-        self.require_owner();
+        self.require_role(Role::AgentOperator);
         require!(
             self.agents.remove(&account_id).is_some(),
             "Agent not registered"
@@ -63,13 +290,69 @@ impl Contract {
             reasons: vec![AgentRemovalReason::ManualRemoval],
         }
         .emit();
+        self.internal_refund_agent_storage(&account_id);
+    }
+
+    // Stage the sha256 hash of the code for the next upgrade
+    // update_contract can deploy it once the upgrade delay has passed
+    pub fn propose_upgrade(&mut self, code_hash: CodeHash) {
+        self.require_role(Role::Upgrader);
+        let proposed_at_ms = block_timestamp_ms();
+        let executable_at_ms = proposed_at_ms
+            .checked_add(self.upgrade_delay_ms)
+            .expect("Upgrade delay overflows the block timestamp");
+
+        Event::UpgradeProposed {
+            code_hash: &code_hash,
+            executable_at_ms: U64::from(executable_at_ms),
+        }
+        .emit();
+
+        self.pending_upgrade = Some(PendingUpgrade {
+            code_hash,
+            proposed_at_ms,
+            executable_at_ms,
+        });
+    }
+
+    // Cancel the staged upgrade
+    pub fn cancel_upgrade(&mut self) {
+        self.require_role(Role::Upgrader);
+        let pending_upgrade = self.pending_upgrade.take().expect("No pending upgrade");
+        Event::UpgradeCancelled {
+            code_hash: &pending_upgrade.code_hash,
+        }
+        .emit();
+    }
+
+    // Change the delay between staging and deploying an upgrade, up to MAX_UPGRADE_DELAY_MS
+    // Upgraders can only increase it, otherwise it could be skipped right before an upgrade
+    // The owner can also lower it, an upgrade that is already staged keeps its original time
+    pub fn update_upgrade_delay(&mut self, upgrade_delay_ms: U64) {
+        self.require_role(Role::Upgrader);
+        require!(
+            upgrade_delay_ms.0 <= MAX_UPGRADE_DELAY_MS,
+            "Upgrade delay cannot be more than 30 days"
+        );
+        require!(
+            upgrade_delay_ms.0 >= self.upgrade_delay_ms
+                || env::predecessor_account_id() == self.owner_id,
+            "Only the owner can lower the upgrade delay"
+        );
+        Event::UpgradeDelayUpdated {
+            old_upgrade_delay_ms: U64::from(self.upgrade_delay_ms),
+            new_upgrade_delay_ms: upgrade_delay_ms,
+        }
+        .emit();
+        self.upgrade_delay_ms = upgrade_delay_ms.into();
     }
 
     // Function to update the contract code
+    // The code must match the staged upgrade and its delay must have passed
     // Input format: [gas_bytes (8 bytes u64 little-endian)] + [wasm_code_bytes...]
     // See tests/update_contract_tests.rs for an example of how to call this function
     pub fn update_contract(&mut self) -> Promise {
-        self.require_owner();
+        self.require_role(Role::Upgrader);
 
         let input = env::input().expect("Error: No input").to_vec();
 
@@ -87,6 +370,21 @@ impl Contract {
 
         require!(!code.is_empty(), "WASM code cannot be empty");
 
+        let pending_upgrade = self.pending_upgrade.take().expect("No pending upgrade");
+        require!(
+            block_timestamp_ms() >= pending_upgrade.executable_at_ms,
+            "Upgrade delay has not passed"
+        );
+        require!(
+            env::sha256_array(&code) == *pending_upgrade.code_hash,
+            "Code hash does not match the pending upgrade"
+        );
+        Event::ContractUpdated {
+            code_hash: &pending_upgrade.code_hash,
+            old_state_version: self.state_version,
+        }
+        .emit();
+
         Promise::new(env::current_account_id())
             .deploy_contract(code)
             .function_call(
@@ -98,6 +396,76 @@ impl Contract {
             .as_return()
     }
 
+    // Pause the given operations, the owner or a PauseGuardian can call this
+    pub fn pause(&mut self, flags: PauseFlags) {
+        self.require_role(Role::PauseGuardian);
+        self.paused.payouts |= flags.payouts;
+        self.paused.signatures |= flags.signatures;
+        self.paused.agent_registration |= flags.agent_registration;
+        self.paused.funding |= flags.funding;
+        Event::PauseUpdated {
+            paused: &self.paused,
+        }
+        .emit();
+    }
+
+    // Resume the given operations, only the owner can call this
+    pub fn unpause(&mut self, flags: PauseFlags) {
+        self.require_owner();
+        self.paused.payouts &= !flags.payouts;
+        self.paused.signatures &= !flags.signatures;
+        self.paused.agent_registration &= !flags.agent_registration;
+        self.paused.funding &= !flags.funding;
+        Event::PauseUpdated {
+            paused: &self.paused,
+        }
+        .emit();
+    }
+
+    // Grant a role to an account, only the owner can manage roles
+    // The owner pays for the storage the role uses, the excess deposit is refunded
+    #[payable]
+    pub fn grant_role(&mut self, role: Role, account_id: AccountId) {
+        self.require_owner();
+        let initial_storage_usage = self.internal_storage_usage();
+        let mut members = self.role_members.get(&role).cloned().unwrap_or_default();
+        require!(
+            !members.contains(&account_id),
+            "Account already has the role"
+        );
+        members.push(account_id.clone());
+        self.role_members.insert(role, members);
+
+        Event::RoleGranted {
+            role,
+            account_id: &account_id,
+        }
+        .emit();
+        self.internal_charge_storage(
+            initial_storage_usage,
+            env::attached_deposit().as_yoctonear(),
+        );
+    }
+
+    // Revoke a role from an account
+    pub fn revoke_role(&mut self, role: Role, account_id: AccountId) {
+        self.require_owner();
+        let mut members = self.role_members.get(&role).cloned().unwrap_or_default();
+        let count_before = members.len();
+        members.retain(|member| member != &account_id);
+        require!(
+            members.len() < count_before,
+            "Account does not have the role"
+        );
+        self.role_members.insert(role, members);
+
+        Event::RoleRevoked {
+            role,
+            account_id: &account_id,
+        }
+        .emit();
+    }
+
     // Local only functions
 
     // Whitelist an agent, it will still need to register afterwards
@@ -105,9 +473,14 @@ impl Contract {
         if self.requires_tee {
             panic!("Whitelisting agents is not supported for TEE");
         }
-        self.require_owner();
+        self.require_role(Role::AgentOperator);
         // Only insert if not already whitelisted
-        self.whitelisted_agents_for_local.insert(account_id);
+        if self.whitelisted_agents_for_local.insert(account_id.clone()) {
+            Event::AgentWhitelistedForLocal {
+                account_id: &account_id,
+            }
+            .emit();
+        }
     }
 
     // Remove an agent from the list of whitelisted agents
@@ -115,10 +488,14 @@ impl Contract {
         if self.requires_tee {
             panic!("Removing agents is not supported for TEE");
         }
-        self.require_owner();
+        self.require_role(Role::AgentOperator);
         require!(
             self.whitelisted_agents_for_local.remove(&account_id),
             "Agent not in whitelist for local"
         );
+        Event::AgentRemovedFromWhitelistForLocal {
+            account_id: &account_id,
+        }
+        .emit();
     }
 }
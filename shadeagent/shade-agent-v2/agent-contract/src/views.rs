@@ -2,10 +2,24 @@ use crate::*;
 
 #[near(serializers = [json])]
 pub struct ContractInfo {
+    pub state_version: u32,
     pub requires_tee: bool,
     pub attestation_expiration_time_ms: U64,
     pub owner_id: AccountId,
+    pub pending_owner_id: Option<AccountId>,
     pub mpc_contract_id: AccountId,
+    pub upgrade_delay_ms: U64,
+    pub paused: PauseFlags,
+    pub claim_expiry_ms: U64,
+    pub payout_approval_timeout_ms: U64,
+    pub payout_limits: PayoutLimits,
+}
+
+#[near(serializers = [json])]
+pub struct PendingUpgradeView {
+    pub code_hash: CodeHash,
+    pub proposed_at_ms: U64,
+    pub executable_at_ms: U64,
 }
 
 #[near(serializers = [json])]
@@ -14,13 +28,191 @@ pub enum AgentValidity {
     Invalid(Vec<AgentRemovalReason>),
 }
 
+#[near(serializers = [json])]
+pub struct MeasurementsView {
+    pub measurements: FullMeasurementsHex,
+    pub status: MeasurementStatus,
+    pub info: Option<MeasurementsInfo>,
+}
+
 #[near(serializers = [json])]
 pub struct AgentView {
     pub account_id: AccountId,
     pub measurements: FullMeasurementsHex,
+    // Label of the release the measurements belong to
+    pub measurements_label: Option<String>,
     pub ppid: Ppid,
     pub valid_until_ms: U64,
     pub validity: AgentValidity,
+    // Returned when the agent is removed
+    pub storage_deposit: U128,
+}
+
+// Storage balance of a funder, as in NEP-145
+// There is no registration, so the whole balance is available
+#[near(serializers = [json])]
+pub struct StorageBalance {
+    pub total: U128,
+    pub available: U128,
+}
+
+// Storage deposit recorded for an agent, like storage_balance_of in NEP-145
+#[near(serializers = [json])]
+pub struct AgentStorageDepositView {
+    pub amount: U128,
+    // Account the deposit is returned to when the agent is removed
+    pub refund_to: AccountId,
+}
+
+#[near(serializers = [json])]
+pub struct BountyView {
+    pub bounty_id: BountyId,
+    pub repo_id: String,
+    pub number: u64,
+    pub token_id: Option<AccountId>,
+    pub amount: U128,
+    pub status: BountyStatus,
+    pub creator: AccountId,
+    pub created_at_ms: U64,
+    pub updated_at_ms: U64,
+    pub deadline_ms: Option<U64>,
+    // Zero once the deadline has passed
+    pub time_remaining_ms: Option<U64>,
+}
+
+impl BountyView {
+    fn new(bounty_id: BountyId, bounty: &Bounty) -> Self {
+        let now = block_timestamp_ms();
+        Self {
+            bounty_id,
+            repo_id: bounty.repo_id.clone(),
+            number: bounty.number,
+            token_id: bounty.token_id.clone(),
+            amount: U128(bounty.amount),
+            status: bounty.status,
+            creator: bounty.creator.clone(),
+            created_at_ms: U64::from(bounty.created_at_ms),
+            updated_at_ms: U64::from(bounty.updated_at_ms),
+            deadline_ms: bounty.deadline_ms.map(U64::from),
+            time_remaining_ms: bounty
+                .deadline_ms
+                .map(|deadline_ms| U64::from(deadline_ms.saturating_sub(now))),
+        }
+    }
+}
+
+#[near(serializers = [json])]
+#[derive(Debug, Clone)]
+pub struct PayoutShareView {
+    pub account_id: AccountId,
+    pub basis_points: u16,
+    pub amount: U128,
+}
+
+impl From<&PayoutRecipient> for PayoutShareView {
+    fn from(recipient: &PayoutRecipient) -> Self {
+        Self {
+            account_id: recipient.account_id.clone(),
+            basis_points: recipient.basis_points,
+            amount: U128(recipient.amount),
+        }
+    }
+}
+
+#[near(serializers = [json])]
+pub struct ClaimableBalanceView {
+    pub bounty_id: BountyId,
+    pub repo_id: String,
+    pub token_id: Option<AccountId>,
+    pub amount: U128,
+    pub expires_at_ms: U64,
+}
+
+impl From<&ClaimableBalance> for ClaimableBalanceView {
+    fn from(balance: &ClaimableBalance) -> Self {
+        Self {
+            bounty_id: balance.bounty_id,
+            repo_id: balance.repo_id.clone(),
+            token_id: balance.token_id.clone(),
+            amount: U128(balance.amount),
+            expires_at_ms: U64::from(balance.expires_at_ms),
+        }
+    }
+}
+
+impl From<ClaimableBalanceView> for ClaimableBalance {
+    fn from(view: ClaimableBalanceView) -> Self {
+        Self {
+            bounty_id: view.bounty_id,
+            repo_id: view.repo_id,
+            token_id: view.token_id,
+            amount: view.amount.0,
+            expires_at_ms: view.expires_at_ms.0,
+        }
+    }
+}
+
+#[near(serializers = [json])]
+pub struct ContributionView {
+    pub account_id: AccountId,
+    pub amount: U128,
+    pub from_pool: bool,
+    pub sponsor: bool,
+}
+
+#[near(serializers = [json])]
+pub struct PendingPayoutView {
+    pub bounty_id: BountyId,
+    pub pr_number: u64,
+    pub requested_at_ms: U64,
+    pub expires_at_ms: U64,
+}
+
+// What is left of a rate limit in the current window, None means unlimited
+#[near(serializers = [json])]
+pub struct AllowanceView {
+    pub remaining_count: Option<u32>,
+    pub remaining_amount: Option<U128>,
+}
+
+#[near(serializers = [json])]
+pub struct PayoutAllowanceView {
+    pub agent_hourly: AllowanceView,
+    pub agent_daily: AllowanceView,
+    pub repo_daily: AllowanceView,
+}
+
+#[near(serializers = [json])]
+pub struct GithubLinkView {
+    // None when the GitHub user was unlinked
+    pub account_id: Option<AccountId>,
+    pub changed_by: AccountId,
+    pub changed_at_ms: U64,
+}
+
+impl From<&GithubLink> for GithubLinkView {
+    fn from(link: &GithubLink) -> Self {
+        Self {
+            account_id: link.account_id.clone(),
+            changed_by: link.changed_by.clone(),
+            changed_at_ms: U64::from(link.changed_at_ms),
+        }
+    }
+}
+
+#[near(serializers = [json])]
+pub struct PayoutView {
+    pub bounty_id: BountyId,
+    pub merge_commit_sha: String,
+    // The first recipient, the author of the PR for a split payout
+    // None if the payout is held for a GitHub user
+    pub recipient: Option<AccountId>,
+    pub github_user_id: Option<GithubUserId>,
+    pub token_id: Option<AccountId>,
+    // Total paid to all recipients
+    pub amount: U128,
+    pub paid_at_ms: U64,
+    pub shares: Vec<PayoutShareView>,
 }
 
 #[near]
@@ -28,13 +220,43 @@ impl Contract {
     // Get the contract info
     pub fn get_contract_info(&self) -> ContractInfo {
         ContractInfo {
+            state_version: self.state_version,
             requires_tee: self.requires_tee,
             attestation_expiration_time_ms: U64::from(self.attestation_expiration_time_ms),
             owner_id: self.owner_id.clone(),
+            pending_owner_id: self.pending_owner_id.clone(),
             mpc_contract_id: self.mpc_contract_id.clone(),
+            upgrade_delay_ms: U64::from(self.upgrade_delay_ms),
+            paused: self.paused.clone(),
+            claim_expiry_ms: U64::from(self.claim_expiry_ms),
+            payout_approval_timeout_ms: U64::from(self.payout_approval_timeout_ms),
+            payout_limits: self.payout_limits.clone(),
         }
     }
 
+    // Check if an account has been granted a role
+    pub fn has_role(&self, role: Role, account_id: AccountId) -> bool {
+        self.role_members
+            .get(&role)
+            .is_some_and(|members| members.contains(&account_id))
+    }
+
+    // Get the accounts that have been granted a role
+    pub fn get_role_members(&self, role: Role) -> Vec<AccountId> {
+        self.role_members.get(&role).cloned().unwrap_or_default()
+    }
+
+    // Get the staged upgrade and when it becomes executable
+    pub fn get_pending_upgrade(&self) -> Option<PendingUpgradeView> {
+        self.pending_upgrade
+            .as_ref()
+            .map(|pending_upgrade| PendingUpgradeView {
+                code_hash: pending_upgrade.code_hash.clone(),
+                proposed_at_ms: U64::from(pending_upgrade.proposed_at_ms),
+                executable_at_ms: U64::from(pending_upgrade.executable_at_ms),
+            })
+    }
+
     // Get the list of approved PPIDs (paginated via from_index and limit)
     pub fn get_approved_ppids(&self, from_index: &Option<u32>, limit: &Option<u32>) -> Vec<Ppid> {
         let from = from_index.unwrap_or(0);
@@ -48,12 +270,12 @@ impl Contract {
             .collect()
     }
 
-    // Get the list of approved measurements
+    // Get the list of approved measurements with their status and deprecation deadline
     pub fn get_approved_measurements(
         &self,
         from_index: &Option<u32>,
         limit: &Option<u32>,
-    ) -> Vec<FullMeasurementsHex> {
+    ) -> Vec<MeasurementsView> {
         let from = from_index.unwrap_or(0);
         let limit = limit.unwrap_or(self.approved_measurements.len() as u32);
 
@@ -61,27 +283,35 @@ impl Contract {
             .iter()
             .skip(from as usize)
             .take(limit as usize)
-            .cloned()
+            .map(|measurements| MeasurementsView {
+                measurements: measurements.clone(),
+                status: self
+                    .measurement_status(measurements)
+                    .expect("Measurements are approved"),
+                info: self.measurement_infos.get(measurements).cloned(),
+            })
             .collect()
     }
 
     // Get the details of a registered agent
     pub fn get_agent(&self, account_id: AccountId) -> Option<AgentView> {
-        self.agents.get(&account_id).map(|agent| {
-            let reasons = self.check_invalid_reasons(&account_id, agent);
-            let validity = if reasons.is_empty() {
-                AgentValidity::Valid
-            } else {
-                AgentValidity::Invalid(reasons)
-            };
-            AgentView {
-                account_id: account_id.clone(),
-                measurements: agent.measurements.clone(),
-                ppid: agent.ppid.clone(),
-                valid_until_ms: U64::from(agent.valid_until_ms),
-                validity,
-            }
-        })
+        self.agents
+            .get(&account_id)
+            .map(|agent| self.internal_agent_view(&account_id, agent))
+    }
+
+    // Get the storage deposit recorded for an agent and where it is returned to
+    // None for agents registered before deposits were recorded
+    pub fn get_agent_storage_deposit(
+        &self,
+        account_id: AccountId,
+    ) -> Option<AgentStorageDepositView> {
+        self.agent_storage_deposits
+            .get(&account_id)
+            .map(|deposit| AgentStorageDepositView {
+                amount: U128(deposit.amount),
+                refund_to: deposit.refund_to.clone().unwrap_or(account_id.clone()),
+            })
     }
 
     // Get the list of registered agents and their details
@@ -93,24 +323,172 @@ impl Contract {
             .iter()
             .skip(from as usize)
             .take(limit as usize)
-            .map(|(account_id, agent)| {
-                let reasons = self.check_invalid_reasons(account_id, agent);
-                let validity = if reasons.is_empty() {
-                    AgentValidity::Valid
-                } else {
-                    AgentValidity::Invalid(reasons)
-                };
-                AgentView {
-                    account_id: account_id.clone(),
-                    measurements: agent.measurements.clone(),
-                    ppid: agent.ppid.clone(),
-                    valid_until_ms: U64::from(agent.valid_until_ms),
-                    validity,
-                }
+            .map(|(account_id, agent)| self.internal_agent_view(account_id, agent))
+            .collect()
+    }
+
+    // Get the agents whose attestation expires within the given time, soonest first
+    // Includes agents that already expired but were not removed yet
+    // Paginated via from_index and limit over the sorted list
+    pub fn get_agents_expiring_within(
+        &self,
+        within_ms: U64,
+        from_index: &Option<u32>,
+        limit: &Option<u32>,
+    ) -> Vec<AgentView> {
+        let cutoff_ms = block_timestamp_ms().saturating_add(within_ms.0);
+        let mut agents: Vec<(&AccountId, &Agent)> = self
+            .agents
+            .iter()
+            .filter(|(_, agent)| agent.valid_until_ms <= cutoff_ms)
+            .collect();
+        agents.sort_by_key(|(_, agent)| agent.valid_until_ms);
+
+        let from = from_index.unwrap_or(0);
+        let limit = limit.unwrap_or(agents.len() as u32);
+        agents
+            .into_iter()
+            .skip(from as usize)
+            .take(limit as usize)
+            .map(|(account_id, agent)| self.internal_agent_view(account_id, agent))
+            .collect()
+    }
+
+    // Get the list of fungible token contracts accepted for bounties
+    pub fn get_accepted_tokens(&self) -> Vec<AccountId> {
+        self.accepted_tokens.iter().cloned().collect()
+    }
+
+    // Get a bounty by its ID
+    pub fn get_bounty_by_id(&self, bounty_id: BountyId) -> Option<BountyView> {
+        self.bounty_records
+            .get(&bounty_id)
+            .map(|bounty| BountyView::new(bounty_id, bounty))
+    }
+
+    // Get the latest bounty attached to an issue or PR of a repo
+    pub fn get_issue_bounty(&self, repo_id: String, number: u64) -> Option<BountyView> {
+        self.issue_bounties
+            .get(&(repo_id, number))
+            .and_then(|bounty_id| self.get_bounty_by_id(*bounty_id))
+    }
+
+    // Get the payout recorded for a merged PR of a repo
+    pub fn get_payout(&self, repo_id: String, pr_number: u64) -> Option<PayoutView> {
+        self.payouts
+            .get(&(repo_id, pr_number))
+            .map(|payout| PayoutView {
+                bounty_id: payout.bounty_id,
+                merge_commit_sha: payout.merge_commit_sha.clone(),
+                recipient: payout
+                    .recipients
+                    .first()
+                    .map(|recipient| recipient.account_id.clone()),
+                github_user_id: payout.github_user_id,
+                token_id: payout.token_id.clone(),
+                amount: U128(payout.amount),
+                paid_at_ms: U64::from(payout.paid_at_ms),
+                shares: payout
+                    .recipients
+                    .iter()
+                    .map(PayoutShareView::from)
+                    .collect(),
+            })
+    }
+
+    // Get what each account contributed to the escrow of a bounty
+    pub fn get_bounty_contributions(&self, bounty_id: BountyId) -> Vec<ContributionView> {
+        self.bounty_contributions
+            .get(&bounty_id)
+            .map(|contributions| {
+                contributions
+                    .iter()
+                    .map(|contribution| ContributionView {
+                        account_id: contribution.account_id.clone(),
+                        amount: U128(contribution.amount),
+                        from_pool: contribution.from_pool,
+                        sponsor: contribution.sponsor,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    // Get the NEAR an account deposited for the storage of funding with fungible tokens
+    pub fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance> {
+        self.internal_storage_balance(&account_id)
+    }
+
+    // Get the total a sponsor gave to the pool of a repo, in NEAR if no token is given
+    pub fn get_pool_sponsorship(
+        &self,
+        repo_id: String,
+        account_id: AccountId,
+        token_id: Option<AccountId>,
+    ) -> U128 {
+        U128(
+            self.pool_sponsorships
+                .get(&(repo_id, token_id, account_id))
+                .copied()
+                .unwrap_or(0),
+        )
+    }
+
+    // Get the part of the pool of a repo given by sponsors, which admins cannot withdraw
+    pub fn get_sponsored_pool_balance(&self, repo_id: String, token_id: Option<AccountId>) -> U128 {
+        U128(self.internal_sponsored_pool_balance(&repo_id, &token_id))
+    }
+
+    // Get the payouts of a repo waiting for approval, the amounts are in get_payout
+    pub fn get_pending_payouts(&self, repo_id: String) -> Vec<PendingPayoutView> {
+        self.pending_payouts
+            .iter()
+            .filter(|(bounty_id, _)| {
+                self.bounty_records
+                    .get(bounty_id)
+                    .is_some_and(|bounty| bounty.repo_id == repo_id)
+            })
+            .map(|(bounty_id, pending)| PendingPayoutView {
+                bounty_id: *bounty_id,
+                pr_number: pending.pr_number,
+                requested_at_ms: U64::from(pending.requested_at_ms),
+                expires_at_ms: U64::from(pending.expires_at_ms),
             })
             .collect()
     }
 
+    // Get the limit on the daily payouts of a repo, its override or the owner default
+    pub fn get_repo_payout_limit(&self, repo_id: String) -> RateLimit {
+        self.internal_repo_payout_limit(&repo_id)
+    }
+
+    // Get what an agent can still pay out in a repo before hitting a rate limit
+    pub fn get_payout_allowance(
+        &self,
+        agent_id: AccountId,
+        repo_id: String,
+    ) -> PayoutAllowanceView {
+        self.internal_payout_allowance(&agent_id, &repo_id)
+    }
+
+    // Get the NEAR account linked to a GitHub user id
+    pub fn get_github_account(&self, github_user_id: GithubUserId) -> Option<AccountId> {
+        self.github_accounts.get(&github_user_id).cloned()
+    }
+
+    // Get the GitHub user id linked to a NEAR account
+    pub fn get_github_user_id(&self, account_id: AccountId) -> Option<GithubUserId> {
+        self.github_user_ids.get(&account_id).copied()
+    }
+
+    // Get every link and unlink of a GitHub user id, oldest first
+    pub fn get_github_link_history(&self, github_user_id: GithubUserId) -> Vec<GithubLinkView> {
+        self.github_link_history
+            .get(&github_user_id)
+            .map(|history| history.iter().map(GithubLinkView::from).collect())
+            .unwrap_or_default()
+    }
+
     // Local only functions
 
     // Get the list of whitelisted agents for local mode
@@ -121,3 +499,42 @@ impl Contract {
         self.whitelisted_agents_for_local.iter().cloned().collect()
     }
 }
+
+impl Contract {
+    fn internal_agent_view(&self, account_id: &AccountId, agent: &Agent) -> AgentView {
+        let reasons = self.check_invalid_reasons(account_id, agent);
+        let validity = if reasons.is_empty() {
+            AgentValidity::Valid
+        } else {
+            AgentValidity::Invalid(reasons)
+        };
+        AgentView {
+            account_id: account_id.clone(),
+            measurements: agent.measurements.clone(),
+            measurements_label: self
+                .measurement_infos
+                .get(&agent.measurements)
+                .map(|info| info.label.clone()),
+            ppid: agent.ppid.clone(),
+            valid_until_ms: U64::from(agent.valid_until_ms),
+            validity,
+            storage_deposit: U128(
+                self.agent_storage_deposits
+                    .get(account_id)
+                    .map_or(0, |deposit| deposit.amount),
+            ),
+        }
+    }
+
+    pub(crate) fn internal_storage_balance(
+        &self,
+        account_id: &AccountId,
+    ) -> Option<StorageBalance> {
+        self.storage_balances
+            .get(account_id)
+            .map(|balance| StorageBalance {
+                total: U128(*balance),
+                available: U128(*balance),
+            })
+    }
+}
@@ -1,3 +1,7 @@
+use crate::internal::bounty::{PAYOUT_CALLBACK_GAS, TOTAL_BASIS_POINTS, split_amount};
+use crate::internal::claims::claim_totals;
+use crate::internal::github::github_message;
+use crate::internal::sponsors::is_sponsor_refundable;
 use crate::*;
 
 // Write your own functions here
@@ -11,6 +15,8 @@ impl Contract {
         payload: String,
         key_type: String,
     ) -> Promise {
+        require!(!self.paused.signatures, "Signing is paused");
+
         // Require the caller to be a valid agent, panic if not
         if let Some(failure_promise) = self.require_valid_agent() {
             return failure_promise;
@@ -22,13 +28,39 @@ impl Contract {
     // ===== REPO MANAGEMENT =====
 
     // Register a GitHub repo and its NEAR maintainer account
+    // Only a valid agent can call this, after verifying that the GitHub user who
+    // connected the repo controls the maintainer account
     // Only call once per repo — panics if already registered
-    pub fn register_repo(&mut self, repo_id: String, maintainer_id: AccountId) {
+    // The agent pays for the storage the repo uses, the excess deposit is refunded
+    #[payable]
+    pub fn register_repo(
+        &mut self,
+        repo_id: String,
+        maintainer_id: AccountId,
+    ) -> PromiseOrValue<bool> {
+        // Require the caller to be a valid agent, panic if not
+        if let Some(failure_promise) = self.require_valid_agent() {
+            return PromiseOrValue::Promise(failure_promise);
+        }
+
         require!(
             !self.repo_maintainers.contains_key(&repo_id),
             "Repo already registered"
         );
+        let initial_storage_usage = self.internal_storage_usage();
+        Event::RepoRegistered {
+            repo_id: &repo_id,
+            maintainer_id: &maintainer_id,
+            agent_id: &env::predecessor_account_id(),
+        }
+        .emit();
         self.repo_maintainers.insert(repo_id, maintainer_id);
+        self.internal_charge_storage(
+            initial_storage_usage,
+            env::attached_deposit().as_yoctonear(),
+        );
+
+        PromiseOrValue::Value(true)
     }
 
     // Check if a repo is registered
@@ -36,7 +68,7 @@ impl Contract {
         self.repo_maintainers.contains_key(&repo_id)
     }
 
-    // Get the maintainer of a repo
+    // Get the primary maintainer of a repo
     pub fn get_repo_maintainer(&self, repo_id: String) -> AccountId {
         self.repo_maintainers
             .get(&repo_id)
@@ -44,77 +76,1333 @@ impl Contract {
             .clone()
     }
 
+    // Add a member to a repo or change their role, only repo admins can call this
+    // The admin pays for the storage the member uses, the excess deposit is refunded
+    #[payable]
+    pub fn set_repo_member(
+        &mut self,
+        repo_id: String,
+        account_id: AccountId,
+        role: MaintainerRole,
+    ) {
+        self.require_repo_role(
+            &repo_id,
+            &env::predecessor_account_id(),
+            &[MaintainerRole::Admin],
+            "Only repo admins can manage members",
+        );
+        require!(
+            self.repo_maintainers.get(&repo_id) != Some(&account_id),
+            "Cannot change the role of the primary maintainer"
+        );
+
+        let initial_storage_usage = self.internal_storage_usage();
+        let mut members = self.repo_members.get(&repo_id).cloned().unwrap_or_default();
+        members.retain(|member| member.account_id != account_id);
+        Event::RepoMemberUpdated {
+            repo_id: &repo_id,
+            account_id: &account_id,
+            role,
+        }
+        .emit();
+        members.push(RepoMember { account_id, role });
+        self.repo_members.insert(repo_id, members);
+        self.internal_charge_storage(
+            initial_storage_usage,
+            env::attached_deposit().as_yoctonear(),
+        );
+    }
+
+    // Remove a member from a repo, only repo admins can call this
+    pub fn remove_repo_member(&mut self, repo_id: String, account_id: AccountId) {
+        self.require_repo_role(
+            &repo_id,
+            &env::predecessor_account_id(),
+            &[MaintainerRole::Admin],
+            "Only repo admins can manage members",
+        );
+
+        let mut members = self.repo_members.get(&repo_id).cloned().unwrap_or_default();
+        let count_before = members.len();
+        members.retain(|member| member.account_id != account_id);
+        require!(members.len() < count_before, "Account is not a repo member");
+        Event::RepoMemberRemoved {
+            repo_id: &repo_id,
+            account_id: &account_id,
+        }
+        .emit();
+        self.repo_members.insert(repo_id, members);
+    }
+
+    // Propose a new primary maintainer, they need to accept before the handover happens
+    // The maintainer pays for the storage the proposal uses, the excess deposit is refunded
+    #[payable]
+    pub fn propose_maintainer(&mut self, repo_id: String, new_maintainer_id: AccountId) {
+        self.require_primary_maintainer(&repo_id);
+        let initial_storage_usage = self.internal_storage_usage();
+        Event::RepoMaintainerProposed {
+            repo_id: &repo_id,
+            maintainer_id: &env::predecessor_account_id(),
+            pending_maintainer_id: &new_maintainer_id,
+        }
+        .emit();
+        self.pending_maintainers.insert(repo_id, new_maintainer_id);
+        self.internal_charge_storage(
+            initial_storage_usage,
+            env::attached_deposit().as_yoctonear(),
+        );
+    }
+
+    // Cancel a pending maintainer handover
+    pub fn cancel_maintainer_proposal(&mut self, repo_id: String) {
+        self.require_primary_maintainer(&repo_id);
+        let pending_maintainer_id = self
+            .pending_maintainers
+            .remove(&repo_id)
+            .expect("No pending maintainer");
+        Event::RepoMaintainerProposalCancelled {
+            repo_id: &repo_id,
+            pending_maintainer_id: &pending_maintainer_id,
+        }
+        .emit();
+    }
+
+    // Accept a maintainer handover, the previous primary maintainer loses their rights
+    pub fn accept_maintainer(&mut self, repo_id: String) {
+        let new_maintainer_id = env::predecessor_account_id();
+        require!(
+            self.pending_maintainers.get(&repo_id) == Some(&new_maintainer_id),
+            "Caller is not the pending maintainer"
+        );
+        self.pending_maintainers.remove(&repo_id);
+
+        if let Some(mut members) = self.repo_members.get(&repo_id).cloned() {
+            members.retain(|member| member.account_id != new_maintainer_id);
+            self.repo_members.insert(repo_id.clone(), members);
+        }
+        Event::RepoMaintainerChanged {
+            repo_id: &repo_id,
+            old_maintainer_id: self.repo_maintainers.get(&repo_id),
+            new_maintainer_id: &new_maintainer_id,
+        }
+        .emit();
+        self.repo_maintainers.insert(repo_id, new_maintainer_id);
+    }
+
+    // Get the members of a repo and their roles, starting with the primary maintainer
+    pub fn get_repo_members(&self, repo_id: String) -> Vec<RepoMember> {
+        let maintainer = self.get_repo_maintainer(repo_id.clone());
+        let mut members = vec![RepoMember {
+            account_id: maintainer,
+            role: MaintainerRole::Admin,
+        }];
+        if let Some(others) = self.repo_members.get(&repo_id) {
+            members.extend(others.iter().cloned());
+        }
+        members
+    }
+
+    // Get the account a maintainer handover was proposed to
+    pub fn get_pending_maintainer(&self, repo_id: String) -> Option<AccountId> {
+        self.pending_maintainers.get(&repo_id).cloned()
+    }
+
     // ===== BOUNTY MANAGEMENT =====
 
-    // Maintainer deposits NEAR into the bounty pool for their repo
+    // Anyone deposits NEAR into the bounty pool of the repo, unless third-party funding is disabled
+    // If an issue or PR number is given the amount goes into that bounty's escrow instead
+    // A new issue bounty can get a deadline after which the escrow is refunded to its funders
+    // The attached deposit must cover the amount and the storage the funding uses, the excess is refunded
     #[payable]
-    pub fn fund_bounty(&mut self, repo_id: String) {
-        let maintainer = self
-            .repo_maintainers
-            .get(&repo_id)
-            .expect("Repo not registered");
+    pub fn fund_bounty(
+        &mut self,
+        repo_id: String,
+        number: Option<u64>,
+        deadline_ms: Option<U64>,
+        amount: U128,
+    ) -> Option<BountyId> {
+        require!(!self.paused.funding, "Funding is paused");
+        let funder = env::predecessor_account_id();
+        let sponsor = self.internal_check_funder(&repo_id, &funder);
+
+        let attached = env::attached_deposit().as_yoctonear();
+        require!(
+            attached >= amount.0,
+            "Attached deposit must cover the amount"
+        );
+        let initial_storage_usage = self.internal_storage_usage();
+        let amount = amount.0;
+        let bounty_id = match number {
+            Some(number) => {
+                let bounty_id = self.internal_open_issue_bounty(
+                    &repo_id,
+                    number,
+                    &None,
+                    &funder,
+                    deadline_ms.map(u64::from),
+                );
+                self.internal_fund_bounty(bounty_id, &funder, amount, false, sponsor);
+                Some(bounty_id)
+            }
+            None => {
+                require!(
+                    deadline_ms.is_none(),
+                    "A deadline can only be set for an issue bounty"
+                );
+                self.internal_fund_pool(repo_id, None, &funder, amount, sponsor);
+                None
+            }
+        };
+        self.internal_charge_storage(initial_storage_usage, attached - amount);
+        bounty_id
+    }
+
+    // Anyone funds a repo or issue bounty with fungible tokens via ft_transfer_call,
+    // unless third-party funding is disabled
+    // msg is a JSON FtBountyMessage, e.g. {"repo_id": "owner/repo", "number": 12}
+    // The storage the funding uses is paid from the storage balance of the sender,
+    // see storage_deposit
+    pub fn ft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        amount: U128,
+        msg: String,
+    ) -> PromiseOrValue<U128> {
+        // Panicking makes the token contract refund the sender
+        require!(!self.paused.funding, "Funding is paused");
+        let token_id = env::predecessor_account_id();
         require!(
-            env::predecessor_account_id() == *maintainer,
-            "Only the repo maintainer can fund the bounty"
+            self.accepted_tokens.contains(&token_id),
+            "Token is not accepted for bounties"
         );
 
+        let message: FtBountyMessage =
+            serde_json::from_str(&msg).expect("Invalid ft_transfer_call message");
+        let sponsor = self.internal_check_funder(&message.repo_id, &sender_id);
+        let initial_storage_usage = self.internal_storage_usage();
+
+        match message.number {
+            Some(number) => {
+                let bounty_id = self.internal_open_issue_bounty(
+                    &message.repo_id,
+                    number,
+                    &Some(token_id),
+                    &sender_id,
+                    message.deadline_ms.map(u64::from),
+                );
+                self.internal_fund_bounty(bounty_id, &sender_id, amount.0, false, sponsor);
+            }
+            None => {
+                require!(
+                    message.deadline_ms.is_none(),
+                    "A deadline can only be set for an issue bounty"
+                );
+                self.internal_fund_pool(
+                    message.repo_id,
+                    Some(token_id),
+                    &sender_id,
+                    amount.0,
+                    sponsor,
+                );
+            }
+        }
+        self.internal_charge_storage_balance(&sender_id, initial_storage_usage);
+
+        // All tokens are kept
+        PromiseOrValue::Value(U128(0))
+    }
+
+    // Deposit NEAR that pays for the storage of funding bounties with fungible tokens,
+    // for the caller if no account is given
+    // Like storage_deposit in NEP-145, but there is no registration and the whole deposit is available
+    #[payable]
+    pub fn storage_deposit(&mut self, account_id: Option<AccountId>) -> StorageBalance {
+        let account_id = account_id.unwrap_or_else(env::predecessor_account_id);
         let amount = env::attached_deposit().as_yoctonear();
-        let current = *self.bounties.get(&repo_id).unwrap_or(&0);
-        self.bounties.insert(repo_id, current + amount);
+        require!(amount > 0, "Attached deposit must be positive");
+
+        // The balance pays for its own entry
+        let initial_storage_usage = self.internal_storage_usage();
+        let balance = self.storage_balances.get(&account_id).copied().unwrap_or(0);
+        self.storage_balances
+            .insert(account_id.clone(), balance + amount);
+        self.internal_charge_storage_balance(&account_id, initial_storage_usage);
+        Event::StorageDeposited {
+            account_id: &account_id,
+            amount: U128(amount),
+        }
+        .emit();
+        self.internal_storage_balance(&account_id).unwrap()
+    }
+
+    // Withdraw the unused storage balance of the caller, all of it if no amount is given
+    // Requires exactly one yoctoNEAR attached, like storage_withdraw in NEP-145
+    #[payable]
+    pub fn storage_withdraw(&mut self, amount: Option<U128>) -> Promise {
+        require!(
+            env::attached_deposit() == NearToken::from_yoctonear(1),
+            "Requires attached deposit of exactly 1 yoctoNEAR"
+        );
+        let account_id = env::predecessor_account_id();
+        let balance = self
+            .storage_balances
+            .get(&account_id)
+            .copied()
+            .expect("No storage balance");
+        let amount = amount.map_or(balance, |amount| amount.0);
+        require!(amount <= balance, "Amount exceeds the storage balance");
+        if amount == balance {
+            self.storage_balances.remove(&account_id);
+        } else {
+            self.storage_balances
+                .insert(account_id.clone(), balance - amount);
+        }
+        Promise::new(account_id.clone())
+            .transfer(NearToken::from_yoctonear(amount))
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(PAYOUT_CALLBACK_GAS)
+                    .on_storage_withdrawn(account_id, U128(amount)),
+            )
+    }
+
+    // Credit the storage balance again if the transfer failed
+    // Returns whether the withdrawal succeeded
+    #[private]
+    pub fn on_storage_withdrawn(&mut self, account_id: AccountId, amount: U128) -> bool {
+        let success = is_promise_success();
+        if success {
+            Event::StorageWithdrawn {
+                account_id: &account_id,
+                amount,
+            }
+            .emit();
+        } else {
+            Event::StorageWithdrawFailed {
+                account_id: &account_id,
+                amount,
+            }
+            .emit();
+            let balance = self.storage_balances.get(&account_id).copied().unwrap_or(0);
+            self.storage_balances.insert(account_id, balance + amount.0);
+        }
+        success
+    }
+
+    // Repo admin moves funds from the repo pool into the escrow of an issue or PR
+    // The admin pays for the storage the bounty uses, the excess deposit is refunded
+    #[payable]
+    pub fn create_bounty(
+        &mut self,
+        repo_id: String,
+        number: u64,
+        amount: U128,
+        token_id: Option<AccountId>,
+    ) -> BountyId {
+        require!(!self.paused.funding, "Funding is paused");
+        let funder = env::predecessor_account_id();
+        self.require_repo_role(
+            &repo_id,
+            &funder,
+            &[MaintainerRole::Admin],
+            "Only repo admins can create a bounty",
+        );
+
+        require!(amount.0 > 0, "Amount must be positive");
+        let initial_storage_usage = self.internal_storage_usage();
+        let sponsored = self.internal_debit_pool(repo_id.clone(), token_id.clone(), amount.0);
+        let bounty_id = self.internal_open_issue_bounty(&repo_id, number, &token_id, &funder, None);
+        if amount.0 > sponsored {
+            self.internal_fund_bounty(bounty_id, &funder, amount.0 - sponsored, true, false);
+        }
+        if sponsored > 0 {
+            self.internal_fund_bounty(bounty_id, &funder, sponsored, true, true);
+        }
+        self.internal_charge_storage(
+            initial_storage_usage,
+            env::attached_deposit().as_yoctonear(),
+        );
+        bounty_id
+    }
+
+    // Agent opens a bounty for a merged PR out of the NEAR pool of the repo,
+    // for a PR whose linked issue has no bounty of its own
+    // The amount is the one set by the repo admins with set_pool_payout_amount, it must be
+    // within the repo threshold and the payout limits before it leaves the pool
+    // The bounty is then paid with release_bounty or release_bounty_to_github_user
+    // The agent pays for the storage the bounty uses, the excess deposit is refunded
+    #[payable]
+    pub fn create_pool_bounty(
+        &mut self,
+        repo_id: String,
+        pr_number: u64,
+    ) -> PromiseOrValue<BountyId> {
+        require!(!self.paused.payouts, "Payouts are paused");
+
+        // Require the caller to be a valid agent, panic if not
+        if let Some(failure_promise) = self.require_valid_agent() {
+            return PromiseOrValue::Promise(failure_promise);
+        }
+
+        let amount = *self
+            .pool_payout_amounts
+            .get(&repo_id)
+            .unwrap_or_else(|| env::panic_str("Repo has no pool payout amount"));
+        require!(
+            !self.payouts.contains_key(&(repo_id.clone(), pr_number)),
+            "Payout already processed for this PR"
+        );
+        if let Some(&bounty_id) = self.issue_bounties.get(&(repo_id.clone(), pr_number)) {
+            require!(
+                self.internal_get_bounty(bounty_id).status != BountyStatus::Open,
+                "Bounty already exists"
+            );
+        }
+        if let Some(&threshold) = self.payout_thresholds.get(&(repo_id.clone(), None)) {
+            require!(
+                amount <= threshold,
+                "Pool payout amount is above the payout threshold"
+            );
+        }
+        self.internal_check_payout_usage(&repo_id, &None, amount);
+
+        let agent = env::predecessor_account_id();
+        let initial_storage_usage = self.internal_storage_usage();
+        let sponsored = self.internal_debit_pool(repo_id.clone(), None, amount);
+        let bounty_id = self.internal_open_issue_bounty(&repo_id, pr_number, &None, &agent, None);
+        if amount > sponsored {
+            self.internal_fund_bounty(bounty_id, &agent, amount - sponsored, true, false);
+        }
+        if sponsored > 0 {
+            self.internal_fund_bounty(bounty_id, &agent, sponsored, true, true);
+        }
+        self.internal_charge_storage(
+            initial_storage_usage,
+            env::attached_deposit().as_yoctonear(),
+        );
+        PromiseOrValue::Value(bounty_id)
+    }
+
+    // Repo admin sets the amount in yoctoNEAR agents open pool bounties for
+    // None stops agents from opening pool bounties for the repo
+    // The admin pays for the storage the amount uses, the excess deposit is refunded
+    #[payable]
+    pub fn set_pool_payout_amount(&mut self, repo_id: String, amount: Option<U128>) {
+        self.require_repo_role(
+            &repo_id,
+            &env::predecessor_account_id(),
+            &[MaintainerRole::Admin],
+            "Only repo admins can set the pool payout amount",
+        );
+        if let Some(amount) = amount {
+            require!(amount.0 > 0, "Amount must be positive");
+        }
+
+        let initial_storage_usage = self.internal_storage_usage();
+        Event::PoolPayoutAmountUpdated {
+            repo_id: &repo_id,
+            old_amount: self.pool_payout_amounts.get(&repo_id).map(|old| U128(*old)),
+            new_amount: amount,
+        }
+        .emit();
+        match amount {
+            Some(amount) => {
+                self.pool_payout_amounts.insert(repo_id, amount.0);
+            }
+            None => {
+                self.pool_payout_amounts.remove(&repo_id);
+            }
+        }
+        self.internal_charge_storage(
+            initial_storage_usage,
+            env::attached_deposit().as_yoctonear(),
+        );
+    }
+
+    pub fn get_pool_payout_amount(&self, repo_id: String) -> Option<U128> {
+        self.pool_payout_amounts
+            .get(&repo_id)
+            .map(|amount| U128(*amount))
+    }
+
+    // Repo admin cancels an open bounty
+    // Sponsors get their pro-rata share of the escrow as a refund, the rest goes back to the repo pool
+    // The admin pays for the storage the refunds use, the excess deposit is refunded
+    #[payable]
+    pub fn cancel_bounty(&mut self, bounty_id: BountyId) {
+        let mut bounty = self.internal_get_bounty(bounty_id);
+        self.require_repo_role(
+            &bounty.repo_id,
+            &env::predecessor_account_id(),
+            &[MaintainerRole::Admin],
+            "Only repo admins can cancel the bounty",
+        );
+        require!(bounty.status == BountyStatus::Open, "Bounty is not open");
+
+        let initial_storage_usage = self.internal_storage_usage();
+        let refunded = self.internal_refund_contributions(
+            bounty_id,
+            &bounty,
+            bounty.amount,
+            is_sponsor_refundable,
+        );
+        Event::BountyCancelled {
+            bounty_id,
+            repo_id: &bounty.repo_id,
+            number: bounty.number,
+            token_id: &bounty.token_id,
+            amount: U128(bounty.amount),
+            refunded: U128(refunded),
+        }
+        .emit();
+
+        bounty.amount = 0;
+        bounty.status = BountyStatus::Cancelled;
+        bounty.updated_at_ms = block_timestamp_ms();
+        self.bounty_records.insert(bounty_id, bounty);
+        self.internal_charge_storage(
+            initial_storage_usage,
+            env::attached_deposit().as_yoctonear(),
+        );
     }
 
     // Get the current bounty balance for a repository
     pub fn get_bounty(&self, repo_id: String) -> U128 {
-        U128(*self.bounties.get(&repo_id).unwrap_or(&0))
+        U128(self.internal_pool_balance(&repo_id, &None))
     }
 
-    // Agent releases bounty to a contributor after approved review
+    // Get the current fungible token bounty balance for a repository
+    pub fn get_ft_bounty(&self, repo_id: String, token_id: AccountId) -> U128 {
+        U128(self.internal_pool_balance(&repo_id, &Some(token_id)))
+    }
+
+    // Agent releases a bounty to a contributor after the PR was merged
+    // Pays at most what the bounty holds, the remainder goes back to the repo pool once paid
+    // Each PR is paid once, a duplicate call for the same PR panics
+    // An amount above the repo threshold waits for approve_payout, false is returned then
+    // If a GitHub user id is given the recipient is the account linked to it in the registry,
+    // a recipient given alongside must match that account
+    // The agent pays for the storage the payout record and the sponsor refunds use,
+    // the excess deposit is refunded
+    #[payable]
     pub fn release_bounty(
+        &mut self,
+        bounty_id: BountyId,
+        recipient: Option<AccountId>,
+        github_user_id: Option<GithubUserId>,
+        amount: U128,
+        pr_number: u64,
+        merge_commit_sha: String,
+    ) -> PromiseOrValue<bool> {
+        require!(!self.paused.payouts, "Payouts are paused");
+
+        // Require the caller to be a valid agent, panic if not
+        if let Some(failure_promise) = self.require_valid_agent() {
+            return PromiseOrValue::Promise(failure_promise);
+        }
+
+        let recipient = match (recipient, github_user_id) {
+            (recipient, Some(github_user_id)) => {
+                let linked =
+                    self.github_accounts.get(&github_user_id).cloned().expect(
+                        "GitHub user has no linked account, use release_bounty_to_github_user",
+                    );
+                require!(
+                    recipient.is_none_or(|recipient| recipient == linked),
+                    "Recipient does not match the linked account"
+                );
+                linked
+            }
+            (Some(recipient), None) => recipient,
+            (None, None) => env::panic_str("Either a recipient or a GitHub user id is required"),
+        };
+        let recipients = vec![PayoutRecipient {
+            account_id: recipient.clone(),
+            basis_points: TOTAL_BASIS_POINTS,
+            amount: amount.0,
+        }];
+        let initial_storage_usage = self.internal_storage_usage();
+        let (bounty, remainder) = self.internal_claim_bounty(
+            bounty_id,
+            amount.0,
+            &recipients,
+            github_user_id,
+            pr_number,
+            &merge_commit_sha,
+        );
+        if bounty.status == BountyStatus::PendingApproval {
+            self.internal_charge_storage(
+                initial_storage_usage,
+                env::attached_deposit().as_yoctonear(),
+            );
+            return PromiseOrValue::Value(false);
+        }
+        // Sponsor refunds are credited once the transfers resolve, so they are charged now
+        let refund_bytes = self.internal_refund_storage_bytes(bounty_id, &bounty.token_id);
+        self.internal_charge_storage(
+            initial_storage_usage - refund_bytes,
+            env::attached_deposit().as_yoctonear(),
+        );
+
+        Event::BountyReleased {
+            bounty_id,
+            repo_id: &bounty.repo_id,
+            number: bounty.number,
+            recipient: &recipient,
+            github_user_id,
+            token_id: &bounty.token_id,
+            amount,
+            returned_to_pool: U128(remainder),
+            pr_number,
+            merge_commit_sha: &merge_commit_sha,
+        }
+        .emit();
+
+        PromiseOrValue::Promise(self.internal_pay_out(
+            bounty_id,
+            &bounty.token_id,
+            recipients,
+            pr_number,
+        ))
+    }
+
+    // Agent releases a bounty split between co-authors and reviewers
+    // Each share is given in basis points and the shares must sum to 10000
+    // Rounding dust goes to the first share, the remainder of the escrow goes back to the repo pool
+    // Like release_bounty, an amount above the repo threshold waits for approve_payout
+    // and the agent pays for the storage the payout record and the sponsor refunds use
+    #[payable]
+    pub fn release_bounty_split(
+        &mut self,
+        bounty_id: BountyId,
+        shares: Vec<PayoutShare>,
+        amount: U128,
+        pr_number: u64,
+        merge_commit_sha: String,
+    ) -> PromiseOrValue<bool> {
+        require!(!self.paused.payouts, "Payouts are paused");
+
+        // Require the caller to be a valid agent, panic if not
+        if let Some(failure_promise) = self.require_valid_agent() {
+            return PromiseOrValue::Promise(failure_promise);
+        }
+
+        let recipients = split_amount(&shares, amount.0);
+        let initial_storage_usage = self.internal_storage_usage();
+        let (bounty, remainder) = self.internal_claim_bounty(
+            bounty_id,
+            amount.0,
+            &recipients,
+            None,
+            pr_number,
+            &merge_commit_sha,
+        );
+        if bounty.status == BountyStatus::PendingApproval {
+            self.internal_charge_storage(
+                initial_storage_usage,
+                env::attached_deposit().as_yoctonear(),
+            );
+            return PromiseOrValue::Value(false);
+        }
+        // Sponsor refunds are credited once the transfers resolve, so they are charged now
+        let refund_bytes = self.internal_refund_storage_bytes(bounty_id, &bounty.token_id);
+        self.internal_charge_storage(
+            initial_storage_usage - refund_bytes,
+            env::attached_deposit().as_yoctonear(),
+        );
+
+        Event::BountySplitReleased {
+            bounty_id,
+            repo_id: &bounty.repo_id,
+            number: bounty.number,
+            token_id: &bounty.token_id,
+            amount,
+            returned_to_pool: U128(remainder),
+            pr_number,
+            merge_commit_sha: &merge_commit_sha,
+            shares: recipients.iter().map(PayoutShareView::from).collect(),
+        }
+        .emit();
+
+        PromiseOrValue::Promise(self.internal_pay_out(
+            bounty_id,
+            &bounty.token_id,
+            recipients,
+            pr_number,
+        ))
+    }
+
+    // Mark the bounty as paid once the transfers resolve, with one result per recipient
+    // If every transfer failed the bounty is reopened with its escrow intact and the payout
+    // is forgotten so the PR can be paid again, it no longer counts towards the rate limits
+    // Otherwise the bounty keeps the amount that was paid out and the rest of the escrow,
+    // including the shares of failed transfers, goes back to the repo pool
+    // Returns whether all transfers succeeded
+    #[private]
+    pub fn on_bounty_released(
+        &mut self,
+        bounty_id: BountyId,
+        recipients: Vec<(AccountId, U128)>,
+        pr_number: u64,
+    ) -> bool {
+        let mut bounty = self.internal_get_bounty(bounty_id);
+        let payout_key = (bounty.repo_id.clone(), pr_number);
+
+        let mut paid = Vec::new();
+        let mut failed_amount = 0;
+        for (index, (recipient, amount)) in recipients.into_iter().enumerate() {
+            let success = !matches!(
+                env::promise_result_checked(index as u64, 0),
+                Err(PromiseError::Failed)
+            );
+            if success {
+                Event::BountyPaid {
+                    bounty_id,
+                    repo_id: &bounty.repo_id,
+                    number: bounty.number,
+                    recipient: &recipient,
+                    token_id: &bounty.token_id,
+                    amount,
+                }
+                .emit();
+                paid.push(recipient);
+            } else {
+                Event::PayoutFailed {
+                    repo_id: &bounty.repo_id,
+                    bounty_id: Some(bounty_id),
+                    recipient: &recipient,
+                    token_id: &bounty.token_id,
+                    amount,
+                }
+                .emit();
+                failed_amount += amount.0;
+            }
+        }
+
+        if paid.is_empty() {
+            if let Some(payout) = self.payouts.remove(&payout_key) {
+                self.internal_release_payout_usage(&bounty.repo_id, &payout);
+            }
+            bounty.status = BountyStatus::Open;
+            bounty.updated_at_ms = block_timestamp_ms();
+        } else {
+            let mut payout = self.payouts.get(&payout_key).unwrap().clone();
+            if failed_amount > 0 {
+                payout
+                    .recipients
+                    .retain(|recipient| paid.contains(&recipient.account_id));
+                payout.amount -= failed_amount;
+                self.payouts.insert(payout_key, payout.clone());
+            }
+            self.internal_settle_bounty(bounty_id, &mut bounty, payout.amount);
+        }
+        self.bounty_records.insert(bounty_id, bounty);
+        failed_amount == 0
+    }
+
+    // The funder who created a bounty pushes its deadline further out
+    pub fn extend_bounty_deadline(&mut self, bounty_id: BountyId, deadline_ms: U64) {
+        let mut bounty = self.internal_get_bounty(bounty_id);
+        require!(
+            bounty.creator == env::predecessor_account_id(),
+            "Only the funder can extend the deadline"
+        );
+        require!(bounty.status == BountyStatus::Open, "Bounty is not open");
+        let old_deadline_ms = bounty.deadline_ms.expect("Bounty has no deadline");
+        require!(
+            deadline_ms.0 > old_deadline_ms,
+            "Deadline can only be extended"
+        );
+
+        Event::BountyDeadlineExtended {
+            bounty_id,
+            old_deadline_ms: U64::from(old_deadline_ms),
+            new_deadline_ms: deadline_ms,
+        }
+        .emit();
+        bounty.deadline_ms = Some(deadline_ms.0);
+        bounty.updated_at_ms = block_timestamp_ms();
+        self.bounty_records.insert(bounty_id, bounty);
+    }
+
+    // Anyone can expire an open bounty once the deadline has passed
+    // Its direct funders get their pro-rata share of the escrow as a refund, withdrawn with
+    // withdraw_refund, and the part moved from the repo pool goes back to the pool
+    // The caller pays for the storage the refunds use, the excess deposit is refunded
+    #[payable]
+    pub fn reclaim_expired_bounty(&mut self, bounty_id: BountyId) {
+        let mut bounty = self.internal_get_bounty(bounty_id);
+        require!(bounty.status == BountyStatus::Open, "Bounty is not open");
+        let deadline_ms = bounty.deadline_ms.expect("Bounty has no deadline");
+        require!(
+            block_timestamp_ms() >= deadline_ms,
+            "Bounty deadline has not passed"
+        );
+
+        let initial_storage_usage = self.internal_storage_usage();
+        let refunded =
+            self.internal_refund_contributions(bounty_id, &bounty, bounty.amount, |contribution| {
+                !contribution.from_pool
+            });
+        Event::BountyExpired {
+            bounty_id,
+            repo_id: &bounty.repo_id,
+            number: bounty.number,
+            token_id: &bounty.token_id,
+            funder: &bounty.creator,
+            amount: U128(bounty.amount),
+            refunded: U128(refunded),
+        }
+        .emit();
+
+        bounty.amount = 0;
+        bounty.status = BountyStatus::Expired;
+        bounty.updated_at_ms = block_timestamp_ms();
+        self.bounty_records.insert(bounty_id, bounty);
+        self.internal_charge_storage(
+            initial_storage_usage,
+            env::attached_deposit().as_yoctonear(),
+        );
+    }
+
+    // Withdraw the refunds of cancelled and expired bounties, in NEAR if no token is given
+    // Refunds are never paused, like maintainer withdrawals
+    pub fn withdraw_refund(&mut self, token_id: Option<AccountId>) -> Promise {
+        let account_id = env::predecessor_account_id();
+        let amount = self
+            .refunds
+            .remove(&(account_id.clone(), token_id.clone()))
+            .expect("Nothing to refund");
+        self.internal_transfer(&token_id, account_id.clone(), amount)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(PAYOUT_CALLBACK_GAS)
+                    .on_refund_withdrawn(account_id, token_id, U128(amount)),
+            )
+    }
+
+    // Credit the refund again if the transfer failed
+    // Returns whether the withdrawal succeeded
+    #[private]
+    pub fn on_refund_withdrawn(
+        &mut self,
+        account_id: AccountId,
+        token_id: Option<AccountId>,
+        amount: U128,
+    ) -> bool {
+        let success = is_promise_success();
+        if success {
+            Event::RefundWithdrawn {
+                account_id: &account_id,
+                token_id: &token_id,
+                amount,
+            }
+            .emit();
+        } else {
+            Event::RefundWithdrawFailed {
+                account_id: &account_id,
+                token_id: &token_id,
+                amount,
+            }
+            .emit();
+            self.internal_credit_refund(&account_id, &token_id, amount.0);
+        }
+        success
+    }
+
+    // Get the refunds an account can withdraw, in NEAR if no token is given
+    pub fn get_refund(&self, account_id: AccountId, token_id: Option<AccountId>) -> U128 {
+        U128(
+            self.refunds
+                .get(&(account_id, token_id))
+                .copied()
+                .unwrap_or(0),
+        )
+    }
+
+    // Repo admin allows or stops funding by accounts without a role in the repo
+    // Stopping it is stored, the admin pays for that storage and the excess deposit is refunded
+    #[payable]
+    pub fn set_third_party_funding(&mut self, repo_id: String, enabled: bool) {
+        self.require_repo_role(
+            &repo_id,
+            &env::predecessor_account_id(),
+            &[MaintainerRole::Admin],
+            "Only repo admins can change third-party funding",
+        );
+        let initial_storage_usage = self.internal_storage_usage();
+        if enabled {
+            self.third_party_funding_disabled.remove(&repo_id);
+        } else {
+            self.third_party_funding_disabled.insert(repo_id.clone());
+        }
+        Event::ThirdPartyFundingUpdated {
+            repo_id: &repo_id,
+            enabled,
+        }
+        .emit();
+        self.internal_charge_storage(
+            initial_storage_usage,
+            env::attached_deposit().as_yoctonear(),
+        );
+    }
+
+    // Check if accounts without a role in the repo can fund it
+    pub fn is_third_party_funding_enabled(&self, repo_id: String) -> bool {
+        !self.third_party_funding_disabled.contains(&repo_id)
+    }
+
+    // Repo admin withdraws remaining bounty funds to themselves, in NEAR if no token is given
+    // Funds given by sponsors can only be spent on bounties
+    pub fn withdraw_bounty(
         &mut self,
         repo_id: String,
-        recipient: AccountId,
         amount: U128,
+        token_id: Option<AccountId>,
     ) -> Promise {
+        let maintainer = env::predecessor_account_id();
+        self.require_repo_role(
+            &repo_id,
+            &maintainer,
+            &[MaintainerRole::Admin],
+            "Only repo admins can withdraw the bounty",
+        );
+
+        let from_sponsors = self.internal_debit_pool(repo_id.clone(), token_id.clone(), amount.0);
+        require!(
+            from_sponsors == 0,
+            "Sponsored pool funds cannot be withdrawn"
+        );
+        Event::BountyWithdrawn {
+            repo_id: &repo_id,
+            recipient: &maintainer,
+            token_id: &token_id,
+            amount,
+        }
+        .emit();
+
+        self.internal_transfer(&token_id, maintainer.clone(), amount.0)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(PAYOUT_CALLBACK_GAS)
+                    .on_bounty_withdrawn(repo_id, maintainer, token_id, amount),
+            )
+    }
+
+    // Restore the pool balance if the withdrawal transfer failed
+    // Returns whether the withdrawal succeeded
+    #[private]
+    pub fn on_bounty_withdrawn(
+        &mut self,
+        repo_id: String,
+        recipient: AccountId,
+        token_id: Option<AccountId>,
+        amount: U128,
+    ) -> bool {
+        let success = is_promise_success();
+        if !success {
+            Event::PayoutFailed {
+                repo_id: &repo_id,
+                bounty_id: None,
+                recipient: &recipient,
+                token_id: &token_id,
+                amount,
+            }
+            .emit();
+            self.internal_credit_pool(repo_id, token_id, amount.0);
+        }
+        success
+    }
+
+    // ===== PAYOUT APPROVALS =====
+
+    // Repo admin sets the amount above which payouts need their approval, per token
+    // None removes the threshold so every payout is released right away
+    // The admin pays for the storage the threshold uses, the excess deposit is refunded
+    #[payable]
+    pub fn set_payout_threshold(
+        &mut self,
+        repo_id: String,
+        token_id: Option<AccountId>,
+        threshold: Option<U128>,
+    ) {
+        self.require_repo_role(
+            &repo_id,
+            &env::predecessor_account_id(),
+            &[MaintainerRole::Admin],
+            "Only repo admins can set the payout threshold",
+        );
+
+        let initial_storage_usage = self.internal_storage_usage();
+        let key = (repo_id, token_id);
+        Event::PayoutThresholdUpdated {
+            repo_id: &key.0,
+            token_id: &key.1,
+            old_threshold: self.payout_thresholds.get(&key).map(|old| U128(*old)),
+            new_threshold: threshold,
+        }
+        .emit();
+        match threshold {
+            Some(threshold) => {
+                self.payout_thresholds.insert(key, threshold.0);
+            }
+            None => {
+                self.payout_thresholds.remove(&key);
+            }
+        }
+        self.internal_charge_storage(
+            initial_storage_usage,
+            env::attached_deposit().as_yoctonear(),
+        );
+    }
+
+    // Get the payout threshold of a repo, in NEAR if no token is given
+    pub fn get_payout_threshold(
+        &self,
+        repo_id: String,
+        token_id: Option<AccountId>,
+    ) -> Option<U128> {
+        self.payout_thresholds
+            .get(&(repo_id, token_id))
+            .map(|threshold| U128(*threshold))
+    }
+
+    // Repo admin overrides the daily payout limit of the repo, None restores the owner default
+    // The admin pays for the storage the limit uses, the excess deposit is refunded
+    #[payable]
+    pub fn set_repo_payout_limit(&mut self, repo_id: String, limit: Option<RateLimit>) {
+        self.require_repo_role(
+            &repo_id,
+            &env::predecessor_account_id(),
+            &[MaintainerRole::Admin],
+            "Only repo admins can set the payout limit",
+        );
+
+        let initial_storage_usage = self.internal_storage_usage();
+        Event::RepoPayoutLimitUpdated {
+            repo_id: &repo_id,
+            old_limit: self.repo_payout_limits.get(&repo_id),
+            new_limit: limit.as_ref(),
+        }
+        .emit();
+        match limit {
+            Some(limit) => {
+                self.repo_payout_limits.insert(repo_id, limit);
+            }
+            None => {
+                self.repo_payout_limits.remove(&repo_id);
+            }
+        }
+        self.internal_charge_storage(
+            initial_storage_usage,
+            env::attached_deposit().as_yoctonear(),
+        );
+    }
+
+    // Repo admin approves a payout above the threshold, the transfers fire right away
+    // A payout past its approval timeout is cancelled instead and false is returned
+    // The admin pays for the storage the approval adds: the balance of a payout held for a GitHub
+    // user, or the sponsor refunds of a transferred payout
+    #[payable]
+    pub fn approve_payout(&mut self, bounty_id: BountyId) -> PromiseOrValue<bool> {
+        require!(!self.paused.payouts, "Payouts are paused");
+        let mut bounty = self.internal_get_bounty(bounty_id);
+        let approved_by = env::predecessor_account_id();
+        self.require_repo_role(
+            &bounty.repo_id,
+            &approved_by,
+            &[MaintainerRole::Admin],
+            "Only repo admins can approve payouts",
+        );
+        let pending = self
+            .pending_payouts
+            .get(&bounty_id)
+            .cloned()
+            .expect("Payout is not pending approval");
+        if block_timestamp_ms() >= pending.expires_at_ms {
+            self.internal_expire_pending_payout(bounty_id);
+            return PromiseOrValue::Value(false);
+        }
+
+        self.pending_payouts.remove(&bounty_id);
+        Event::PayoutApproved {
+            bounty_id,
+            repo_id: &bounty.repo_id,
+            pr_number: pending.pr_number,
+            approved_by: &approved_by,
+        }
+        .emit();
+        bounty.status = BountyStatus::Claimed;
+        bounty.updated_at_ms = block_timestamp_ms();
+        self.bounty_records.insert(bounty_id, bounty.clone());
+
+        let payout = self
+            .payouts
+            .get(&(bounty.repo_id.clone(), pending.pr_number))
+            .cloned()
+            .unwrap();
+        let initial_storage_usage = self.internal_storage_usage();
+        let token_id = bounty.token_id.clone();
+        let held = payout.recipients.is_empty();
+        // Sponsor refunds of a transferred payout are credited once the transfers resolve,
+        // so they are charged now
+        let refund_bytes = if held {
+            self.internal_hold_payout(bounty, pending.pr_number);
+            0
+        } else {
+            self.internal_refund_storage_bytes(bounty_id, &token_id)
+        };
+        self.internal_charge_storage(
+            initial_storage_usage - refund_bytes,
+            env::attached_deposit().as_yoctonear(),
+        );
+        if held {
+            return PromiseOrValue::Value(true);
+        }
+        PromiseOrValue::Promise(self.internal_pay_out(
+            bounty_id,
+            &token_id,
+            payout.recipients,
+            pending.pr_number,
+        ))
+    }
+
+    // Repo admin rejects a pending payout, the bounty is reopened so the PR can be paid again
+    pub fn reject_payout(&mut self, bounty_id: BountyId) {
+        let repo_id = self.internal_get_bounty(bounty_id).repo_id;
+        let rejected_by = env::predecessor_account_id();
+        self.require_repo_role(
+            &repo_id,
+            &rejected_by,
+            &[MaintainerRole::Admin],
+            "Only repo admins can reject payouts",
+        );
+
+        let (bounty, pr_number) = self.internal_cancel_pending_payout(bounty_id);
+        Event::PayoutRejected {
+            bounty_id,
+            repo_id: &bounty.repo_id,
+            pr_number,
+            rejected_by: &rejected_by,
+        }
+        .emit();
+    }
+
+    // Anyone can cancel a pending payout once its approval timeout has passed
+    pub fn cancel_expired_payout(&mut self, bounty_id: BountyId) {
+        let pending = self
+            .pending_payouts
+            .get(&bounty_id)
+            .expect("Payout is not pending approval");
+        require!(
+            block_timestamp_ms() >= pending.expires_at_ms,
+            "Payout approval has not timed out"
+        );
+        self.internal_expire_pending_payout(bounty_id);
+    }
+
+    // ===== GITHUB ACCOUNTS =====
+
+    // Agent links a GitHub user id to a NEAR account whose holder proved control of it to the agent
+    // Replaces any previous link of the GitHub user, but an account linked to another GitHub user
+    // can only replace its link itself, see link_own_github_account
+    // Balances held for the GitHub user stay held until the account claims them or links itself
+    // The agent pays for the storage the link uses, the excess deposit is refunded
+    #[payable]
+    pub fn link_github_account(
+        &mut self,
+        github_user_id: GithubUserId,
+        account_id: AccountId,
+    ) -> PromiseOrValue<bool> {
         // Require the caller to be a valid agent, panic if not
         if let Some(failure_promise) = self.require_valid_agent() {
-            return failure_promise;
+            return PromiseOrValue::Promise(failure_promise);
         }
+        require!(
+            self.github_user_ids
+                .get(&account_id)
+                .is_none_or(|linked| *linked == github_user_id),
+            "Account is linked to another GitHub user, only the account can replace its link"
+        );
 
+        let initial_storage_usage = self.internal_storage_usage();
+        self.internal_link_github_account(github_user_id, account_id);
+        self.internal_charge_storage(
+            initial_storage_usage,
+            env::attached_deposit().as_yoctonear(),
+        );
+        PromiseOrValue::Value(true)
+    }
+
+    // An account links itself to a GitHub user id
+    // The agent signs the "link" github_message after verifying the caller controls the GitHub account,
+    // the signature is only valid for the caller and until expires_at_ms
+    // Replaces any previous link of the GitHub user or of the caller
+    // Balances held for the GitHub user are transferred to the caller, see claim
+    // The caller pays for the storage the link uses, the excess deposit is refunded
+    #[payable]
+    pub fn link_own_github_account(
+        &mut self,
+        github_user_id: GithubUserId,
+        agent_id: AccountId,
+        expires_at_ms: U64,
+        signature: Signature,
+    ) -> PromiseOrValue<bool> {
         require!(
-            self.repo_maintainers.contains_key(&repo_id),
-            "Repo not registered"
+            block_timestamp_ms() < expires_at_ms.0,
+            "Link message has expired"
         );
 
-        let bounty = *self
-            .bounties
-            .get(&repo_id)
-            .expect("No bounty funds for repo");
-        require!(bounty >= amount.0, "Insufficient bounty funds");
+        let account_id = env::predecessor_account_id();
+        let message = github_message("link", github_user_id, &account_id, expires_at_ms.0);
+        if let Some(failure_promise) = self.require_agent_signature(&agent_id, &message, &signature)
+        {
+            return PromiseOrValue::Promise(failure_promise);
+        }
 
-        self.bounties.insert(repo_id, bounty - amount.0);
+        let initial_storage_usage = self.internal_storage_usage();
+        self.internal_link_github_account(github_user_id, account_id.clone());
+        self.internal_charge_storage(
+            initial_storage_usage,
+            env::attached_deposit().as_yoctonear(),
+        );
+        self.internal_release_on_link(github_user_id, &account_id)
+    }
 
-        Promise::new(recipient).transfer(NearToken::from_yoctonear(amount.0))
+    // An account removes its own link, payouts for its GitHub user are held again until it relinks
+    pub fn unlink_github_account(&mut self) {
+        let github_user_id = *self
+            .github_user_ids
+            .get(&env::predecessor_account_id())
+            .expect("Account is not linked to a GitHub user");
+        self.internal_unlink_github_account(github_user_id);
     }
 
-    // Maintainer withdraws their remaining bounty funds
-    pub fn withdraw_bounty(&mut self, repo_id: String, amount: U128) -> Promise {
-        let maintainer = self
-            .repo_maintainers
-            .get(&repo_id)
-            .expect("Repo not registered");
+    // ===== CLAIMS =====
+
+    // Agent holds a bounty for a contributor who has not linked a NEAR account yet
+    // The contributor claims it later, unclaimed funds go back to the repo pool after claim_expiry_ms
+    // Each PR is paid once, a duplicate call for the same PR panics
+    // Like release_bounty, an amount above the repo threshold waits for approve_payout
+    // The agent pays for the storage the payout record and the held balance use
+    #[payable]
+    pub fn release_bounty_to_github_user(
+        &mut self,
+        bounty_id: BountyId,
+        github_user_id: GithubUserId,
+        amount: U128,
+        pr_number: u64,
+        merge_commit_sha: String,
+    ) -> PromiseOrValue<bool> {
+        require!(!self.paused.payouts, "Payouts are paused");
+
+        // Require the caller to be a valid agent, panic if not
+        if let Some(failure_promise) = self.require_valid_agent() {
+            return PromiseOrValue::Promise(failure_promise);
+        }
+
+        let initial_storage_usage = self.internal_storage_usage();
+        let (bounty, _) = self.internal_claim_bounty(
+            bounty_id,
+            amount.0,
+            &[],
+            Some(github_user_id),
+            pr_number,
+            &merge_commit_sha,
+        );
+        let held = bounty.status != BountyStatus::PendingApproval;
+        if held {
+            self.internal_hold_payout(bounty, pr_number);
+        }
+        self.internal_charge_storage(
+            initial_storage_usage,
+            env::attached_deposit().as_yoctonear(),
+        );
+        PromiseOrValue::Value(held)
+    }
+
+    // Contributor claims everything held for their GitHub user id
+    // The agent signs the "claim" github_message after verifying the caller controls the GitHub account,
+    // the signature is only valid for the caller and until expires_at_ms
+    pub fn claim(
+        &mut self,
+        github_user_id: GithubUserId,
+        agent_id: AccountId,
+        expires_at_ms: U64,
+        signature: Signature,
+    ) -> Promise {
+        require!(!self.paused.payouts, "Payouts are paused");
         require!(
-            env::predecessor_account_id() == *maintainer,
-            "Only the repo maintainer can withdraw the bounty"
+            block_timestamp_ms() < expires_at_ms.0,
+            "Claim message has expired"
         );
 
-        let bounty = *self
-            .bounties
-            .get(&repo_id)
-            .expect("No bounty funds for repo");
-        require!(bounty >= amount.0, "Insufficient bounty funds");
+        let account_id = env::predecessor_account_id();
+        let message = github_message("claim", github_user_id, &account_id, expires_at_ms.0);
+        if let Some(failure_promise) = self.require_agent_signature(&agent_id, &message, &signature)
+        {
+            return failure_promise;
+        }
 
-        self.bounties.insert(repo_id, bounty - amount.0);
+        self.internal_pay_claimable_balances(github_user_id, &account_id)
+            .unwrap_or_else(|| env::panic_str("Nothing to claim"))
+    }
+
+    // Hold the balances of every failed transfer for the GitHub user again, one result per token
+    // Returns whether all transfers succeeded
+    #[private]
+    pub fn on_claimed(
+        &mut self,
+        github_user_id: GithubUserId,
+        account_id: AccountId,
+        balances: Vec<ClaimableBalanceView>,
+    ) -> bool {
+        let balances: Vec<ClaimableBalance> =
+            balances.into_iter().map(ClaimableBalance::from).collect();
+
+        let mut success = true;
+        for (index, token_id) in claim_totals(&balances).into_keys().enumerate() {
+            if !matches!(
+                env::promise_result_checked(index as u64, 0),
+                Err(PromiseError::Failed)
+            ) {
+                continue;
+            }
+            success = false;
+            for balance in balances
+                .iter()
+                .filter(|balance| balance.token_id == token_id)
+            {
+                Event::PayoutFailed {
+                    repo_id: &balance.repo_id,
+                    bounty_id: Some(balance.bounty_id),
+                    recipient: &account_id,
+                    token_id: &balance.token_id,
+                    amount: U128(balance.amount),
+                }
+                .emit();
+                self.internal_add_claimable_balance(github_user_id, balance.clone());
+            }
+        }
+        success
+    }
+
+    // Return the expired balances of a GitHub user to their repo pools, anyone can call this
+    pub fn return_expired_claims(&mut self, github_user_id: GithubUserId) {
+        let balances = self.internal_take_claimable_balances(github_user_id);
+        if !balances.is_empty() {
+            self.claimable_balances.insert(github_user_id, balances);
+        }
+    }
 
-        Promise::new(maintainer.clone()).transfer(NearToken::from_yoctonear(amount.0))
+    // Get the balances held for a GitHub user, including expired ones not yet returned
+    pub fn get_claimable_balances(
+        &self,
+        github_user_id: GithubUserId,
+    ) -> Vec<ClaimableBalanceView> {
+        self.claimable_balances
+            .get(&github_user_id)
+            .map(|balances| balances.iter().map(ClaimableBalanceView::from).collect())
+            .unwrap_or_default()
     }
 }
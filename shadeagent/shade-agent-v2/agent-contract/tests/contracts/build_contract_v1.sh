@@ -0,0 +1,17 @@
+#!/bin/bash
+# Build tests/contracts/contract_v1.wasm from the baseline commit, the contract deployed before
+# state versioning, whose state migrate upgrades from the ContractV1 layout
+# The upgrade tests deploy it and upgrade to the current code, CI runs this before cargo test
+set -euo pipefail
+
+BASELINE_COMMIT=a1fa7a7
+CONTRACT_DIR=shadeagent/shade-agent-v2/agent-contract
+
+REPO_ROOT=$(git rev-parse --show-toplevel)
+WORKTREE=$(mktemp -d)
+trap 'git -C "$REPO_ROOT" worktree remove --force "$WORKTREE"' EXIT
+
+git -C "$REPO_ROOT" worktree add --detach "$WORKTREE" "$BASELINE_COMMIT"
+(cd "$WORKTREE/$CONTRACT_DIR" && cargo near build non-reproducible-wasm)
+cp "$WORKTREE/$CONTRACT_DIR/target/near/shade_contract_template.wasm" \
+    "$REPO_ROOT/$CONTRACT_DIR/tests/contracts/contract_v1.wasm"
@@ -33,7 +33,8 @@ async fn test_cross_contract_call_to_mpc() -> Result<(), Box<dyn std::error::Err
             "owner_id": genesis_account_id,
             "mpc_contract_id": mpc_contract_id,
             "requires_tee": false,
-            "attestation_expiration_time_ms": "100000"
+            "attestation_expiration_time_ms": "100000",
+            "upgrade_delay_ms": "0"
         })),
         None,
     )
@@ -91,7 +92,7 @@ async fn test_cross_contract_call_to_mpc() -> Result<(), Box<dyn std::error::Err
     .await?
     .assert_success();
 
-    // Register agent with 0.005 NEAR deposit
+    // Register agent with 0.01 NEAR deposit, the excess is refunded
     let _ = call_transaction(
         &contract_id,
         "register_agent",
@@ -101,7 +102,7 @@ async fn test_cross_contract_call_to_mpc() -> Result<(), Box<dyn std::error::Err
         &agent_id,
         &agent_signer,
         &network_config,
-        Some(helpers::DEPOSIT_005_NEAR),
+        Some(helpers::DEPOSIT_STORAGE),
     )
     .await?
     .assert_success();
@@ -12,12 +12,21 @@ use tokio::time::{Duration, sleep};
 // Deposit constants for integration tests
 #[allow(dead_code)]
 pub const DEPOSIT_005_NEAR: NearToken = NearToken::from_yoctonear(5_000_000_000_000_000_000_000); // 0.005 NEAR
+// Covers the storage of an agent or repo registration, the excess is refunded
+#[allow(dead_code)]
+pub const DEPOSIT_STORAGE: NearToken = NearToken::from_yoctonear(10_000_000_000_000_000_000_000); // 0.01 NEAR
 
 #[allow(dead_code)]
 pub const CONTRACT_WASM_PATH: &str = concat!(
     env!("CARGO_MANIFEST_DIR"),
     "/target/near/shade_contract_template.wasm"
 );
+// The baseline contract, before state versioning, built by tests/contracts/build_contract_v1.sh
+#[allow(dead_code)]
+pub const CONTRACT_V1_WASM_PATH: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/contracts/contract_v1.wasm"
+);
 #[allow(dead_code)]
 pub const MOCK_MPC_WASM_PATH: &str =
     concat!(env!("CARGO_MANIFEST_DIR"), "/tests/contracts/mock_mpc.wasm");
@@ -176,7 +185,8 @@ pub async fn deploy_contract_default(
             "owner_id": owner,
             "mpc_contract_id": mpc_contract,
             "requires_tee": false,
-            "attestation_expiration_time_ms": "100000"
+            "attestation_expiration_time_ms": "100000",
+            "upgrade_delay_ms": "0"
         })),
         None,
     )
@@ -216,6 +226,73 @@ pub async fn deploy_contract_default(
     Ok(contract_id)
 }
 
+// Deploy the baseline contract with its own init args and approve the default measurements
+// and PPID, so agents can register in local mode before the upgrade
+#[allow(dead_code)]
+pub async fn deploy_contract_v1(
+    network_config: &NetworkConfig,
+    genesis_account_id: &AccountId,
+    genesis_signer: &Arc<Signer>,
+) -> Result<AccountId, Box<dyn std::error::Error + Send + Sync>> {
+    if !std::path::Path::new(CONTRACT_V1_WASM_PATH).exists() {
+        return Err(format!(
+            "{CONTRACT_V1_WASM_PATH} not found, build it with tests/contracts/build_contract_v1.sh"
+        )
+        .into());
+    }
+    let owner = genesis_account_id.clone();
+    let mpc_contract: AccountId = "mpc-contract".parse().unwrap();
+
+    let contract_id = deploy_contract(
+        network_config,
+        genesis_account_id,
+        genesis_signer,
+        CONTRACT_V1_WASM_PATH,
+        Some("new"),
+        Some(json!({
+            "owner_id": owner,
+            "mpc_contract_id": mpc_contract,
+            "requires_tee": false,
+            "attestation_expiration_time_ms": "100000"
+        })),
+        None,
+    )
+    .await?;
+
+    // The baseline approve_measurements takes no label or source
+    let _ = call_transaction(
+        &contract_id,
+        "approve_measurements",
+        json!({ "measurements": default_measurements_json() }),
+        genesis_account_id,
+        genesis_signer,
+        network_config,
+        None,
+    )
+    .await?
+    .into_result()
+    .map_err(|e| -> Box<dyn std::error::Error + Send + Sync> {
+        format!("approve_measurements failed: {:?}", e).into()
+    })?;
+
+    let _ = call_transaction(
+        &contract_id,
+        "approve_ppids",
+        default_ppids_json(),
+        genesis_account_id,
+        genesis_signer,
+        network_config,
+        None,
+    )
+    .await?
+    .into_result()
+    .map_err(|e| -> Box<dyn std::error::Error + Send + Sync> {
+        format!("approve_ppids failed: {:?}", e).into()
+    })?;
+
+    Ok(contract_id)
+}
+
 /// Returns JSON for mock full measurements (all zeros). Used for local mode registration.
 /// Uses the same type as the contract so serialization matches exactly.
 #[allow(dead_code)]
@@ -227,7 +304,11 @@ pub fn default_measurements_json() -> serde_json::Value {
 /// Args for approve_measurements with default measurements (parameter name required by contract).
 #[allow(dead_code)]
 pub fn approve_measurements_default_args() -> serde_json::Value {
-    json!({ "measurements": default_measurements_json() })
+    json!({
+        "measurements": default_measurements_json(),
+        "label": "agent v1",
+        "source": "sha256:default"
+    })
 }
 
 /// Returns JSON for approve_ppids with default PPID (16 zero bytes = 32 hex chars).
@@ -253,7 +334,11 @@ pub fn non_default_measurements_json() -> serde_json::Value {
 /// Returns JSON for approve_measurements with non-default measurements.
 #[allow(dead_code)]
 pub fn approve_non_default_measurements_args() -> serde_json::Value {
-    json!({ "measurements": non_default_measurements_json() })
+    json!({
+        "measurements": non_default_measurements_json(),
+        "label": "agent v2",
+        "source": "sha256:non-default"
+    })
 }
 
 /// Returns JSON for approve_ppids with non-default PPID (differs from 16 zero bytes).
@@ -374,6 +459,29 @@ pub async fn deploy_mock_mpc_contract(
     Ok(mpc_contract_id)
 }
 
+/// Stage an upgrade to the given WASM code by proposing its sha256 hash
+#[allow(dead_code)]
+pub async fn propose_upgrade(
+    contract_id: &AccountId,
+    wasm_bytes: &[u8],
+    owner_id: &AccountId,
+    owner_signer: &Arc<Signer>,
+    network_config: &NetworkConfig,
+) -> Result<ExecutionFinalResult, Box<dyn std::error::Error + Send + Sync>> {
+    use sha2::Digest;
+    let code_hash = hex::encode(sha2::Sha256::digest(wasm_bytes));
+    call_transaction(
+        contract_id,
+        "propose_upgrade",
+        json!({ "code_hash": code_hash }),
+        owner_id,
+        owner_signer,
+        network_config,
+        None,
+    )
+    .await
+}
+
 /// Extract and parse EVENT_JSON logs from a transaction result
 /// Returns a vector of parsed event JSON objects
 #[allow(dead_code)]
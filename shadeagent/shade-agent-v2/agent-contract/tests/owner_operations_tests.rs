@@ -41,10 +41,10 @@ async fn test_owner_transfer_and_new_owner_operations()
         "Initial owner should be genesis account"
     );
 
-    // Transfer ownership
+    // Propose the new owner
     let _ = call_transaction(
         &contract_id,
-        "update_owner_id",
+        "propose_owner",
         json!({
             "owner_id": new_owner_id
         }),
@@ -58,6 +58,35 @@ async fn test_owner_transfer_and_new_owner_operations()
 
     sleep(Duration::from_millis(500)).await;
 
+    // Ownership is not transferred until the new owner accepts
+    let contract_info: Data<ContractInfo> = call_view(
+        &contract_id,
+        "get_contract_info",
+        json!({}),
+        &network_config,
+    )
+    .await?;
+    assert_eq!(contract_info.data.owner_id, genesis_account_id);
+    assert_eq!(
+        contract_info.data.pending_owner_id,
+        Some(new_owner_id.clone())
+    );
+
+    // Accept the ownership
+    let _ = call_transaction(
+        &contract_id,
+        "accept_ownership",
+        json!({}),
+        &new_owner_id,
+        &new_owner_signer,
+        &network_config,
+        None,
+    )
+    .await?
+    .assert_success();
+
+    sleep(Duration::from_millis(500)).await;
+
     // Verify owner was updated using get_contract_info
     let contract_info: Data<ContractInfo> = call_view(
         &contract_id,
@@ -1,8 +1,10 @@
 mod helpers;
 
 use helpers::*;
-use near_api::Data;
+use near_api::{AccountId, Data, NearToken};
 use serde_json::json;
+use shade_attestation::attestation::create_mock_dstack_attestation;
+use shade_contract_template::{AgentValidity, AgentView, BountyView, ContractInfo, STATE_VERSION};
 use tokio::time::{Duration, sleep};
 
 #[tokio::test]
@@ -22,6 +24,17 @@ async fn test_update_contract() -> Result<(), Box<dyn std::error::Error + Send +
         "/tests/contracts/contract_update.wasm"
     ))?;
 
+    // Stage the upgrade, the test contract is deployed without an upgrade delay
+    propose_upgrade(
+        &contract_id,
+        &wasm_bytes,
+        &genesis_account_id,
+        &genesis_signer,
+        &network_config,
+    )
+    .await?
+    .assert_success();
+
     // Pass gas (10 TGas) + WASM bytes concatenated
     // First 8 bytes: gas as u64 little-endian, then WASM code
     let gas_value: u64 = 10;
@@ -94,3 +107,251 @@ async fn test_update_contract() -> Result<(), Box<dyn std::error::Error + Send +
 
     Ok(())
 }
+
+/// Tests that upgrading the baseline contract, holding agents and funded repos, migrates its
+/// state from the ContractV1 layout and keeps all of it
+/// The baseline is built by tests/contracts/build_contract_v1.sh
+#[tokio::test]
+async fn test_update_contract_keeps_state() -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+{
+    let sandbox = near_sandbox::Sandbox::start_sandbox().await?;
+    let network_config = create_network_config(&sandbox);
+    let (genesis_account_id, genesis_signer) = setup_genesis_account().await;
+
+    let contract_id =
+        deploy_contract_v1(&network_config, &genesis_account_id, &genesis_signer).await?;
+
+    sleep(Duration::from_millis(200)).await;
+
+    // Register an agent
+    let (agent_id, agent_signer) = create_user_account(
+        &network_config,
+        &genesis_account_id,
+        &genesis_signer,
+        "agent",
+    )
+    .await?;
+
+    let _ = call_transaction(
+        &contract_id,
+        "whitelist_agent_for_local",
+        json!({ "account_id": agent_id }),
+        &genesis_account_id,
+        &genesis_signer,
+        &network_config,
+        None,
+    )
+    .await?
+    .assert_success();
+
+    let _ = call_transaction(
+        &contract_id,
+        "register_agent",
+        json!({
+            "attestation": serde_json::to_value(create_mock_dstack_attestation()).unwrap()
+        }),
+        &agent_id,
+        &agent_signer,
+        &network_config,
+        Some(DEPOSIT_STORAGE),
+    )
+    .await?
+    .assert_success();
+
+    // The agent registers a repo and the maintainer funds its pool
+    // The baseline register_repo takes no deposit and fund_bounty pools the whole deposit
+    let (maintainer_id, maintainer_signer) = create_user_account(
+        &network_config,
+        &genesis_account_id,
+        &genesis_signer,
+        "maintainer",
+    )
+    .await?;
+
+    let _ = call_transaction(
+        &contract_id,
+        "register_repo",
+        json!({ "repo_id": "owner/repo", "maintainer_id": maintainer_id }),
+        &agent_id,
+        &agent_signer,
+        &network_config,
+        None,
+    )
+    .await?
+    .assert_success();
+
+    let _ = call_transaction(
+        &contract_id,
+        "fund_bounty",
+        json!({ "repo_id": "owner/repo" }),
+        &maintainer_id,
+        &maintainer_signer,
+        &network_config,
+        Some(NearToken::from_near(1)),
+    )
+    .await?
+    .assert_success();
+
+    sleep(Duration::from_millis(200)).await;
+
+    // Upgrade to the current contract code with the baseline update_contract, which takes
+    // no staged hash, migrate gets 50 TGas
+    let wasm_bytes = std::fs::read(CONTRACT_WASM_PATH)?;
+    let gas_value: u64 = 50;
+    let mut input = gas_value.to_le_bytes().to_vec();
+    input.extend_from_slice(&wasm_bytes);
+
+    call_transaction_raw(
+        &contract_id,
+        "update_contract",
+        input,
+        &genesis_account_id,
+        &genesis_signer,
+        &network_config,
+    )
+    .await?
+    .into_result()
+    .expect("update_contract should succeed");
+
+    sleep(Duration::from_millis(200)).await;
+
+    let contract_info: Data<ContractInfo> = call_view(
+        &contract_id,
+        "get_contract_info",
+        json!({}),
+        &network_config,
+    )
+    .await?;
+    assert_eq!(contract_info.data.state_version, STATE_VERSION);
+    assert_eq!(contract_info.data.owner_id, genesis_account_id);
+
+    let agent: Data<Option<AgentView>> = call_view(
+        &contract_id,
+        "get_agent",
+        json!({ "account_id": agent_id }),
+        &network_config,
+    )
+    .await?;
+    let agent = agent.data.expect("Agent should survive the upgrade");
+    assert!(matches!(agent.validity, AgentValidity::Valid));
+
+    let maintainer: Data<AccountId> = call_view(
+        &contract_id,
+        "get_repo_maintainer",
+        json!({ "repo_id": "owner/repo" }),
+        &network_config,
+    )
+    .await?;
+    assert_eq!(maintainer.data, maintainer_id);
+
+    let pool: Data<String> = call_view(
+        &contract_id,
+        "get_bounty",
+        json!({ "repo_id": "owner/repo" }),
+        &network_config,
+    )
+    .await?;
+    assert_eq!(
+        pool.data,
+        NearToken::from_near(1).as_yoctonear().to_string()
+    );
+
+    // The upgraded contract keeps working with the current API: the agent registers another
+    // repo and the maintainer funds an issue bounty
+    let _ = call_transaction(
+        &contract_id,
+        "register_repo",
+        json!({ "repo_id": "owner/other", "maintainer_id": maintainer_id }),
+        &agent_id,
+        &agent_signer,
+        &network_config,
+        Some(DEPOSIT_STORAGE),
+    )
+    .await?
+    .assert_success();
+
+    let _ = call_transaction(
+        &contract_id,
+        "fund_bounty",
+        json!({
+            "repo_id": "owner/repo",
+            "number": 7,
+            "amount": NearToken::from_near(2).as_yoctonear().to_string()
+        }),
+        &maintainer_id,
+        &maintainer_signer,
+        &network_config,
+        Some(NearToken::from_near(2).saturating_add(DEPOSIT_STORAGE)),
+    )
+    .await?
+    .assert_success();
+
+    let bounty: Data<Option<BountyView>> = call_view(
+        &contract_id,
+        "get_issue_bounty",
+        json!({ "repo_id": "owner/repo", "number": 7 }),
+        &network_config,
+    )
+    .await?;
+    let bounty = bounty.data.expect("Issue bounty should be funded");
+    assert_eq!(bounty.amount.0, NearToken::from_near(2).as_yoctonear());
+
+    Ok(())
+}
+
+/// Tests that update_contract refuses code that was not staged with propose_upgrade
+#[tokio::test]
+async fn test_update_contract_requires_staged_hash()
+-> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let sandbox = near_sandbox::Sandbox::start_sandbox().await?;
+    let network_config = create_network_config(&sandbox);
+    let (genesis_account_id, genesis_signer) = setup_genesis_account().await;
+
+    let contract_id =
+        deploy_contract_default(&network_config, &genesis_account_id, &genesis_signer).await?;
+
+    sleep(Duration::from_millis(200)).await;
+
+    let wasm_bytes = std::fs::read(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/contracts/contract_update.wasm"
+    ))?;
+    let mut input = 10u64.to_le_bytes().to_vec();
+    input.extend_from_slice(&wasm_bytes);
+
+    // Nothing staged yet
+    let _ = call_transaction_raw(
+        &contract_id,
+        "update_contract",
+        input.clone(),
+        &genesis_account_id,
+        &genesis_signer,
+        &network_config,
+    )
+    .await?
+    .assert_failure();
+
+    // A different code hash is staged
+    let _ = propose_upgrade(
+        &contract_id,
+        b"other code",
+        &genesis_account_id,
+        &genesis_signer,
+        &network_config,
+    )
+    .await?
+    .assert_success();
+
+    let _ = call_transaction_raw(
+        &contract_id,
+        "update_contract",
+        input,
+        &genesis_account_id,
+        &genesis_signer,
+        &network_config,
+    )
+    .await?
+    .assert_failure();
+
+    Ok(())
+}
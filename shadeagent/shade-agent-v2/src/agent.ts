@@ -0,0 +1,14 @@
+import type { ShadeClient } from "@neardefi/shade-agent-js";
+
+let agent: ShadeClient | null = null;
+
+export function setAgent(client: ShadeClient): void {
+  agent = client;
+}
+
+export function getAgent(): ShadeClient {
+  if (!agent) {
+    throw new Error("Shade agent not initialized");
+  }
+  return agent;
+}
@@ -7,6 +7,7 @@ import bountyRouter from "./routes/bounty";
 import { getPayoutStats } from "./store/payoutLog";
 import swaggerUi from "swagger-ui-express";
 import { swaggerSpec } from "./config/swagger.js";
+import { getAgent, setAgent } from "./agent";
 
 if (process.env.NODE_ENV !== "production") {
   dotenv.config();
@@ -22,18 +23,9 @@ if (!agentContractId || !sponsorAccountId || !sponsorPrivateKey) {
   );
 }
 
-export let agent: ShadeClient | null = null;
-
-export function getAgent(): ShadeClient {
-  if (!agent) {
-    throw new Error("Shade agent not initialized");
-  }
-  return agent;
-}
-
 async function start() {
   const networkId = process.env.NETWORK_ID as "testnet" | "mainnet" | undefined || "testnet";
-  agent = await ShadeClient.create({
+  const agent = await ShadeClient.create({
     networkId,
     agentContractId: agentContractId!,
     sponsor: {
@@ -43,6 +35,7 @@ async function start() {
     numKeys: 10,
     derivationPath: "default",
   });
+  setAgent(agent);
   // Fund the agent account so it can exist on-chain and manage keys
   console.log("Mocking agent funding...");
   // await agent.fund(0.1); // 0.2 NEAR
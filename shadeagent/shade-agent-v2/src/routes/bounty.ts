@@ -1,5 +1,12 @@
 import express, { Request, Response } from "express";
-import { getBounty, releaseBounty, registerRepo } from "../services/bounty";
+import {
+  getBounty,
+  GithubMessageAction,
+  releaseBounty,
+  registerRepo,
+  signGithubMessage,
+} from "../services/bounty";
+import { getGithubUserId } from "../services/github";
 import { getPayouts } from "../store/payoutLog";
 
 const router = express.Router();
@@ -9,7 +16,16 @@ const router = express.Router();
  * /api/repo/register:
  *   post:
  *     summary: Register a repository with the Shade Agent
+ *     description: |
+ *       Called by the Backend once it has checked the caller owns the repository.
+ *       Requires the x-agent-secret header shared with the Backend.
  *     tags: [Bounty]
+ *     parameters:
+ *       - in: header
+ *         name: x-agent-secret
+ *         required: true
+ *         schema:
+ *           type: string
  *     requestBody:
  *       required: true
  *       content:
@@ -31,10 +47,18 @@ const router = express.Router();
  *         description: Repository registered successfully
  *       400:
  *         description: Missing required fields
+ *       401:
+ *         description: Invalid secret
  *       500:
  *         description: Registration failed
  */
 router.post("/api/repo/register", async (req: Request, res: Response) => {
+  // The agent signs register_repo, so only the Backend may ask for it
+  const secret = process.env.MAINTAINER_SECRET;
+  if (!secret || req.header("x-agent-secret") !== secret) {
+    return res.status(401).json({ success: false, error: "Invalid secret" });
+  }
+
   const { repo, maintainerNearId } = req.body || {};
 
   if (!repo || !maintainerNearId) {
@@ -91,6 +115,7 @@ router.get("/api/bounty/:owner/:repo", async (req: Request, res: Response) => {
  *               - repo
  *               - contributorWallet
  *               - prNumber
+ *               - mergeCommitSha
  *               - secret
  *             properties:
  *               repo:
@@ -101,6 +126,9 @@ router.get("/api/bounty/:owner/:repo", async (req: Request, res: Response) => {
  *                 example: contributor.testnet
  *               prNumber:
  *                 type: number
+ *               mergeCommitSha:
+ *                 type: string
+ *                 description: Merge commit of the PR, each PR is paid once
  *               secret:
  *                 type: string
  *                 description: Maintainer secret for authorization
@@ -118,12 +146,13 @@ router.get("/api/bounty/:owner/:repo", async (req: Request, res: Response) => {
  *         description: Release failed
  */
 router.post("/api/bounty/release", async (req: Request, res: Response) => {
-  const { repo, contributorWallet, prNumber, secret, amount } = req.body || {};
+  const { repo, contributorWallet, prNumber, mergeCommitSha, secret, amount } =
+    req.body || {};
 
-  if (!repo || !contributorWallet || !prNumber || !secret) {
+  if (!repo || !contributorWallet || !prNumber || !mergeCommitSha || !secret) {
     return res.status(400).json({
       success: false,
-      error: "repo, contributorWallet, prNumber, and secret are required",
+      error: "repo, contributorWallet, prNumber, mergeCommitSha, and secret are required",
     });
   }
 
@@ -135,12 +164,81 @@ router.post("/api/bounty/release", async (req: Request, res: Response) => {
     repoFullName: repo,
     contributorWallet,
     prNumber: Number(prNumber),
+    mergeCommitSha,
     amount,
   });
 
   return res.status(result.success ? 200 : 500).json(result);
 });
 
+/**
+ * @swagger
+ * /api/github/sign:
+ *   post:
+ *     summary: Sign a claim or link message for a GitHub user
+ *     description: |
+ *       Verifies the GitHub OAuth token belongs to the GitHub user and signs the message the
+ *       contract checks in claim (action "claim") or link_own_github_account (action "link").
+ *       The account then calls the contract method with agentId, githubUserId, expiresAtMs and
+ *       signature before the message expires.
+ *     tags: [Bounty]
+ *     requestBody:
+ *       required: true
+ *       content:
+ *         application/json:
+ *           schema:
+ *             type: object
+ *             required:
+ *               - action
+ *               - accountId
+ *               - githubToken
+ *             properties:
+ *               action:
+ *                 type: string
+ *                 enum: [claim, link]
+ *               accountId:
+ *                 type: string
+ *                 example: contributor.testnet
+ *                 description: Account that calls the contract, the signature is only valid for it
+ *               githubToken:
+ *                 type: string
+ *                 description: GitHub OAuth token of the contributor
+ *     responses:
+ *       200:
+ *         description: Signed message
+ *       400:
+ *         description: Missing or invalid fields
+ *       401:
+ *         description: Invalid GitHub token
+ *       500:
+ *         description: Signing failed
+ */
+router.post("/api/github/sign", async (req: Request, res: Response) => {
+  const { action, accountId, githubToken } = req.body || {};
+
+  if (!["claim", "link"].includes(action) || !accountId || !githubToken) {
+    return res.status(400).json({
+      success: false,
+      error: "action (claim or link), accountId, and githubToken are required",
+    });
+  }
+
+  let githubUserId: number;
+  try {
+    githubUserId = await getGithubUserId(githubToken);
+  } catch {
+    return res.status(401).json({ success: false, error: "Invalid GitHub token" });
+  }
+
+  try {
+    const signed = signGithubMessage(action as GithubMessageAction, githubUserId, accountId);
+    return res.json({ success: true, ...signed });
+  } catch (error: any) {
+    console.error("Failed to sign GitHub message:", error);
+    return res.status(500).json({ success: false, error: error?.message || "Unknown error" });
+  }
+});
+
 /**
  * @swagger
  * /api/bounty/history:
@@ -0,0 +1,169 @@
+import { afterEach, beforeEach, describe, it, mock } from "node:test";
+import assert from "node:assert/strict";
+import crypto from "crypto";
+import { AddressInfo } from "net";
+import { Server } from "http";
+import express from "express";
+import axios from "axios";
+import type { ShadeClient } from "@neardefi/shade-agent-js";
+import { setAgent } from "../agent";
+import webhookRouter from "./webhook";
+
+const REPO = "octocat/hello-world";
+const PR_NUMBER = 42;
+const ISSUE_NUMBER = 12;
+const ONE_NEAR = "1000000000000000000000000";
+
+interface AgentCall {
+  methodName: string;
+  args: Record<string, any>;
+}
+
+// Fake agent over an in-memory set of issue bounties, keyed by issue or PR number
+function fakeAgent(
+  issueBounties: Map<number, { bounty_id: number; amount: string; status: string }>,
+  linkedAccount: string | null = "contributor.testnet",
+) {
+  const calls: AgentCall[] = [];
+  const agent = {
+    accountId: () => "agent.testnet",
+    view: async ({ methodName, args }: AgentCall) => {
+      switch (methodName) {
+        case "get_payout":
+          return null;
+        case "get_github_account":
+          return linkedAccount;
+        case "get_issue_bounty":
+          return issueBounties.get(args.number) ?? null;
+        default:
+          throw new Error(`Unexpected view ${methodName}`);
+      }
+    },
+    call: async ({ methodName, args }: AgentCall) => {
+      calls.push({ methodName, args });
+      if (methodName === "create_pool_bounty") {
+        issueBounties.set(args.pr_number, { bounty_id: 99, amount: ONE_NEAR, status: "Open" });
+      }
+      return { transaction: { hash: "tx-hash" } };
+    },
+  };
+  setAgent(agent as unknown as ShadeClient);
+  return calls;
+}
+
+function mergedPullRequest(body: string) {
+  return {
+    action: "closed",
+    repository: { full_name: REPO },
+    pull_request: {
+      number: PR_NUMBER,
+      title: "Fix the bug",
+      body,
+      diff_url: `https://github.com/${REPO}/pull/${PR_NUMBER}.diff`,
+      user: { id: 1001, login: "contributor" },
+      base: { ref: "main" },
+      head: { ref: "fix" },
+      merged: true,
+      merge_commit_sha: "abc123",
+    },
+  };
+}
+
+describe("webhook merge payout", () => {
+  let server: Server;
+  let url: string;
+  let comments: string[];
+
+  beforeEach(async () => {
+    process.env.GH_WEBHOOK_SECRET = "webhook-secret";
+    process.env.BACKEND_URL = "http://backend.test";
+    process.env.MAINTAINER_SECRET = "maintainer-secret";
+    delete process.env.TEST_CONTRIBUTOR_WALLET;
+
+    comments = [];
+    mock.method(axios, "get", async (requestUrl: string) =>
+      requestUrl.startsWith("http://backend.test")
+        ? { data: { bounty: null, githubToken: "gh-token" } }
+        : { data: "diff --git a/file b/file" },
+    );
+    mock.method(axios, "post", async (_requestUrl: string, body: any) => {
+      if (body?.body) comments.push(body.body);
+      return { data: {} };
+    });
+
+    const app = express();
+    app.use("/api/webhook", express.raw({ type: "application/json" }), webhookRouter);
+    server = app.listen(0);
+    url = `http://127.0.0.1:${(server.address() as AddressInfo).port}/api/webhook`;
+  });
+
+  afterEach(() => {
+    mock.restoreAll();
+    server.close();
+  });
+
+  async function deliver(payload: unknown) {
+    const body = JSON.stringify(payload);
+    const signature = crypto
+      .createHmac("sha256", "webhook-secret")
+      .update(body)
+      .digest("hex");
+    return fetch(url, {
+      method: "POST",
+      headers: {
+        "content-type": "application/json",
+        "x-github-event": "pull_request",
+        "x-hub-signature-256": `sha256=${signature}`,
+      },
+      body,
+    });
+  }
+
+  it("pays the bounty of the issue the PR closes", async () => {
+    const calls = fakeAgent(
+      new Map([[ISSUE_NUMBER, { bounty_id: 7, amount: ONE_NEAR, status: "Open" }]]),
+    );
+
+    const response = await deliver(mergedPullRequest(`Fixes #${ISSUE_NUMBER}`));
+    assert.equal(response.status, 200);
+
+    assert.deepEqual(calls.map((call) => call.methodName), ["release_bounty"]);
+    assert.equal(calls[0].args.bounty_id, 7);
+    assert.equal(calls[0].args.amount, ONE_NEAR);
+    assert.equal(calls[0].args.pr_number, PR_NUMBER);
+    assert.equal(calls[0].args.github_user_id, 1001);
+    assert.ok(comments.some((comment) => comment.includes("Bounty released: 1.0000 NEAR")));
+  });
+
+  it("falls back to the repo pool when the linked issue has no bounty", async () => {
+    const calls = fakeAgent(new Map());
+
+    const response = await deliver(mergedPullRequest(`Fixes #${ISSUE_NUMBER}`));
+    assert.equal(response.status, 200);
+
+    assert.deepEqual(calls.map((call) => call.methodName), [
+      "create_pool_bounty",
+      "release_bounty",
+    ]);
+    assert.equal(calls[0].args.pr_number, PR_NUMBER);
+    assert.equal(calls[0].args.amount, undefined);
+    assert.equal(calls[1].args.bounty_id, 99);
+    assert.equal(calls[1].args.amount, ONE_NEAR);
+  });
+
+  it("holds the payout instead of linking the account named by /link-wallet", async () => {
+    const calls = fakeAgent(
+      new Map([[ISSUE_NUMBER, { bounty_id: 7, amount: ONE_NEAR, status: "Open" }]]),
+      null,
+    );
+
+    const response = await deliver(
+      mergedPullRequest(`Fixes #${ISSUE_NUMBER}\n/link-wallet victim.testnet`),
+    );
+    assert.equal(response.status, 200);
+
+    assert.deepEqual(calls.map((call) => call.methodName), ["release_bounty_to_github_user"]);
+    assert.equal(calls[0].args.github_user_id, 1001);
+    assert.ok(comments.some((comment) => comment.includes(`"accountId": "victim.testnet"`)));
+  });
+});
@@ -2,8 +2,12 @@ import crypto from "crypto";
 import express, { Request, Response } from "express";
 import axios from "axios";
 import { reviewPullRequest } from "../services/review";
-import { postPayoutComment, postReviewComment } from "../services/github";
-import { releaseBounty } from "../services/bounty";
+import { findClosingIssue, postPayoutComment, postReviewComment } from "../services/github";
+import {
+  getGithubAccount,
+  holdBountyForGithubUser,
+  releaseBounty,
+} from "../services/bounty";
 import { getCriteria } from "../store/criteria";
 
 const router = express.Router();
@@ -227,51 +231,63 @@ router.post("/", async (req: Request, res: Response) => {
     console.info(`[Step 3] Diff retrieved (${diff.length} chars).`);
 
     if (action === "closed" && pr?.merged) {
-      let payoutBounty = bounty;
-      // If no bounty, create one with default amount 0.1 NEAR
-      if (!payoutBounty) {
-        const createResp = await axios.post(
-          `${backendUrl}/api/bounty/attach`,
-          {
-            repo: repoFullName,
-            prNumber,
-            amount: "0.1",
-          },
-          { headers: { "x-agent-secret": agentSecret } }
-        );
-        payoutBounty = createResp.data.bounty;
-      }
-
-
-      // Extract /link-wallet <wallet> from PR body or comments
-      let contributorWallet = process.env.TEST_CONTRIBUTOR_WALLET;
-      if (!contributorWallet) {
+      // The bounty of the issue the PR closes is paid, else one attached to the PR,
+      // else a bounty opened out of the repo pool, see releaseBounty
+      const linkedIssue = findClosingIssue(prBody);
+
+      // The contract resolves the recipient from the on-chain GitHub account registry
+      // A /link-wallet <wallet> from the PR author only fills in the linking instructions below,
+      // linking needs proof of control of the account, so the account links itself
+      const testWallet = process.env.TEST_CONTRIBUTOR_WALLET;
+      let linkedAccount: string | null = null;
+      let requestedWallet: string | undefined;
+      if (!testWallet) {
         const linkWalletRegex = /\/link-wallet\s+([a-zA-Z0-9_.-]+\.testnet)/i;
         let match = prBody.match(linkWalletRegex);
-        if (!match && payload?.comment?.body) {
+        if (!match && payload?.comment?.body && payload.comment.user?.id === pr.user.id) {
           match = payload.comment.body.match(linkWalletRegex);
         }
-        if (match) {
-          contributorWallet = match[1];
-        }
+        requestedWallet = match?.[1];
+        linkedAccount = await getGithubAccount(pr.user.id);
       }
 
-      // If no wallet found, do not attempt payout
-      if (!contributorWallet) {
-        console.warn("No /link-wallet found in PR body or comments. Skipping payout.");
-        return res.status(200).json({ status: "skipped-no-wallet" });
+      // If no wallet is linked, hold the payout on-chain until the contributor claims it
+      if (!testWallet && !linkedAccount) {
+        console.warn("No linked wallet for the PR author. Holding payout for claim.");
+        const holdResult = await holdBountyForGithubUser({
+          repoFullName,
+          githubUserId: pr.user.id,
+          prNumber,
+          issueNumber: linkedIssue,
+          mergeCommitSha: pr.merge_commit_sha,
+        });
+        await postPayoutComment({
+          repoFullName,
+          prNumber,
+          message: holdResult.success
+            ? [
+                `💰 Bounty of ${holdResult.amount} NEAR is held for @${contributor} until you link a NEAR account to your GitHub user.`,
+                `To link one, ask the Shade Agent to sign a link message with \`POST /api/github/sign\` and \`{ "action": "link", "accountId": "${requestedWallet ?? "<your-account>"}", "githubToken": "<your GitHub token>" }\`,`,
+                `then call \`link_own_github_account\` on \`${process.env.AGENT_CONTRACT_ID}\` from that account with the returned agentId, githubUserId, expiresAtMs and signature.`,
+                `Linking pays out everything held for you. Unclaimed bounties go back to the repository pool once they expire.`,
+              ].join("\n")
+            : `❌ Bounty hold failed: ${holdResult.error || "Unknown error"}`,
+          token: githubToken,
+        });
+        return res.status(200).json({ status: "held-for-claim" });
       }
 
       // Retry logic for payout (up to 3 times)
-      let payoutResult: { success?: boolean; txHash?: string; error?: string } | undefined = undefined;
+      let payoutResult: { success?: boolean; txHash?: string; amount?: string; error?: string } | undefined = undefined;
       let attempts = 0;
       while (attempts < 3) {
         try {
           payoutResult = await releaseBounty({
             repoFullName,
-            contributorWallet,
+            ...(testWallet ? { contributorWallet: testWallet } : { githubUserId: pr.user.id }),
             prNumber,
-            amount: String(payoutBounty.amount),
+            issueNumber: linkedIssue,
+            mergeCommitSha: pr.merge_commit_sha,
           });
           if (payoutResult?.success) break;
         } catch (err: any) {
@@ -287,7 +303,7 @@ router.post("/", async (req: Request, res: Response) => {
       }
 
       const message = payoutResult.success
-        ? `✅ Bounty released: ${payoutBounty.amount} NEAR\nTx: ${payoutResult.txHash || ""}`
+        ? `✅ Bounty released: ${payoutResult.amount} NEAR\nTx: ${payoutResult.txHash || ""}`
         : `❌ Bounty release failed: ${payoutResult.error || "Unknown error"}`;
 
       await postPayoutComment({
@@ -297,9 +313,9 @@ router.post("/", async (req: Request, res: Response) => {
         token: githubToken,
       });
 
-      if (payoutResult.success && payoutBounty?.id) {
+      if (payoutResult.success && bounty?.id) {
         await axios.post(
-          `${backendUrl}/api/bounty/${payoutBounty.id}/mark-paid`,
+          `${backendUrl}/api/bounty/${bounty.id}/mark-paid`,
           {},
           { headers: { "x-agent-secret": agentSecret } },
         );
@@ -1,8 +1,12 @@
-import { getAgent } from "../index";
+import { KeyPair, KeyPairString } from "@near-js/crypto";
+import { getAgent } from "../agent";
 import { logPayout } from "../store/payoutLog";
 
 const NEAR_TO_YOCTO = BigInt("1000000000000000000000000");
 
+// Attached to calls that grow contract storage, the contract refunds what it does not use
+const STORAGE_DEPOSIT = BigInt("10000000000000000000000"); // 0.01 NEAR
+
 function toYocto(near: string): string {
   const nearNum = parseFloat(near);
   if (isNaN(nearNum)) return "0";
@@ -34,6 +38,7 @@ export async function registerRepo(
         maintainer_id: maintainerNearId,
       },
       gas: BigInt("30000000000000"),
+      deposit: STORAGE_DEPOSIT,
     });
     console.log(`Registered repo: ${repoId} with maintainer: ${maintainerNearId}`);
     return { success: true };
@@ -59,35 +64,240 @@ export async function getBounty(repoFullName: string): Promise<string> {
   }
 }
 
+interface OnChainBounty {
+  bounty_id: number;
+  amount: string;
+  status: "Open" | "Claimed" | "Paid" | "Cancelled" | "Expired" | "PendingApproval";
+}
+
+export async function getIssueBounty(
+  repoFullName: string,
+  number: number,
+): Promise<OnChainBounty | null> {
+  try {
+    const agent = getAgent();
+    return await agent.view<OnChainBounty | null>({
+      methodName: "get_issue_bounty",
+      args: { repo_id: repoFullName, number },
+    });
+  } catch (error) {
+    console.error("Failed to fetch issue bounty:", error);
+    return null;
+  }
+}
+
+interface OnChainPayout {
+  bounty_id: number;
+  merge_commit_sha: string;
+  recipient: string | null;
+  github_user_id: number | null;
+  amount: string;
+  paid_at_ms: string;
+  shares: { account_id: string; basis_points: number; amount: string }[];
+}
+
+export async function getPayout(
+  repoFullName: string,
+  prNumber: number,
+): Promise<OnChainPayout | null> {
+  try {
+    const agent = getAgent();
+    return await agent.view<OnChainPayout | null>({
+      methodName: "get_payout",
+      args: { repo_id: repoFullName, pr_number: prNumber },
+    });
+  } catch (error) {
+    console.error("Failed to fetch payout:", error);
+    return null;
+  }
+}
+
+// Get the NEAR account linked on-chain to a GitHub user, if any
+export async function getGithubAccount(githubUserId: number): Promise<string | null> {
+  try {
+    const agent = getAgent();
+    return await agent.view<string | null>({
+      methodName: "get_github_account",
+      args: { github_user_id: githubUserId },
+    });
+  } catch (error) {
+    console.error("Failed to fetch GitHub account:", error);
+    return null;
+  }
+}
+
+// How long a signed claim or link message can be used
+const GITHUB_MESSAGE_TTL_MS = 10 * 60 * 1000;
+
+export type GithubMessageAction = "claim" | "link";
+
+export interface SignedGithubMessage {
+  agentId: string;
+  githubUserId: number;
+  accountId: string;
+  expiresAtMs: string;
+  signature: string;
+}
+
+// Sign the message the contract checks in claim and link_own_github_account
+// The agent is an implicit account, so its own key is the one whose public key is the account id
+export function signGithubMessage(
+  action: GithubMessageAction,
+  githubUserId: number,
+  accountId: string,
+): SignedGithubMessage {
+  const agent = getAgent();
+  const agentId = agent.accountId();
+  const keyPair = agent
+    .getPrivateKeys({ acknowledgeRisk: true })
+    .map((privateKey) => KeyPair.fromString(privateKey as KeyPairString))
+    .find((key) => Buffer.from(key.getPublicKey().data).toString("hex") === agentId);
+  if (!keyPair) {
+    throw new Error("Agent key not found");
+  }
+
+  const expiresAtMs = String(Date.now() + GITHUB_MESSAGE_TTL_MS);
+  const message = [process.env.AGENT_CONTRACT_ID, action, githubUserId, accountId, expiresAtMs].join(":");
+  const { signature } = keyPair.sign(new TextEncoder().encode(message));
+  return {
+    agentId,
+    githubUserId,
+    accountId,
+    expiresAtMs,
+    signature: Buffer.from(signature).toString("hex"),
+  };
+}
+
+// Find the bounty a merged PR is paid from: the bounty of the issue it closes,
+// else a bounty attached to the PR itself, else a new one opened out of the repo pool
+// for the amount the repo admins set on-chain with set_pool_payout_amount
+// The pool bounty is keyed by the PR number, so a retry finds it instead of opening another one
+async function resolvePayoutBounty(
+  repoFullName: string,
+  prNumber: number,
+  issueNumber?: number,
+): Promise<OnChainBounty> {
+  if (issueNumber) {
+    const issueBounty = await getIssueBounty(repoFullName, issueNumber);
+    if (issueBounty?.status === "Open") return issueBounty;
+  }
+
+  const prBounty = await getIssueBounty(repoFullName, prNumber);
+  if (prBounty?.status === "Open") return prBounty;
+
+  console.log(`No bounty for ${repoFullName}#${prNumber}, opening one out of the repo pool`);
+  await getAgent().call({
+    methodName: "create_pool_bounty",
+    args: {
+      repo_id: repoFullName,
+      pr_number: prNumber,
+    },
+    gas: BigInt("30000000000000"),
+    deposit: STORAGE_DEPOSIT,
+  });
+
+  const poolBounty = await getIssueBounty(repoFullName, prNumber);
+  if (!poolBounty || poolBounty.status !== "Open") {
+    throw new Error(`No open on-chain bounty for ${repoFullName}#${prNumber}`);
+  }
+  return poolBounty;
+}
+
+// issueNumber is the issue the PR closes, its bounty is paid if it has one
+// amount defaults to the whole escrow of the bounty that is paid
+interface HoldBountyInput {
+  repoFullName: string;
+  githubUserId: number;
+  prNumber: number;
+  issueNumber?: number;
+  mergeCommitSha: string;
+  amount?: string;
+}
+
+// Hold the bounty on-chain for a contributor who has not linked a wallet yet
+export async function holdBountyForGithubUser(
+  input: HoldBountyInput,
+): Promise<{ success: boolean; amount?: string; error?: string }> {
+  const { repoFullName, githubUserId, prNumber, mergeCommitSha } = input;
+  try {
+    const payout = await getPayout(repoFullName, prNumber);
+    if (payout && payout.merge_commit_sha === mergeCommitSha) {
+      return { success: true, amount: fromYocto(payout.amount) };
+    }
+
+    const bounty = await resolvePayoutBounty(repoFullName, prNumber, input.issueNumber);
+    const amountYocto = input.amount ? toYocto(input.amount) : bounty.amount;
+
+    await getAgent().call({
+      methodName: "release_bounty_to_github_user",
+      args: {
+        bounty_id: bounty.bounty_id,
+        github_user_id: githubUserId,
+        amount: amountYocto,
+        pr_number: prNumber,
+        merge_commit_sha: mergeCommitSha,
+      },
+      gas: BigInt("30000000000000"),
+      deposit: STORAGE_DEPOSIT,
+    });
+    console.log(`Held bounty for GitHub user ${githubUserId}: ${repoFullName}#${prNumber}`);
+    return { success: true, amount: fromYocto(amountYocto) };
+  } catch (error: any) {
+    const message = error?.message ? String(error.message) : "Unknown error";
+    console.error("Failed to hold bounty:", error);
+    return { success: false, error: message };
+  }
+}
+
+// With a githubUserId the contract pays the account linked to it in the registry
+// issueNumber and amount work as for holdBountyForGithubUser
 interface ReleaseBountyInput {
   repoFullName: string;
-  contributorWallet: string;
+  contributorWallet?: string;
+  githubUserId?: number;
   prNumber: number;
+  issueNumber?: number;
+  mergeCommitSha: string;
   amount?: string;
 }
 
 export async function releaseBounty(
   input: ReleaseBountyInput,
-): Promise<{ success: boolean; txHash?: string; error?: string }> {
-  const { repoFullName, contributorWallet, prNumber } = input;
-  const amount = input.amount || (await getBounty(repoFullName));
+): Promise<{ success: boolean; txHash?: string; amount?: string; error?: string }> {
+  const { repoFullName, githubUserId, prNumber, mergeCommitSha } = input;
+  const contributorWallet = input.contributorWallet ?? `github:${githubUserId}`;
+
+  // A redelivered webhook or a retry after a timeout finds the recorded payout
+  const payout = await getPayout(repoFullName, prNumber);
+  if (payout && payout.merge_commit_sha === mergeCommitSha) {
+    console.log(`Payout for ${repoFullName}#${prNumber} already processed, skipping`);
+    return { success: true, amount: fromYocto(payout.amount) };
+  }
 
-  console.log(
-    `Attempting bounty payout: repo=${repoFullName} pr=${prNumber} contributor=${contributorWallet} amount=${amount} NEAR`,
-  );
+  let amount = input.amount || "0";
 
   try {
-    const agent = getAgent();
-    const amountYocto = toYocto(amount);
+    const bounty = await resolvePayoutBounty(repoFullName, prNumber, input.issueNumber);
+    const amountYocto = input.amount ? toYocto(input.amount) : bounty.amount;
+    amount = fromYocto(amountYocto);
 
+    console.log(
+      `Attempting bounty payout: repo=${repoFullName} pr=${prNumber} bounty=${bounty.bounty_id} contributor=${contributorWallet} amount=${amount} NEAR`,
+    );
+
+    const agent = getAgent();
     const result: any = await agent.call({
       methodName: "release_bounty",
       args: {
-        repo_id: repoFullName,
-        recipient: contributorWallet,
+        bounty_id: bounty.bounty_id,
+        recipient: input.contributorWallet ?? null,
+        github_user_id: githubUserId ?? null,
         amount: amountYocto,
+        pr_number: prNumber,
+        merge_commit_sha: mergeCommitSha,
       },
       gas: BigInt("100000000000000"),
+      deposit: STORAGE_DEPOSIT,
     });
 
     if (process.env.DEBUG === "true") {
@@ -111,7 +321,7 @@ export async function releaseBounty(
       timestamp: new Date().toISOString(),
     });
 
-    return { success: true, txHash };
+    return { success: true, txHash, amount };
   } catch (error: any) {
     const message = error?.message ? String(error.message) : "Unknown error";
     console.error("Bounty payout failed:", error);
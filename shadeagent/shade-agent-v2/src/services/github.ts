@@ -1,6 +1,25 @@
 import axios from "axios";
 import { ReviewResult } from "./review";
 
+const CLOSING_ISSUE_REGEX = /\b(?:close[sd]?|fix(?:e[sd])?|resolve[sd]?)\s+#(\d+)/i;
+
+// Number of the issue a PR links with a GitHub closing keyword, e.g. "Fixes #12"
+export function findClosingIssue(prBody: string): number | undefined {
+  const match = prBody.match(CLOSING_ISSUE_REGEX);
+  return match ? Number(match[1]) : undefined;
+}
+
+// Get the id of the GitHub user an OAuth token belongs to, proving the caller controls the account
+export async function getGithubUserId(token: string): Promise<number> {
+  const response = await axios.get("https://api.github.com/user", {
+    headers: {
+      Authorization: `Bearer ${token}`,
+      Accept: "application/vnd.github+json",
+    },
+  });
+  return Number(response.data.id);
+}
+
 interface PostCommentRequest {
   repoFullName: string;
   prNumber: number;